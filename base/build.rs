@@ -0,0 +1,52 @@
+//! # Build Script
+//!
+//! Records the sources of `base` and the compiler building it, so that the
+//! host can tell whether programs are compiled against the same `base` it was
+//! built with.
+
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    path::Path,
+    process::Command,
+};
+
+
+
+fn main() {
+    println!("cargo::rerun-if-changed=src");
+    println!("cargo::rerun-if-changed=Cargo.toml");
+
+    let mut hasher = DefaultHasher::new();
+    hash_path(Path::new("Cargo.toml"), &mut hasher);
+    hash_path(Path::new("src"), &mut hasher);
+
+    println!("cargo::rustc-env=BASE_SOURCE_HASH={}", hasher.finish());
+
+    let rustc = std::env::var_os("RUSTC").expect("RUSTC should be set by cargo");
+    let output = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .expect("failed to run rustc");
+    let version = String::from_utf8(output.stdout).expect("rustc version should be UTF-8");
+    println!("cargo::rustc-env=BASE_RUSTC_VERSION={}", version.trim());
+}
+
+fn hash_path(path: &Path, hasher: &mut DefaultHasher) {
+    path.hash(hasher);
+
+    if path.is_dir() {
+        let mut entries = std::fs::read_dir(path)
+            .expect("failed to read source directory")
+            .map(|entry| entry.expect("failed to read source directory").path())
+            .collect::<Vec<_>>();
+        // The order entries are read in isn't specified.
+        entries.sort();
+        for entry in entries {
+            hash_path(&entry, hasher);
+        }
+    } else {
+        std::fs::read(path)
+            .expect("failed to read source file")
+            .hash(hasher);
+    }
+}
@@ -0,0 +1,152 @@
+//! # Arena
+//!
+//! See [`Arena`] for details.
+
+/// A generational index into an [`Arena`].
+///
+/// A key stays valid until the value it points to is removed. The slot may
+/// then be reused, but with a new generation, so stale keys never alias newer
+/// values.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct ArenaKey {
+    index: u32,
+    generation: u32,
+}
+
+impl ArenaKey {
+    /// Pack this key into a single integer, with the generation in the upper
+    /// half.
+    #[inline]
+    pub(crate) const fn to_bits(self) -> u64 {
+        ((self.generation as u64) << 32) | self.index as u64
+    }
+
+    #[inline]
+    pub(crate) const fn from_bits(bits: u64) -> Self {
+        Self {
+            index: bits as u32,
+            generation: (bits >> 32) as u32,
+        }
+    }
+}
+
+/// A collection of values addressed by [generational keys](ArenaKey), with
+/// constant-time insertion, removal and lookup.
+pub(crate) struct Arena<T> {
+    slots: Vec<Slot<T>>,
+    /// The indices of the slots which are empty and can be reused.
+    free: Vec<u32>,
+}
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+}
+
+impl<T> Arena<T> {
+    /// Insert the value returned by `value`, which is given the key the value
+    /// will be stored under.
+    pub(crate) fn insert_with(&mut self, value: impl FnOnce(ArenaKey) -> T) -> ArenaKey {
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            let key = ArenaKey {
+                index,
+                generation: slot.generation,
+            };
+            slot.value = Some(value(key));
+
+            return key;
+        }
+
+        let index = u32::try_from(self.slots.len()).expect("too many values in arena");
+        let key = ArenaKey {
+            index,
+            generation: 0,
+        };
+        self.slots.push(Slot {
+            generation: 0,
+            value: Some(value(key)),
+        });
+
+        key
+    }
+
+    pub(crate) fn remove(&mut self, key: ArenaKey) -> Option<T> {
+        let slot = self.slots.get_mut(key.index as usize)?;
+        if slot.generation != key.generation {
+            return None;
+        }
+
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(key.index);
+
+        Some(value)
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, key: ArenaKey) -> Option<&T> {
+        self.slots
+            .get(key.index as usize)
+            .filter(|slot| slot.generation == key.generation)?
+            .value
+            .as_ref()
+    }
+
+    #[inline]
+    pub(crate) fn get_mut(&mut self, key: ArenaKey) -> Option<&mut T> {
+        self.slots
+            .get_mut(key.index as usize)
+            .filter(|slot| slot.generation == key.generation)?
+            .value
+            .as_mut()
+    }
+
+    /// Iterate over every value along with its key, in no particular order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (ArenaKey, &T)> {
+        self.slots.iter().zip(0..).filter_map(|(slot, index)| {
+            let key = ArenaKey {
+                index,
+                generation: slot.generation,
+            };
+
+            Some((key, slot.value.as_ref()?))
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_keys_are_rejected() {
+        let mut arena = Arena::default();
+        let a = arena.insert_with(|_| "a");
+        assert_eq!(arena.remove(a), Some("a"));
+
+        // The slot is reused, but the old key doesn't point to the new value.
+        let b = arena.insert_with(|key| {
+            assert_eq!(key.index, a.index);
+            "b"
+        });
+        assert_eq!(a.index, b.index);
+        assert_eq!(arena.get(a), None);
+        assert_eq!(arena.remove(a), None);
+        assert_eq!(arena.get(b), Some(&"b"));
+        assert_eq!(ArenaKey::from_bits(b.to_bits()), b);
+        assert_ne!(a.to_bits(), b.to_bits());
+        assert_eq!(arena.iter().collect::<Vec<_>>(), vec![(b, &"b")]);
+    }
+}
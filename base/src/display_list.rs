@@ -0,0 +1,383 @@
+//! # Display List
+//!
+//! See [`DisplayList`] for details.
+
+use std::collections::HashMap;
+
+use crate::{
+    Area, ObjectNodeMut, ObjectNodeRef, ObjectTree, Point, RenderPass, Renderer, Rgba, Size,
+    SpanKind,
+};
+
+
+
+/// A recording of everything drawn by the [objects](crate::Object) in an
+/// [`ObjectTree`], which can be replayed without calling into the objects
+/// again.
+///
+/// See [`render_pass_damaged`].
+#[derive(Default)]
+pub struct DisplayList {
+    entries: HashMap<u64, DisplayEntry>,
+    order: Vec<DisplayItem>,
+}
+
+#[derive(Default)]
+struct DisplayEntry {
+    render: Vec<DrawCommand>,
+    overlay: Vec<DrawCommand>,
+}
+
+/// A reference to one of an object's recorded command lists, in paint order.
+#[derive(Clone, Copy)]
+enum DisplayItem {
+    Render(u64),
+    Overlay(u64),
+}
+
+impl DisplayList {
+    /// Whether nothing has been recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// Draw every recorded command with the provided renderer.
+    pub fn replay(&self, renderer: &mut dyn Renderer) {
+        for item in &self.order {
+            let commands = match *item {
+                DisplayItem::Render(id) => &self.entries[&id].render,
+                DisplayItem::Overlay(id) => &self.entries[&id].overlay,
+            };
+            for command in commands {
+                command.draw(renderer);
+            }
+        }
+    }
+}
+
+/// A single call to a [`Renderer`] method.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DrawCommand {
+    Text {
+        content: String,
+        position: Point,
+        font_size: f32,
+        color: Rgba,
+    },
+    Quad {
+        position: Point,
+        size: Size,
+        color: Rgba,
+    },
+    Image {
+        texture_id: u64,
+        position: Point,
+        size: Size,
+    },
+    ImageRegion {
+        texture_id: u64,
+        position: Point,
+        size: Size,
+        uv: Area,
+        tint: Rgba,
+    },
+    Icon {
+        texture_id: u64,
+        position: Point,
+        size: Size,
+        color: Rgba,
+    },
+}
+
+impl DrawCommand {
+    pub fn draw(&self, renderer: &mut dyn Renderer) {
+        match self {
+            Self::Text {
+                content,
+                position,
+                font_size,
+                color,
+            } => renderer.text(content, *position, *font_size, *color),
+            Self::Quad {
+                position,
+                size,
+                color,
+            } => renderer.quad(*position, *size, *color),
+            Self::Image {
+                texture_id,
+                position,
+                size,
+            } => renderer.image(*texture_id, *position, *size),
+            Self::ImageRegion {
+                texture_id,
+                position,
+                size,
+                uv,
+                tint,
+            } => renderer.image_region(*texture_id, *position, *size, *uv, *tint),
+            Self::Icon {
+                texture_id,
+                position,
+                size,
+                color,
+            } => renderer.icon(*texture_id, *position, *size, *color),
+        }
+    }
+}
+
+/// A [`Renderer`] that records its calls into a list of [draw
+/// commands](DrawCommand).
+struct Recorder<'a>(&'a mut Vec<DrawCommand>);
+
+impl Renderer for Recorder<'_> {
+    fn text(&mut self, content: &str, position: Point, font_size: f32, color: Rgba) {
+        self.0.push(DrawCommand::Text {
+            content: content.to_string(),
+            position,
+            font_size,
+            color,
+        });
+    }
+
+    fn quad(&mut self, position: Point, size: Size, color: Rgba) {
+        self.0.push(DrawCommand::Quad {
+            position,
+            size,
+            color,
+        });
+    }
+
+    fn image(&mut self, texture_id: u64, position: Point, size: Size) {
+        self.0.push(DrawCommand::Image {
+            texture_id,
+            position,
+            size,
+        });
+    }
+
+    fn image_region(&mut self, texture_id: u64, position: Point, size: Size, uv: Area, tint: Rgba) {
+        self.0.push(DrawCommand::ImageRegion {
+            texture_id,
+            position,
+            size,
+            uv,
+            tint,
+        });
+    }
+
+    fn icon(&mut self, texture_id: u64, position: Point, size: Size, color: Rgba) {
+        self.0.push(DrawCommand::Icon {
+            texture_id,
+            position,
+            size,
+            color,
+        });
+    }
+}
+
+
+
+/// Re-render only the objects that requested a repaint or that intersect an
+/// area damaged since the last call, updating the provided [`DisplayList`].
+///
+/// Returns `false` if nothing was damaged, in which case the display list was
+/// left untouched.
+pub fn render_pass_damaged(tree: &mut ObjectTree, display_list: &mut DisplayList) -> bool {
+    let mut damage = std::mem::take(&mut tree.interaction.damage);
+    collect_paint_requests(tree.root_node(), &mut damage);
+
+    if damage.is_empty() && !display_list.is_empty() {
+        return false;
+    }
+
+    let span = tree.interaction.profiler.begin(SpanKind::RenderPass, None);
+    let mut order = Vec::with_capacity(display_list.order.len());
+    record_object(
+        tree.root_node_mut().node_mut(),
+        &damage,
+        display_list,
+        &mut order,
+    );
+
+    // Forget about objects that are no longer in the tree.
+    let mut live_ids = std::collections::HashSet::new();
+    for item in &order {
+        if let DisplayItem::Render(id) = item {
+            live_ids.insert(*id);
+        }
+    }
+    display_list.entries.retain(|id, _| live_ids.contains(id));
+    display_list.order = order;
+    tree.interaction.profiler.end(span);
+
+    true
+}
+
+fn collect_paint_requests(node: ObjectNodeRef<'_>, damage: &mut Vec<Area>) {
+    if node.state.needs_paint {
+        damage.push(node.state.global_area);
+    }
+    for child_id in node.object.children_ids() {
+        collect_paint_requests(
+            node.children
+                .get(child_id)
+                .expect("Object::children_ids produced an invalid child ID"),
+            damage,
+        );
+    }
+}
+
+fn record_object(
+    mut node: ObjectNodeMut<'_>,
+    damage: &[Area],
+    display_list: &mut DisplayList,
+    order: &mut Vec<DisplayItem>,
+) {
+    let object = &**node.object;
+    let state = &mut node.state;
+    let mut children = node.children;
+    let id = state.id;
+
+    let dirty = state.needs_paint
+        || !display_list.entries.contains_key(&id)
+        || damage.iter().any(|area| area.intersects(state.global_area));
+    state.needs_paint = false;
+
+    order.push(DisplayItem::Render(id));
+    let span = dirty.then(|| {
+        children
+            .interaction
+            .profiler
+            .begin(SpanKind::Render, Some(id))
+    });
+    if dirty {
+        let entry = display_list.entries.entry(id).or_default();
+        entry.render.clear();
+        object.render(&mut RenderPass { state }, &mut Recorder(&mut entry.render));
+    }
+
+    let parent_state = &mut *state;
+    crate::for_each_child_object(object, children.reborrow_mut(), |mut node| {
+        record_object(node.reborrow_mut(), damage, display_list, order);
+        parent_state.merge_with_child(node.state);
+    });
+
+    order.push(DisplayItem::Overlay(id));
+    if dirty {
+        let entry = display_list.entries.entry(id).or_default();
+        entry.overlay.clear();
+        object.render_overlay(&mut RenderPass { state }, &mut Recorder(&mut entry.overlay));
+    }
+    if let Some(span) = span {
+        children.interaction.profiler.end(span);
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use {
+        super::*,
+        crate::{
+            Axis, EventPass, Flex, LengthRequest, MeasurePass, Object, ObjectBuilder,
+            PointerButton, PointerEvent,
+        },
+    };
+
+    /// A square that counts how many times it has been rendered, and requests a
+    /// repaint when clicked.
+    struct Painted(Rc<Cell<u32>>);
+
+    impl Object for Painted {
+        fn measure(
+            &mut self,
+            _pass: &mut MeasurePass<'_>,
+            _axis: Axis,
+            _length_request: LengthRequest,
+            _cross_length: Option<f32>,
+        ) -> f32 {
+            10.0
+        }
+
+        fn render(&self, pass: &mut RenderPass<'_>, renderer: &mut dyn Renderer) {
+            self.0.set(self.0.get() + 1);
+            renderer.quad(pass.position(), pass.size(), Rgba::rgb(0xff, 0, 0));
+        }
+
+        fn on_pointer_event(&mut self, pass: &mut EventPass<'_>, event: &PointerEvent) {
+            if let PointerEvent::Down { .. } = event {
+                pass.request_paint();
+            }
+        }
+    }
+
+    #[test]
+    fn only_damaged_objects_are_rerendered() {
+        let a_count = Rc::new(Cell::new(0));
+        let b_count = Rc::new(Cell::new(0));
+        let mut tree = ObjectTree::new(Box::new(
+            Flex::row()
+                .with(Painted(a_count.clone()), 0.0)
+                .with(Painted(b_count.clone()), 0.0),
+        ))
+        .unwrap();
+        tree.resize(Size::new(100.0, 100.0), &mut ());
+
+        let mut display_list = DisplayList::default();
+        assert!(render_pass_damaged(&mut tree, &mut display_list));
+        assert_eq!((a_count.get(), b_count.get()), (1, 1));
+
+        // Nothing changed, so nothing is re-rendered.
+        assert!(!render_pass_damaged(&mut tree, &mut display_list));
+        assert_eq!((a_count.get(), b_count.get()), (1, 1));
+
+        // Clicking `a` makes it request a repaint, which shouldn't touch `b`.
+        let button = PointerButton::Primary;
+        let position = Some(Point::new(5.0, 50.0));
+        tree.handle_pointer_event(PointerEvent::Move { position }, &mut ());
+        tree.handle_pointer_event(PointerEvent::Down { button }, &mut ());
+        assert!(render_pass_damaged(&mut tree, &mut display_list));
+        assert_eq!((a_count.get(), b_count.get()), (2, 1));
+
+        // Both squares are still replayed.
+        let mut commands = Vec::new();
+        display_list.replay(&mut Recorder(&mut commands));
+        assert_eq!(commands.len(), 2);
+    }
+
+    #[test]
+    fn hover_changes_and_mutable_access_repaint() {
+        let a_count = Rc::new(Cell::new(0));
+        let b_count = Rc::new(Cell::new(0));
+        let mut tree = ObjectTree::new(Box::new(
+            Flex::row()
+                .with_builder(
+                    ObjectBuilder::new(Painted(a_count.clone())).with_key("a"),
+                    0.0,
+                )
+                .with(Painted(b_count.clone()), 0.0),
+        ))
+        .unwrap();
+        tree.resize(Size::new(100.0, 100.0), &mut ());
+        let mut display_list = DisplayList::default();
+        render_pass_damaged(&mut tree, &mut display_list);
+
+        // Hovering `b` repaints it without it asking to be, and leaves `a` and
+        // their parent alone.
+        let position = Some(Point::new(15.0, 50.0));
+        tree.handle_pointer_event(PointerEvent::Move { position }, &mut ());
+        assert!(render_pass_damaged(&mut tree, &mut display_list));
+        assert_eq!((a_count.get(), b_count.get()), (1, 2));
+
+        // Looking `a` up doesn't repaint it, until a repaint is requested.
+        let a_id = tree.find_by_key("a").unwrap().state.id();
+        tree.find_mut(a_id).unwrap();
+        assert!(!render_pass_damaged(&mut tree, &mut display_list));
+        tree.request_paint(a_id);
+        assert!(render_pass_damaged(&mut tree, &mut display_list));
+        assert_eq!(a_count.get(), 2);
+    }
+}
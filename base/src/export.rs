@@ -0,0 +1,112 @@
+//! # Exporting Programs
+//!
+//! The host loads programs through a few symbols, which programs export with
+//! [`export_program!`]. The signatures of those symbols are defined here, so
+//! that the host and the macro can't disagree on them.
+
+use crate::*;
+
+
+
+/// The name of the symbol programs which only build a view export their
+/// [`ViewFn`] as.
+pub const VIEW_SYMBOL: &[u8] = b"view";
+
+/// The name of the symbol programs with a [`ProgramState`] export its
+/// [`NewStateFn`] as.
+pub const PROGRAM_STATE_SYMBOL: &[u8] = b"__program_state";
+
+/// The signature of the `view` function of programs without a state.
+pub type ViewFn = extern "Rust" fn(&mut dyn ViewContext) -> Box<dyn Object>;
+
+/// The signature of the constructor of a program's [`ProgramState`].
+pub type NewStateFn = extern "Rust" fn() -> Box<dyn ProgramState>;
+
+/// Export the symbols the host loads a program through: its
+/// [manifest](ProgramManifest), along with either its [state](ProgramState)
+/// or its view.
+///
+/// ```ignore
+/// // The expression is evaluated whenever the program is loaded.
+/// base::export_program!(state: MyState::default());
+/// ```
+///
+/// ```ignore
+/// fn view(context: &mut dyn ViewContext) -> Box<dyn Object> {
+///     Box::new(Label::new("Hello!"))
+/// }
+///
+/// base::export_program!(view: view);
+/// ```
+///
+/// The entry point is checked against the signature the host expects, so
+/// that a mismatch fails to compile rather than to load.
+#[macro_export]
+macro_rules! export_program {
+    (state: $new_state:expr $(,)?) => {
+        $crate::export_program!(@manifest);
+
+        #[unsafe(export_name = "__program_state")]
+        pub extern "Rust" fn __program_state() -> ::std::boxed::Box<dyn $crate::ProgramState> {
+            ::std::boxed::Box::new($new_state)
+        }
+
+        const _: $crate::NewStateFn = __program_state;
+    };
+    (view: $view:expr $(,)?) => {
+        $crate::export_program!(@manifest);
+
+        #[unsafe(export_name = "view")]
+        pub extern "Rust" fn __program_view(
+            context: &mut dyn $crate::ViewContext,
+        ) -> ::std::boxed::Box<dyn $crate::Object> {
+            let view: fn(&mut dyn $crate::ViewContext) -> ::std::boxed::Box<dyn $crate::Object> =
+                $view;
+            view(context)
+        }
+
+        const _: $crate::ViewFn = __program_view;
+    };
+    (@manifest) => {
+        #[unsafe(no_mangle)]
+        pub static __PROGRAM_MANIFEST: $crate::ProgramManifest = $crate::PROGRAM_MANIFEST;
+    };
+}
+
+
+
+#[cfg(test)]
+// Exporting symbols is unsafe.
+#[allow(unsafe_code)]
+mod tests {
+    use super::*;
+
+    struct NoTextures;
+
+    impl ViewContext for NoTextures {
+        fn load_texture(&mut self, _path: &str) -> u64 {
+            0
+        }
+
+        fn embed_asset(&mut self, _path: &str, _content: &[u8]) {}
+
+        fn read_view(&mut self, _path: &str) -> Result<String, String> {
+            Err("no views".into())
+        }
+    }
+
+    fn view(_context: &mut dyn ViewContext) -> Box<dyn Object> {
+        Box::new(Label::new("Exported"))
+    }
+
+    export_program!(view: view);
+
+    #[test]
+    fn exported_view_calls_the_provided_function() {
+        assert_eq!(__PROGRAM_MANIFEST.layout_hash, PROGRAM_MANIFEST.layout_hash);
+
+        let view: ViewFn = __program_view;
+        let object = view(&mut NoTextures);
+        assert!(object.debug_name().ends_with("Label"));
+    }
+}
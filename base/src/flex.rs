@@ -1,8 +1,8 @@
 //! # Flex Layout
 
 use crate::{
-    Axis, ChildObject, LayoutPass, Length, LengthRequest, MeasurePass, Object, ObjectBuilder,
-    Point, Size,
+    Area, Axis, ChildObject, LayoutPass, LayoutRegion, LayoutRegionKind, Length, LengthRequest,
+    MeasurePass, Object, ObjectBuilder, Point, Size,
 };
 
 
@@ -13,6 +13,9 @@ pub struct Flex {
     cross_alignment: CrossAlignment,
     elements: Vec<FlexElement>,
     gap: f32,
+
+    /// The gaps and spacers from the last layout, for the layout overlay.
+    regions: Vec<LayoutRegion>,
 }
 
 enum FlexElement {
@@ -41,6 +44,7 @@ impl Flex {
             cross_alignment: CrossAlignment::Center,
             elements: Vec::new(),
             gap: 0.0,
+            regions: Vec::new(),
         }
     }
 
@@ -54,10 +58,14 @@ impl Flex {
         Self::new(Axis::Vertical)
     }
 
-    pub fn with(mut self, child: impl Object + 'static, params: impl Into<FlexParams>) -> Self {
+    pub fn with(self, child: impl Object + 'static, params: impl Into<FlexParams>) -> Self {
+        self.with_builder(ObjectBuilder::new(child), params)
+    }
+
+    pub fn with_builder(mut self, child: ObjectBuilder, params: impl Into<FlexParams>) -> Self {
         let params = params.into();
         self.elements.push(FlexElement::Child {
-            object: ObjectBuilder::new(child).into_child(),
+            object: child.into_child(),
             alignment: params.alignment,
             flex: params.flex,
             basis: params.basis,
@@ -97,7 +105,7 @@ impl Object for Flex {
         self.elements
             .iter()
             .filter_map(|element| match element {
-                FlexElement::Child { object, .. } => Some(object.id()),
+                FlexElement::Child { object, .. } => object.id(),
                 FlexElement::Spacer { .. } => None,
             })
             .collect()
@@ -136,12 +144,8 @@ impl Object for Flex {
                     _ => Length::FitContent(cross_space),
                 };
 
-                let child_cross_length = pass.resolve_length(
-                    child.id(),
-                    cross_axis,
-                    cross_auto,
-                    Some(child_main_length),
-                );
+                let child_cross_length =
+                    pass.resolve_length(child, cross_axis, cross_auto, Some(child_main_length));
 
                 main_axis.pack_size(child_main_length, child_cross_length)
             };
@@ -149,21 +153,25 @@ impl Object for Flex {
             |pass: &mut LayoutPass<'_>, child: &mut ChildObject, child_size: Size| {
                 pass.do_layout(child, child_size);
 
-                let baseline = pass.expect_child(child.id()).state.layout_baseline_offset;
-                let ascent = child_size.height - baseline;
+                let baseline = pass
+                    .expect_child(child_id(child))
+                    .state
+                    .layout_baseline_offset;
+                let ascent = child_size.height - baseline.unwrap_or(0.0);
                 max_ascent = max_ascent.max(ascent);
             };
         let mut place_child =
             |pass: &mut LayoutPass<'_>, child: &mut ChildObject, child_origin: Point| {
                 pass.place_child(child, child_origin);
 
-                let child_node = pass.expect_child(child.id());
-                let child_size = child_node.state.layout_area.size;
-                let child_baseline = child_node.state.layout_baseline_offset;
-                let child_bottom = child_origin.y + child_size.height;
-                let bottom_gap = size.height - child_bottom;
-                let baseline = child_baseline + bottom_gap;
-                lowest_baseline = lowest_baseline.min(baseline);
+                // Use the placed area, since placement rounds the origin.
+                let child_node = pass.expect_child(child_id(child));
+                if let Some(child_baseline) = child_node.state.layout_baseline_offset {
+                    let child_bottom = child_node.state.layout_area.max_point().y;
+                    let bottom_gap = size.height - child_bottom;
+                    let baseline = child_baseline + bottom_gap;
+                    lowest_baseline = lowest_baseline.min(baseline);
+                }
             };
 
         // Add up flex factors, resolve bases, subtract bases from main space, and lay
@@ -182,7 +190,7 @@ impl Object for Flex {
                             // Basis is always resolved with a `MaxContent` fallback.
                             let main_fallback = Length::MaxContent;
                             *resolved_basis = pass.resolve_length(
-                                object.id(),
+                                object,
                                 main_axis,
                                 main_fallback,
                                 Some(cross_space),
@@ -272,7 +280,15 @@ impl Object for Flex {
         // Distribute free space and place children.
         let mut main_offset = space_before;
         let mut previous_was_object = false;
-        for child in &mut self.elements {
+        let element_count = self.elements.len();
+        let region_area = |offset: f32, length: f32| {
+            Area::new(
+                main_axis.pack_point(offset, 0.0),
+                main_axis.pack_size(length, cross_space),
+            )
+        };
+        self.regions.clear();
+        for (index, child) in self.elements.iter_mut().enumerate() {
             match child {
                 FlexElement::Child {
                     object, alignment, ..
@@ -281,13 +297,13 @@ impl Object for Flex {
                         main_offset += space_between;
                     }
 
-                    let child_node = pass.expect_child(object.id());
+                    let child_node = pass.expect_child(child_id(object));
                     let child_size = child_node.state.layout_area.size;
                     let alignment = alignment.unwrap_or(self.cross_alignment);
                     let child_origin_cross = match alignment {
                         CrossAlignment::Baseline if main_axis == Axis::Horizontal => {
                             let baseline = child_node.state.layout_baseline_offset;
-                            let ascent = child_size.height - baseline;
+                            let ascent = child_size.height - baseline.unwrap_or(0.0);
                             max_ascent - ascent
                         }
                         _ => {
@@ -300,33 +316,40 @@ impl Object for Flex {
                     place_child(pass, object, child_origin);
 
                     main_offset += child_size.value_for_axis(main_axis);
-                    main_offset += gap_length;
                     previous_was_object = true;
                 }
                 FlexElement::Spacer {
                     resolved_length, ..
                 } => {
+                    self.regions.push(LayoutRegion {
+                        kind: LayoutRegionKind::Spacer,
+                        area: region_area(main_offset, *resolved_length),
+                    });
                     main_offset += *resolved_length;
-                    main_offset += gap_length;
                     previous_was_object = false;
                 }
             }
-        }
 
-        // If we have at least one child then we can use the lowest child
-        // baseline.
+            if index + 1 < element_count && gap_length > 0.0 {
+                self.regions.push(LayoutRegion {
+                    kind: LayoutRegionKind::Gap,
+                    area: region_area(main_offset, gap_length),
+                });
+            }
+            main_offset += gap_length;
+        }
 
-        // let baseline = self
-        //     .elements
-        //     .iter()
-        //     .any(|element| matches!(element, FlexElement::Child { .. }))
-        //     .then_some(lowest_baseline);
+        // If at least one child has a baseline then we can use the lowest
+        // child baseline.
+        if lowest_baseline.is_finite() {
+            pass.set_baseline_offset(lowest_baseline);
+        } else {
+            pass.clear_baseline_offset();
+        }
+    }
 
-        // if let Some(baseline) = baseline {
-        //     pass.set_baseline_offset(baseline);
-        // } else {
-        //     pass.clear_baseline_offset();
-        // }
+    fn layout_regions(&self, regions: &mut Vec<LayoutRegion>) {
+        regions.extend_from_slice(&self.regions);
     }
 
     fn measure(
@@ -373,12 +396,8 @@ impl Object for Flex {
                         ..
                     } => match effective_basis(*basis, *flex) {
                         FlexBasis::Auto => {
-                            *resolved_basis = pass.resolve_length(
-                                object.id(),
-                                main_axis,
-                                main_fallback,
-                                cross_space,
-                            );
+                            *resolved_basis =
+                                pass.resolve_length(object, main_axis, main_fallback, cross_space);
                         }
                         FlexBasis::Zero => {
                             *resolved_basis = 0.0;
@@ -418,7 +437,7 @@ impl Object for Flex {
                                 }
                                 FlexBasis::Zero => {
                                     let child_length = pass.resolve_length(
-                                        object.id(),
+                                        object,
                                         main_axis,
                                         main_fallback,
                                         cross_space,
@@ -511,12 +530,8 @@ impl Object for Flex {
                             .map(|flex_fraction| *resolved_basis + *flex * flex_fraction);
                         let cross_auto = length_request.into();
 
-                        let child_cross_length = pass.resolve_length(
-                            object.id(),
-                            cross_axis,
-                            cross_auto,
-                            child_main_length,
-                        );
+                        let child_cross_length =
+                            pass.resolve_length(object, cross_axis, cross_auto, child_main_length);
 
                         length = length.max(child_cross_length);
                     }
@@ -563,6 +578,14 @@ impl From<f32> for FlexParams {
 
 
 
+/// The ID of a child, which is always instantiated during the update pass
+/// before it's laid out.
+fn child_id(child: &ChildObject) -> u64 {
+    child
+        .id()
+        .expect("flex children are instantiated before layout")
+}
+
 fn effective_basis(basis: Option<FlexBasis>, flex: f32) -> FlexBasis {
     basis.unwrap_or(if flex == 0.0 {
         FlexBasis::Auto
@@ -651,3 +674,215 @@ impl CrossAlignment {
         }
     }
 }
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use {
+        super::*,
+        crate::{EventPass, Label, MeasureContext, ObjectTree, PointerButton, PointerEvent},
+    };
+
+    /// An object that counts how many times it has been measured, and requests
+    /// layout when clicked.
+    struct Counter(Rc<Cell<u32>>);
+
+    impl Object for Counter {
+        fn measure(
+            &mut self,
+            _pass: &mut MeasurePass<'_>,
+            _axis: Axis,
+            _length_request: LengthRequest,
+            _cross_length: Option<f32>,
+        ) -> f32 {
+            self.0.set(self.0.get() + 1);
+            10.0
+        }
+
+        fn on_pointer_event(&mut self, pass: &mut EventPass<'_>, event: &PointerEvent) {
+            if let PointerEvent::Down { .. } = event {
+                pass.request_layout();
+            }
+        }
+    }
+
+    fn nested_flex(depth: usize, innermost: Counter) -> Flex {
+        let mut flex = Flex::column().with(innermost, 0.0);
+        for level in 0..depth {
+            let outer = if level % 2 == 0 {
+                Flex::row()
+            } else {
+                Flex::column()
+            };
+            flex = outer
+                .with(flex, 0.0)
+                .with(Counter(Rc::new(Cell::new(0))), 1.0);
+        }
+
+        flex
+    }
+
+    #[test]
+    fn nested_measure_count_is_bounded() {
+        let measure_counts = (1..=12)
+            .map(|depth| {
+                let count = Rc::new(Cell::new(0));
+                let mut tree =
+                    ObjectTree::new(Box::new(nested_flex(depth, Counter(count.clone())))).unwrap();
+                tree.resize(Size::new(500.0, 500.0), &mut ());
+                let first_layout = count.get();
+
+                // A second layout of the whole tree is answered from the cache.
+                tree.root_node_mut().node_mut().state.request_layout();
+                crate::layout_pass(&mut tree, &mut ());
+                assert_eq!(count.get(), first_layout);
+
+                first_layout
+            })
+            .collect::<Vec<_>>();
+
+        // The innermost object is only measured once per distinct request, no
+        // matter how deeply it's nested.
+        assert_eq!(measure_counts[0], 4);
+        assert!(measure_counts[1..].iter().all(|count| *count == 5));
+    }
+
+    #[test]
+    fn request_layout_invalidates_measurements() {
+        let a_count = Rc::new(Cell::new(0));
+        let b_count = Rc::new(Cell::new(0));
+        let mut tree = ObjectTree::new(Box::new(
+            Flex::row()
+                .with(Counter(a_count.clone()), 0.0)
+                .with_builder(
+                    ObjectBuilder::new(Counter(b_count.clone())).with_key("b"),
+                    0.0,
+                ),
+        ))
+        .unwrap();
+        tree.resize(Size::new(100.0, 100.0), &mut ());
+        let (a_measured, b_measured) = (a_count.get(), b_count.get());
+
+        // Re-laying out the parent alone reuses both children's measurements.
+        tree.root_node_mut().node_mut().state.request_layout();
+        crate::layout_pass(&mut tree, &mut ());
+        assert_eq!((a_count.get(), b_count.get()), (a_measured, b_measured));
+
+        // Clicking `a` makes it request layout, which should only remeasure `a`.
+        let button = PointerButton::Primary;
+        let position = Some(Point::new(5.0, 50.0));
+        tree.handle_pointer_event(PointerEvent::Move { position }, &mut ());
+        tree.handle_pointer_event(PointerEvent::Down { button }, &mut ());
+        assert!(a_count.get() > a_measured);
+        assert_eq!(b_count.get(), b_measured);
+
+        // Looking `b` up doesn't remeasure it, until layout is requested.
+        let a_measured = a_count.get();
+        let b_id = tree.find_by_key("b").unwrap().state.id();
+        tree.find_mut(b_id).unwrap();
+        crate::layout_pass(&mut tree, &mut ());
+        assert_eq!(b_count.get(), b_measured);
+        tree.request_layout(b_id);
+        crate::layout_pass(&mut tree, &mut ());
+        assert!(b_count.get() > b_measured);
+        assert_eq!(a_count.get(), a_measured);
+    }
+
+    /// An object measured from the size of texture 1.
+    struct Textured(Rc<Cell<u32>>);
+
+    impl Object for Textured {
+        fn measure(
+            &mut self,
+            pass: &mut MeasurePass<'_>,
+            axis: Axis,
+            _length_request: LengthRequest,
+            _cross_length: Option<f32>,
+        ) -> f32 {
+            self.0.set(self.0.get() + 1);
+            pass.measure_context()
+                .texture_size(1)
+                .map_or(0.0, |size| size.value_for_axis(axis))
+        }
+    }
+
+    struct Textures(Option<Size>);
+
+    impl MeasureContext for Textures {
+        fn text_size(&mut self, content: &str, font_size: f32) -> Size {
+            ().text_size(content, font_size)
+        }
+
+        fn texture_size(&mut self, texture_id: u64) -> Option<Size> {
+            self.0.filter(|_| texture_id == 1)
+        }
+    }
+
+    #[test]
+    fn texture_changes_invalidate_dependent_measurements() {
+        let textured_count = Rc::new(Cell::new(0));
+        let other_count = Rc::new(Cell::new(0));
+        let mut tree = ObjectTree::new(Box::new(
+            Flex::row()
+                .with_builder(
+                    ObjectBuilder::new(Textured(textured_count.clone())).with_key("textured"),
+                    0.0,
+                )
+                .with(Counter(other_count.clone()), 0.0),
+        ))
+        .unwrap();
+        let mut textures = Textures(None);
+        tree.resize(Size::new(100.0, 100.0), &mut textures);
+        let (textured_measured, other_measured) = (textured_count.get(), other_count.get());
+        let width = |tree: &ObjectTree| {
+            let state = tree.find_by_key("textured").unwrap().state;
+            state.layout_area().size.width
+        };
+        assert_eq!(width(&tree), 0.0);
+
+        // Other textures don't affect anything.
+        textures.0 = Some(Size::new(20.0, 20.0));
+        tree.invalidate_textures(&[2], &mut textures);
+        assert_eq!(textured_count.get(), textured_measured);
+        assert_eq!(width(&tree), 0.0);
+
+        // Only the object measured from the texture is measured again.
+        tree.invalidate_textures(&[1], &mut textures);
+        assert!(textured_count.get() > textured_measured);
+        assert_eq!(other_count.get(), other_measured);
+        assert_eq!(width(&tree), 20.0);
+    }
+
+    #[test]
+    fn labels_align_by_baseline() {
+        let mut tree = ObjectTree::new(Box::new(
+            Flex::row()
+                .cross_align(CrossAlignment::Baseline)
+                .with_builder(
+                    ObjectBuilder::new(Label::new("small").font_size(20.0)).with_key("small"),
+                    0.0,
+                )
+                .with_builder(
+                    ObjectBuilder::new(Label::new("large").font_size(40.0)).with_key("large"),
+                    0.0,
+                )
+                .with(Counter(Rc::new(Cell::new(0))), 0.0),
+        ))
+        .unwrap();
+        tree.resize(Size::new(300.0, 100.0), &mut ());
+
+        let baseline_y = |key| {
+            let state = tree.find_by_key(key).unwrap().state;
+            state.area().max_point().y - state.baseline_offset().unwrap()
+        };
+        assert_eq!(baseline_y("small"), baseline_y("large"));
+
+        // Objects without a baseline don't affect the row's baseline.
+        let root = tree.root_node().state;
+        let root_baseline_y = root.area().max_point().y - root.baseline_offset().unwrap();
+        assert_eq!(root_baseline_y, baseline_y("large"));
+    }
+}
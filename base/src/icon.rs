@@ -0,0 +1,168 @@
+//! # Icon
+//!
+//! See [`Icon`] for details.
+
+use crate::{
+    Area, Axis, ImageAlignment, ImageFit, LayoutPass, LengthRequest, MeasurePass, Object,
+    RenderPass, Renderer, Rgba, Size, image::fit,
+};
+
+
+
+/// An [object](Object) that will render an icon: a texture, usually an SVG,
+/// drawn in a single [color](Rgba).
+///
+/// Icons measure to a square of their size, and the texture is scaled to fit
+/// inside it, keeping its aspect ratio.
+pub struct Icon {
+    pub texture_id: u64,
+    pub size: f32,
+    pub color: Rgba,
+    /// The size of the texture the last time the icon was measured or laid
+    /// out, if it had loaded.
+    texture_size: Option<Size>,
+}
+
+impl Icon {
+    /// Create a new icon showing the texture with the provided ID, as
+    /// returned by
+    /// [`ViewContext::load_texture`](crate::ViewContext::load_texture).
+    pub const fn new(texture_id: u64) -> Self {
+        Self {
+            texture_id,
+            size: 16.0,
+            color: Rgba::WHITE,
+            texture_size: None,
+        }
+    }
+
+    /// Defines the length of the sides of this icon.
+    pub const fn size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Defines the [color](Rgba) this icon is drawn in.
+    pub const fn color(mut self, color: Rgba) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set this icon's size to the given value.
+    pub const fn set_size(&mut self, size: f32) {
+        self.size = size;
+    }
+
+    /// Set this icon's color to the given value.
+    pub const fn set_color(&mut self, color: Rgba) {
+        self.color = color;
+    }
+}
+
+impl Object for Icon {
+    fn render(&self, pass: &mut RenderPass<'_>, renderer: &mut dyn Renderer) {
+        let area = Area::new(pass.position(), pass.size());
+        let area = match self.texture_size {
+            Some(texture_size) => {
+                fit(
+                    ImageFit::Contain,
+                    ImageAlignment::CENTER,
+                    area,
+                    Area::from_size(texture_size),
+                )
+                .destination
+            }
+            None => area,
+        };
+        renderer.icon(self.texture_id, area.position, area.size, self.color);
+    }
+
+    fn layout(&mut self, pass: &mut LayoutPass<'_>) {
+        self.texture_size = pass.measure_context().texture_size(self.texture_id);
+    }
+
+    fn measure(
+        &mut self,
+        pass: &mut MeasurePass<'_>,
+        _axis: Axis,
+        _length_request: LengthRequest,
+        _cross_length: Option<f32>,
+    ) -> f32 {
+        // The size doesn't depend on the texture, but querying it makes the
+        // icon measured and laid out again once the texture has loaded.
+        self.texture_size = pass.measure_context().texture_size(self.texture_id);
+        self.size
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{Flex, MeasureContext, ObjectTree, Point, render_pass},
+    };
+
+    struct Textures(Option<Size>);
+
+    impl MeasureContext for Textures {
+        fn text_size(&mut self, content: &str, font_size: f32) -> Size {
+            ().text_size(content, font_size)
+        }
+
+        fn texture_size(&mut self, texture_id: u64) -> Option<Size> {
+            self.0.filter(|_| texture_id == 1)
+        }
+    }
+
+    /// A [`Renderer`] that only records where icons are drawn.
+    #[derive(Default)]
+    struct IconAreas(Vec<Area>);
+
+    impl Renderer for IconAreas {
+        fn text(&mut self, _content: &str, _position: Point, _font_size: f32, _color: Rgba) {}
+
+        fn quad(&mut self, _position: Point, _size: Size, _color: Rgba) {}
+
+        fn image(&mut self, _texture_id: u64, _position: Point, _size: Size) {}
+
+        fn image_region(
+            &mut self,
+            _texture_id: u64,
+            _position: Point,
+            _size: Size,
+            _uv: Area,
+            _tint: Rgba,
+        ) {
+        }
+
+        fn icon(&mut self, _texture_id: u64, position: Point, size: Size, _color: Rgba) {
+            self.0.push(Area::new(position, size));
+        }
+    }
+
+    #[test]
+    fn late_textures_are_fitted() {
+        let mut tree =
+            ObjectTree::new(Box::new(Flex::row().with(Icon::new(1).size(20.0), 0.0))).unwrap();
+        let mut textures = Textures(None);
+        tree.resize(Size::new(100.0, 100.0), &mut textures);
+        let mut areas = IconAreas::default();
+        render_pass(&mut tree, &mut areas);
+        assert_eq!(
+            areas.0,
+            [Area::new(Point::new(0.0, 40.0), Size::new(20.0, 20.0))]
+        );
+
+        // Once the texture has loaded, it's fitted into the icon's square.
+        textures.0 = Some(Size::new(10.0, 5.0));
+        tree.invalidate_textures(&[1], &mut textures);
+        let mut areas = IconAreas::default();
+        render_pass(&mut tree, &mut areas);
+        assert_eq!(
+            areas.0,
+            [Area::new(Point::new(0.0, 45.0), Size::new(20.0, 10.0))],
+        );
+    }
+}
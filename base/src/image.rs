@@ -0,0 +1,366 @@
+//! # Image
+//!
+//! See [`Image`] for details.
+
+use crate::{
+    Area, Axis, Insets, LayoutPass, LengthRequest, MeasurePass, Object, Point, RenderPass,
+    Renderer, Rgba, Size,
+};
+
+
+
+/// An [object](Object) that will render a texture, or part of one.
+///
+/// Images measure to the size of their [source](Image::source) in pixels, and
+/// are scaled into the area they're laid out in according to their
+/// [fit](ImageFit). Until the texture has loaded, only images with an explicit
+/// source know their size.
+pub struct Image {
+    pub texture_id: u64,
+    pub fit: ImageFit,
+    pub alignment: ImageAlignment,
+    /// The part of the texture to draw, in pixels, like a single sprite of a
+    /// sprite sheet. Defaults to the whole texture.
+    pub source: Option<Area>,
+    /// The [color](Rgba) the image is multiplied by.
+    pub tint: Rgba,
+    /// The size in pixels of the borders of the source which aren't stretched
+    /// when the image is scaled, in which case the image fills its area
+    /// regardless of its fit.
+    pub nine_slice: Option<Insets>,
+    /// The size of the texture the last time the image was measured or laid
+    /// out, if it had loaded.
+    texture_size: Option<Size>,
+}
+
+impl Image {
+    /// Create a new image showing the texture with the provided ID, as
+    /// returned by
+    /// [`ViewContext::load_texture`](crate::ViewContext::load_texture).
+    pub const fn new(texture_id: u64) -> Self {
+        Self {
+            texture_id,
+            fit: ImageFit::Contain,
+            alignment: ImageAlignment::CENTER,
+            source: None,
+            tint: Rgba::WHITE,
+            nine_slice: None,
+            texture_size: None,
+        }
+    }
+
+    /// Defines how this image is scaled into its area.
+    pub const fn fit(mut self, fit: ImageFit) -> Self {
+        self.fit = fit;
+        self
+    }
+
+    /// Defines where this image is placed within its area when it doesn't
+    /// exactly fill it.
+    pub const fn alignment(mut self, alignment: ImageAlignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// Defines the part of the texture this image shows, in pixels.
+    pub const fn source(mut self, source: Area) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Defines the [color](Rgba) this image is multiplied by.
+    pub const fn tint(mut self, tint: Rgba) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    /// Scale this image by stretching the middle of its source, keeping the
+    /// provided borders intact.
+    pub const fn nine_slice(mut self, insets: Insets) -> Self {
+        self.nine_slice = Some(insets);
+        self
+    }
+
+    /// Set the part of the texture this image shows to the given value.
+    pub const fn set_source(&mut self, source: Option<Area>) {
+        self.source = source;
+    }
+
+    /// Set this image's tint to the given value.
+    pub const fn set_tint(&mut self, tint: Rgba) {
+        self.tint = tint;
+    }
+
+    fn source_area(&self) -> Option<Area> {
+        self.source.or(self.texture_size.map(Area::from_size))
+    }
+
+    fn draw_region(&self, renderer: &mut dyn Renderer, texture_size: Size, region: Region) {
+        if region.destination.size.width <= 0.0 || region.destination.size.height <= 0.0 {
+            return;
+        }
+
+        let uv = Area::new(
+            Point::new(
+                region.source.position.x / texture_size.width,
+                region.source.position.y / texture_size.height,
+            ),
+            Size::new(
+                region.source.size.width / texture_size.width,
+                region.source.size.height / texture_size.height,
+            ),
+        );
+        renderer.image_region(
+            self.texture_id,
+            region.destination.position,
+            region.destination.size,
+            uv,
+            self.tint,
+        );
+    }
+}
+
+impl Object for Image {
+    fn render(&self, pass: &mut RenderPass<'_>, renderer: &mut dyn Renderer) {
+        let area = Area::new(pass.position(), pass.size());
+        let (Some(texture_size), Some(source)) = (self.texture_size, self.source_area()) else {
+            // The renderer draws a placeholder for textures that aren't ready.
+            renderer.image(self.texture_id, area.position, area.size);
+            return;
+        };
+
+        if let Some(insets) = self.nine_slice {
+            for region in nine_slice(area, source, insets) {
+                self.draw_region(renderer, texture_size, region);
+            }
+        } else {
+            let region = fit(self.fit, self.alignment, area, source);
+            self.draw_region(renderer, texture_size, region);
+        }
+    }
+
+    fn layout(&mut self, pass: &mut LayoutPass<'_>) {
+        self.texture_size = pass.measure_context().texture_size(self.texture_id);
+    }
+
+    fn measure(
+        &mut self,
+        pass: &mut MeasurePass<'_>,
+        axis: Axis,
+        _length_request: LengthRequest,
+        cross_length: Option<f32>,
+    ) -> f32 {
+        self.texture_size = pass.measure_context().texture_size(self.texture_id);
+        let size = self.source_area().map_or(Size::ZERO, |source| source.size);
+        let length = size.value_for_axis(axis);
+        let cross = size.value_for_axis(axis.cross());
+
+        // Images which keep their aspect ratio grow along with their other axis.
+        let keeps_aspect_ratio =
+            matches!(self.fit, ImageFit::Contain | ImageFit::Cover) && self.nine_slice.is_none();
+        match cross_length {
+            Some(cross_length) if keeps_aspect_ratio && cross > 0.0 => {
+                cross_length * length / cross
+            }
+            _ => length,
+        }
+    }
+}
+
+/// How an [`Image`] is scaled into its area.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum ImageFit {
+    /// Scale the image to fit inside its area, keeping its aspect ratio.
+    #[default]
+    Contain,
+    /// Scale the image to cover its area, keeping its aspect ratio and
+    /// cropping whatever doesn't fit.
+    Cover,
+    /// Stretch the image to its area.
+    Fill,
+    /// Don't scale the image, cropping whatever doesn't fit.
+    None,
+}
+
+/// Where an [`Image`] is placed within its area, as fractions of the space
+/// left over (or cropped) along each axis. `0.0` is the top or left edge, and
+/// `1.0` the bottom or right edge.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageAlignment {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl ImageAlignment {
+    pub const TOP_LEFT: Self = Self::new(0.0, 0.0);
+    pub const TOP: Self = Self::new(0.5, 0.0);
+    pub const TOP_RIGHT: Self = Self::new(1.0, 0.0);
+    pub const LEFT: Self = Self::new(0.0, 0.5);
+    pub const CENTER: Self = Self::new(0.5, 0.5);
+    pub const RIGHT: Self = Self::new(1.0, 0.5);
+    pub const BOTTOM_LEFT: Self = Self::new(0.0, 1.0);
+    pub const BOTTOM: Self = Self::new(0.5, 1.0);
+    pub const BOTTOM_RIGHT: Self = Self::new(1.0, 1.0);
+
+    #[inline]
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+}
+
+
+
+/// A part of the source drawn into a part of the destination.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Region {
+    pub destination: Area,
+    pub source: Area,
+}
+
+pub(crate) fn fit(fit: ImageFit, alignment: ImageAlignment, area: Area, source: Area) -> Region {
+    let horizontal_scale = area.size.width / source.size.width;
+    let vertical_scale = area.size.height / source.size.height;
+    let scale = match fit {
+        ImageFit::Contain => horizontal_scale.min(vertical_scale),
+        ImageFit::Cover => horizontal_scale.max(vertical_scale),
+        ImageFit::Fill => {
+            return Region {
+                destination: area,
+                source,
+            };
+        }
+        ImageFit::None => 1.0,
+    };
+    if !scale.is_finite() || scale <= 0.0 {
+        return Region {
+            destination: Area::new(area.position, Size::ZERO),
+            source,
+        };
+    }
+
+    // Along each axis, the scaled source either leaves space in the area or is
+    // cropped to it, and the alignment decides where that happens.
+    let scaled = Size::new(source.size.width * scale, source.size.height * scale);
+    let shown = Size::new(
+        scaled.width.min(area.size.width),
+        scaled.height.min(area.size.height),
+    );
+
+    Region {
+        destination: Area::new(
+            Point::new(
+                area.position.x + (area.size.width - shown.width) * alignment.x,
+                area.position.y + (area.size.height - shown.height) * alignment.y,
+            ),
+            shown,
+        ),
+        source: Area::new(
+            Point::new(
+                source.position.x + (scaled.width - shown.width) / scale * alignment.x,
+                source.position.y + (scaled.height - shown.height) / scale * alignment.y,
+            ),
+            Size::new(shown.width / scale, shown.height / scale),
+        ),
+    }
+}
+
+fn nine_slice(area: Area, source: Area, insets: Insets) -> impl Iterator<Item = Region> {
+    // The borders keep their size, unless the area is too small to fit them.
+    let scale = (area.size.width / (insets.left + insets.right))
+        .min(area.size.height / (insets.top + insets.bottom))
+        .min(1.0);
+
+    let edges = |start: f32, length: f32, before: f32, after: f32| {
+        [
+            start,
+            start + before,
+            start + length - after,
+            start + length,
+        ]
+    };
+    let destination_x = edges(
+        area.position.x,
+        area.size.width,
+        insets.left * scale,
+        insets.right * scale,
+    );
+    let destination_y = edges(
+        area.position.y,
+        area.size.height,
+        insets.top * scale,
+        insets.bottom * scale,
+    );
+    let source_x = edges(
+        source.position.x,
+        source.size.width,
+        insets.left,
+        insets.right,
+    );
+    let source_y = edges(
+        source.position.y,
+        source.size.height,
+        insets.top,
+        insets.bottom,
+    );
+
+    (0..3).flat_map(move |row| {
+        (0..3).map(move |column| Region {
+            destination: Area::from_min_max(
+                Point::new(destination_x[column], destination_y[row]),
+                Point::new(destination_x[column + 1], destination_y[row + 1]),
+            ),
+            source: Area::from_min_max(
+                Point::new(source_x[column], source_y[row]),
+                Point::new(source_x[column + 1], source_y[row + 1]),
+            ),
+        })
+    })
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(x: f32, y: f32, width: f32, height: f32) -> Area {
+        Area::new(Point::new(x, y), Size::new(width, height))
+    }
+
+    #[test]
+    fn fit_modes_place_and_crop_the_source() {
+        let target = area(0.0, 0.0, 100.0, 50.0);
+        let source = area(0.0, 0.0, 20.0, 20.0);
+
+        let contain = fit(ImageFit::Contain, ImageAlignment::CENTER, target, source);
+        assert_eq!(contain.destination, area(25.0, 0.0, 50.0, 50.0));
+        assert_eq!(contain.source, source);
+
+        let cover = fit(ImageFit::Cover, ImageAlignment::TOP, target, source);
+        assert_eq!(cover.destination, target);
+        assert_eq!(cover.source, area(0.0, 0.0, 20.0, 10.0));
+
+        let none = fit(ImageFit::None, ImageAlignment::BOTTOM_RIGHT, target, source);
+        assert_eq!(none.destination, area(80.0, 30.0, 20.0, 20.0));
+
+        let fill = fit(ImageFit::Fill, ImageAlignment::CENTER, target, source);
+        assert_eq!(fill.destination, target);
+    }
+
+    #[test]
+    fn nine_slice_keeps_the_borders_intact() {
+        let regions: Vec<_> = nine_slice(
+            area(0.0, 0.0, 100.0, 40.0),
+            area(32.0, 0.0, 16.0, 16.0),
+            Insets::uniform(4.0),
+        )
+        .collect();
+
+        assert_eq!(regions.len(), 9);
+        assert_eq!(regions[0].destination, area(0.0, 0.0, 4.0, 4.0));
+        assert_eq!(regions[0].source, area(32.0, 0.0, 4.0, 4.0));
+        assert_eq!(regions[4].destination, area(4.0, 4.0, 92.0, 32.0));
+        assert_eq!(regions[4].source, area(36.0, 4.0, 8.0, 8.0));
+        assert_eq!(regions[8].destination, area(96.0, 36.0, 4.0, 4.0));
+    }
+}
@@ -2,7 +2,9 @@
 //!
 //! See [`Label`] for details.
 
-use crate::{Axis, CursorIcon, LengthRequest, MeasurePass, Object, RenderPass, Renderer, Rgba};
+use crate::{
+    Axis, CursorIcon, LayoutPass, LengthRequest, MeasurePass, Object, RenderPass, Renderer, Rgba,
+};
 
 
 
@@ -68,6 +70,14 @@ impl Object for Label {
             .value_for_axis(axis)
     }
 
+    fn layout(&mut self, pass: &mut LayoutPass<'_>) {
+        // The text is drawn from the top, so any extra height sits below it.
+        let context = pass.measure_context();
+        let text_height = context.text_size(&self.content, self.font_size).height;
+        let text_baseline = context.text_baseline_offset(&self.content, self.font_size);
+        pass.set_baseline_offset(pass.size.height - text_height + text_baseline);
+    }
+
     fn cursor_icon(&self) -> CursorIcon {
         CursorIcon::IBeam
     }
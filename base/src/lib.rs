@@ -1,21 +1,45 @@
 //! # Demo Library
 
+#![deny(unsafe_code)]
+
+mod arena;
 mod color;
+mod display_list;
+mod export;
 mod flex;
+mod icon;
+mod image;
 mod label;
+mod manifest;
 mod math;
 mod object_tree;
-
-pub use {color::*, flex::*, label::*, math::*, object_tree::*};
+mod panic;
+mod profiler;
+mod program_state;
+mod view;
+
+pub use {
+    color::*, display_list::*, export::*, flex::*, icon::*, image::*, label::*, manifest::*,
+    math::*, object_tree::*, panic::*, profiler::*, program_state::*, view::*,
+};
 
 use std::{
     any::{Any, TypeId},
-    collections::HashSet,
+    collections::{HashSet, VecDeque},
 };
 
 
 pub const OBJECT_TYPE_ID: TypeId = TypeId::of::<dyn Object>();
 
+/// A hash of the sources this crate was built from.
+///
+/// Programs are compiled against a separate build of this crate, which the
+/// host uses to check that both builds agree.
+pub const SOURCE_HASH: u64 = match u64::from_str_radix(env!("BASE_SOURCE_HASH"), 10) {
+    Ok(hash) => hash,
+    Err(_) => panic!("invalid source hash"),
+};
+
 /// A visible element within the [`ObjectTree`].
 ///
 /// ## Instantiation
@@ -23,6 +47,14 @@ pub const OBJECT_TYPE_ID: TypeId = TypeId::of::<dyn Object>();
 /// *TODO: Document the instantiation process.*
 #[allow(unused)]
 pub trait Object: Any {
+    /// A human-readable name for this object's type, used by debugging tools
+    /// like the inspector.
+    ///
+    /// *Defaults to the object's [type name](std::any::type_name).*
+    fn debug_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
     /// Whether this object can accept pointer events like hovering and
     /// clicking.
     ///
@@ -43,6 +75,12 @@ pub trait Object: Any {
 
     fn layout(&mut self, pass: &mut LayoutPass<'_>) {}
 
+    /// Report the regions of this object that aren't covered by any child, like
+    /// gaps and spacers, for the [layout overlay](layout_overlay_pass).
+    ///
+    /// Region areas are relative to this object's layout area.
+    fn layout_regions(&self, regions: &mut Vec<LayoutRegion>) {}
+
     // Reduce boilerplate required for tests.
     #[cfg(test)]
     fn measure(
@@ -90,7 +128,13 @@ pub trait Object: Any {
 
     fn on_pointer_event(&mut self, pass: &mut EventPass<'_>, event: &PointerEvent) {}
 
+    /// Called when the pointer starts or stops hovering this object.
+    ///
+    /// The object is repainted afterwards either way, but any other changes,
+    /// like to its size, have to be requested through the pass.
     fn on_hover(&mut self, pass: &mut EventPass<'_>, hovered: bool) {}
+    /// Called when this object gains or loses focus. Like with
+    /// [`on_hover`](Object::on_hover), the object is repainted afterwards.
     fn on_focus(&mut self, pass: &mut EventPass<'_>, focused: bool) {}
     fn on_child_hover(&mut self, pass: &mut EventPass<'_>, hovered: bool) {}
     fn on_child_focus(&mut self, pass: &mut EventPass<'_>, focused: bool) {}
@@ -104,7 +148,7 @@ pub struct ObjectState {
     global_transform: Affine,
 
     layout_area: Area,
-    layout_baseline_offset: f32,
+    layout_baseline_offset: Option<f32>,
     local_transform: Affine,
     scroll_translation: Point,
 
@@ -116,6 +160,9 @@ pub struct ObjectState {
     /// Whether the object needs to be recomposed onto the screen (i.e. whether
     /// its global position should be updated).
     needs_compose: bool,
+    /// Whether the object needs to be re-rendered, even if nothing around it
+    /// was damaged.
+    needs_paint: bool,
     /// Whether the object wants to be recomposed onto the screen (i.e. whether
     /// [`Object::compose`] should be called).
     wants_compose: bool,
@@ -129,8 +176,30 @@ pub struct ObjectState {
     hovered: bool,
     /// Whether this object has the user's focus.
     focused: bool,
+
+    /// The key given with [`ObjectBuilder::with_key`], if any.
+    key: Option<String>,
+
+    /// The results of previous calls to [`Object::measure`], which stay valid
+    /// until the object next requests layout, oldest first.
+    measure_cache: VecDeque<(MeasureKey, f32)>,
+    /// The textures whose sizes this object's cached measurements and layout
+    /// were computed from. See [`ObjectTree::invalidate_textures`].
+    texture_dependencies: Vec<u64>,
 }
 
+/// The inputs to [`Object::measure`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct MeasureKey {
+    axis: Axis,
+    length_request: LengthRequest,
+    cross_length: Option<f32>,
+}
+
+/// The most measurements kept in an [`ObjectState`]'s cache. Objects are rarely
+/// measured with more than a handful of distinct requests between layouts.
+const MEASURE_CACHE_CAPACITY: usize = 16;
+
 impl ObjectState {
     const fn new(id: u64) -> Self {
         Self {
@@ -140,18 +209,24 @@ impl ObjectState {
             global_transform: Affine::IDENTITY,
 
             layout_area: Area::ZERO,
-            layout_baseline_offset: 0.0,
+            layout_baseline_offset: None,
             local_transform: Affine::IDENTITY,
             scroll_translation: Point::ZERO,
 
             newly_instantiated: true,
             needs_layout: true,
             needs_compose: true,
+            needs_paint: true,
             wants_compose: true,
             children_changed: true,
             transformed: true,
             hovered: false,
             focused: false,
+
+            key: None,
+
+            measure_cache: VecDeque::new(),
+            texture_dependencies: Vec::new(),
         }
     }
 
@@ -160,13 +235,101 @@ impl ObjectState {
         self.id
     }
 
+    /// The [stable key](ObjectBuilder::with_key) given to this object, if any.
+    #[inline]
+    pub fn key(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+
     #[inline]
     pub const fn area(&self) -> Area {
         self.global_area
     }
 
+    /// The area of this object relative to its parent, as determined by the
+    /// last [layout pass](layout_pass).
+    #[inline]
+    pub const fn layout_area(&self) -> Area {
+        self.layout_area
+    }
+
+    /// The distance from the bottom of this object to its text baseline, if
+    /// its last [layout pass](layout_pass) set one.
+    #[inline]
+    pub const fn baseline_offset(&self) -> Option<f32> {
+        self.layout_baseline_offset
+    }
+
+    #[inline]
+    pub const fn needs_layout(&self) -> bool {
+        self.needs_layout
+    }
+
+    #[inline]
+    pub const fn needs_compose(&self) -> bool {
+        self.needs_compose
+    }
+
+    #[inline]
+    pub const fn needs_paint(&self) -> bool {
+        self.needs_paint
+    }
+
+    #[inline]
+    pub const fn children_changed(&self) -> bool {
+        self.children_changed
+    }
+
+    #[inline]
+    pub const fn is_hovered(&self) -> bool {
+        self.hovered
+    }
+
+    #[inline]
+    pub const fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    fn request_layout(&mut self) {
+        self.needs_layout = true;
+        self.needs_paint = true;
+        self.measure_cache.clear();
+        self.texture_dependencies.clear();
+    }
+
+    fn cached_measurement(&self, key: MeasureKey) -> Option<f32> {
+        self.measure_cache
+            .iter()
+            .find_map(|(cached_key, length)| (*cached_key == key).then_some(*length))
+    }
+
+    fn cache_measurement(&mut self, key: MeasureKey, length: f32) {
+        if self.measure_cache.len() >= MEASURE_CACHE_CAPACITY {
+            self.measure_cache.pop_front();
+        }
+        self.measure_cache.push_back((key, length));
+    }
+
+    fn add_texture_dependencies(&mut self, texture_ids: Vec<u64>) {
+        for texture_id in texture_ids {
+            if !self.texture_dependencies.contains(&texture_id) {
+                self.texture_dependencies.push(texture_id);
+            }
+        }
+    }
+
+    #[inline]
+    fn depends_on_texture(&self, texture_ids: &[u64]) -> bool {
+        self.texture_dependencies
+            .iter()
+            .any(|texture_id| texture_ids.contains(texture_id))
+    }
+
     fn merge_with_child(&mut self, child_state: &Self) {
-        self.needs_layout |= child_state.needs_layout;
+        if child_state.needs_layout {
+            // The child's measurements may have changed, and so ours may have too.
+            self.request_layout();
+        }
         self.children_changed |= child_state.children_changed;
     }
 }
@@ -219,7 +382,43 @@ impl ScrollDelta {
 
 
 pub trait ViewContext {
+    /// Start loading the image at the provided path, relative to the
+    /// program's asset directory, and get the ID of its texture to pass to
+    /// [`Renderer::image`].
+    ///
+    /// The ID is valid straight away, but the image is decoded in the
+    /// background. The host draws a placeholder until it's ready, or if it
+    /// fails to load, and reports the failure itself.
+    ///
+    /// SVG images are rasterized at the size they're drawn at, so that they
+    /// stay sharp at any scale.
+    ///
+    /// Loading the same path again, including from a later build of the
+    /// program, gives the same ID. The texture is reloaded under that ID when
+    /// its file changes.
     fn load_texture(&mut self, path: &str) -> u64;
+
+    /// Provide the content of the asset at the provided path, which is then
+    /// loaded from it rather than from disk. This is meant for assets built
+    /// into the program:
+    ///
+    /// ```ignore
+    /// context.embed_asset("icon.png", include_bytes!("icon.png"));
+    /// let icon = context.load_texture("icon.png");
+    /// ```
+    ///
+    /// The content is copied, and can be embedded again on every build of
+    /// the view. Asset paths are relative to the program's directory, like
+    /// `include_bytes!` paths are relative to the program's source file.
+    fn embed_asset(&mut self, path: &str, content: &[u8]);
+
+    /// Read the declarative view at the provided path, which is resolved like
+    /// asset paths, or get why it couldn't be read. Use
+    /// [`ViewRegistry::load`] to build it.
+    ///
+    /// The host watches the file, and builds the program's view again when it
+    /// changes, without recompiling the program.
+    fn read_view(&mut self, path: &str) -> Result<String, String>;
 }
 
 
@@ -232,19 +431,24 @@ pub struct UpdatePass<'tree> {
 impl UpdatePass<'_> {
     /// See [`Object::update_children`].
     pub fn update_child(&mut self, child: &mut ChildObject) {
-        let Some(ObjectBuilder { id, object }) = child.take_inner() else {
-            return;
-        };
-
-        let state = ObjectState::new(id);
+        child.instantiate(|builder| self.children.push(builder));
+    }
 
-        self.children.push(id, object, state);
+    /// Remove a child and all of its descendants from the tree. The object
+    /// must no longer include the child in [`Object::children_ids`].
+    pub fn remove_child(&mut self, child: ChildObject) {
+        if let Some(id) = child.id()
+            && self.children.remove(id).is_some()
+        {
+            self.state.request_layout();
+        }
     }
 }
 
 pub fn update_pass(tree: &mut ObjectTree) {
-    let node = tree.root_node_mut();
-    update_object_tree(node);
+    let span = tree.interaction.profiler.begin(SpanKind::UpdatePass, None);
+    update_object_tree(tree.root_node_mut().node_mut());
+    tree.interaction.profiler.end(span);
 }
 
 fn update_object_tree(mut node: ObjectNodeMut<'_>) {
@@ -273,15 +477,12 @@ fn update_object_tree(mut node: ObjectNodeMut<'_>) {
     let parent_state = &mut *state;
     for_each_child_object(object, children.reborrow_mut(), |mut node| {
         update_object_tree(node.reborrow_mut());
-        parent_state.merge_with_child(&node.state);
+        parent_state.merge_with_child(node.state);
     });
 
     if state.newly_instantiated {
         state.newly_instantiated = false;
-        object.ready(&mut UpdatePass {
-            state,
-            children: children,
-        });
+        object.ready(&mut UpdatePass { state, children });
     }
 }
 
@@ -312,6 +513,7 @@ fn event_pass(
     target: Option<u64>,
     mut callback: impl FnMut(&mut dyn Object, &mut EventPass<'_>),
 ) {
+    let span = tree.interaction.profiler.begin(SpanKind::EventPass, target);
     let mut target_id = target;
     let mut handled = false;
     while let Some(node_id) = target_id {
@@ -319,10 +521,11 @@ fn event_pass(
             let mut node = tree
                 .find_mut(node_id)
                 .expect("invalid object ID for event target");
+            let node = node.node_mut();
 
             if !handled {
                 let mut pass = EventPass {
-                    state: &mut node.state,
+                    state: node.state,
                     children: node.children,
                     handled: false,
                 };
@@ -336,13 +539,15 @@ fn event_pass(
 
         if let Some(parent_id) = parent_id {
             let mut parent_node = tree.find_mut(parent_id).unwrap();
-            let node = parent_node.children.get_mut(node_id).unwrap();
+            let mut parent_node = parent_node.node_mut();
+            let mut node = parent_node.children.get_mut(node_id).unwrap();
 
-            parent_node.state.merge_with_child(&node.state);
+            parent_node.state.merge_with_child(node.node_mut().state);
         }
 
         target_id = parent_id;
     }
+    tree.interaction.profiler.end(span);
 }
 
 fn single_event_pass(
@@ -354,28 +559,33 @@ fn single_event_pass(
         return;
     };
 
-    let mut node = tree
-        .find_mut(target)
-        .expect("invalid object ID passed to single_event_pass");
+    // The target may have been removed since it was recorded.
+    {
+        let Some(mut node) = tree.find_mut(target) else {
+            return;
+        };
+        let node = node.node_mut();
 
-    let mut pass = EventPass {
-        state: &mut node.state,
-        children: node.children,
-        handled: false,
-    };
-    callback(&mut **node.object, &mut pass);
+        let mut pass = EventPass {
+            state: node.state,
+            children: node.children,
+            handled: false,
+        };
+        callback(&mut **node.object, &mut pass);
+    }
 
     let mut current_id = Some(target);
     while let Some(node_id) = current_id {
         let parent_id = tree
-            .find_mut(node_id)
+            .find(node_id)
             .expect("invalid object ID for pointer target")
             .parent_id;
         if let Some(parent_id) = parent_id {
             let mut parent_node = tree.find_mut(parent_id).unwrap();
-            let node = parent_node.children.get_mut(node_id).unwrap();
+            let mut parent_node = parent_node.node_mut();
+            let mut node = parent_node.children.get_mut(node_id).unwrap();
 
-            parent_node.state.merge_with_child(&node.state);
+            parent_node.state.merge_with_child(node.node_mut().state);
         }
 
         current_id = parent_id;
@@ -383,11 +593,13 @@ fn single_event_pass(
 }
 
 fn update_pointer_pass(tree: &mut ObjectTree) {
+    let span = tree.interaction.profiler.begin(SpanKind::HitTest, None);
     let next_hovered_object = tree
         .interaction
         .pointer_position
         .and_then(|pos| find_pointer_target(tree.root_node(), pos))
         .map(|node| node.state.id());
+    tree.interaction.profiler.end(span);
     let next_hovered_path =
         next_hovered_object.map_or(Vec::new(), |node_id| tree.get_id_path(node_id, None));
     let prev_hovered_path = std::mem::take(&mut tree.interaction.hovered_path);
@@ -401,7 +613,7 @@ fn update_pointer_pass(tree: &mut ObjectTree) {
 
         for node_id in prev_hovered_path.iter().copied() {
             if tree
-                .find_mut(node_id)
+                .find(node_id)
                 .map(|node| node.state.hovered != hovered_set.contains(&node_id))
                 .unwrap_or(false)
             {
@@ -416,7 +628,7 @@ fn update_pointer_pass(tree: &mut ObjectTree) {
         }
         for node_id in next_hovered_path.iter().copied() {
             if tree
-                .find_mut(node_id)
+                .find(node_id)
                 .map(|node| node.state.hovered != hovered_set.contains(&node_id))
                 .unwrap_or(false)
             {
@@ -431,13 +643,17 @@ fn update_pointer_pass(tree: &mut ObjectTree) {
         }
     }
 
+    // Objects usually look different while hovered or focused, so they're
+    // repainted whenever that changes, without having to request it.
     if prev_hovered_object != next_hovered_object {
         single_event_pass(tree, prev_hovered_object, |object, pass| {
             pass.state.hovered = false;
+            pass.request_paint();
             object.on_hover(pass, false);
         });
         single_event_pass(tree, next_hovered_object, |object, pass| {
             pass.state.hovered = true;
+            pass.request_paint();
             object.on_hover(pass, true);
         });
     }
@@ -448,7 +664,7 @@ fn update_pointer_pass(tree: &mut ObjectTree) {
         .or(next_hovered_object)
     {
         let node = tree
-            .find_mut(node_id)
+            .find(node_id)
             .expect("failed to find the object tree's hover target");
 
         node.object.cursor_icon()
@@ -501,7 +717,7 @@ fn update_focus_pass(tree: &mut ObjectTree) {
 
         for node_id in prev_focused_path.iter().copied() {
             if tree
-                .find_mut(node_id)
+                .find(node_id)
                 .map(|node| node.state.focused != focused_set.contains(&node_id))
                 .unwrap_or(false)
             {
@@ -516,7 +732,7 @@ fn update_focus_pass(tree: &mut ObjectTree) {
         }
         for node_id in next_focused_path.iter().copied() {
             if tree
-                .find_mut(node_id)
+                .find(node_id)
                 .map(|node| node.state.focused != focused_set.contains(&node_id))
                 .unwrap_or(false)
             {
@@ -534,10 +750,12 @@ fn update_focus_pass(tree: &mut ObjectTree) {
     if prev_focused_object != next_focused_object {
         single_event_pass(tree, prev_focused_object, |object, pass| {
             pass.state.focused = false;
+            pass.request_paint();
             object.on_focus(pass, false);
         });
         single_event_pass(tree, next_focused_object, |object, pass| {
             pass.state.focused = true;
+            pass.request_paint();
             object.on_focus(pass, true);
         });
     }
@@ -549,24 +767,34 @@ fn update_focus_pass(tree: &mut ObjectTree) {
 
 
 pub fn render_pass(tree: &mut ObjectTree, renderer: &mut dyn Renderer) {
-    let node = tree.root_node_mut();
-    render_object(node, renderer);
+    let span = tree.interaction.profiler.begin(SpanKind::RenderPass, None);
+    render_object(tree.root_node_mut().node_mut(), renderer);
+    tree.interaction.profiler.end(span);
 }
 
 fn render_object(mut node: ObjectNodeMut<'_>, renderer: &mut dyn Renderer) {
     let object = &**node.object;
     let state = &mut node.state;
-    let children = node.children;
+    let mut children = node.children;
+
+    // The span covers the object's children so that they're nested under it.
+    let span = children
+        .interaction
+        .profiler
+        .begin(SpanKind::Render, Some(state.id));
 
     object.render(&mut RenderPass { state }, renderer);
+    state.needs_paint = false;
 
     let parent_state = &mut *state;
-    for_each_child_object(object, children, |mut node| {
+    for_each_child_object(object, children.reborrow_mut(), |mut node| {
         render_object(node.reborrow_mut(), renderer);
-        parent_state.merge_with_child(&node.state);
+        parent_state.merge_with_child(node.state);
     });
 
     object.render_overlay(&mut RenderPass { state }, renderer);
+
+    children.interaction.profiler.end(span);
 }
 
 pub struct RenderPass<'tree> {
@@ -577,14 +805,100 @@ pub trait Renderer {
     fn text(&mut self, content: &str, position: Point, font_size: f32, color: Rgba);
     fn quad(&mut self, position: Point, size: Size, color: Rgba);
     fn image(&mut self, texture_id: u64, position: Point, size: Size);
+    /// Draw the part of a texture covered by `uv`, in texture coordinates
+    /// ranging from 0 to 1, multiplied by `tint`.
+    fn image_region(&mut self, texture_id: u64, position: Point, size: Size, uv: Area, tint: Rgba);
+    /// Draw a texture in a single color. Vector images keep only their
+    /// shape and are drawn in `color`, raster images are multiplied by it.
+    fn icon(&mut self, texture_id: u64, position: Point, size: Size, color: Rgba);
+}
+
+
+
+/// Walk the tree and describe its current layout to the provided overlay.
+///
+/// This should be called after the [render pass](render_pass) so that the
+/// overlay is drawn on top of the tree's objects.
+pub fn layout_overlay_pass(tree: &ObjectTree, overlay: &mut dyn LayoutOverlay) {
+    overlay_object(tree.root_node(), None, overlay, &mut Vec::new());
+}
+
+fn overlay_object(
+    node: ObjectNodeRef<'_>,
+    parent_size: Option<Size>,
+    overlay: &mut dyn LayoutOverlay,
+    regions: &mut Vec<LayoutRegion>,
+) {
+    let state = node.state;
+    let area = state.global_area;
+
+    // Objects laid out past the edges of their parent are likely to be clipped or
+    // to overlap their siblings.
+    let overflowing = parent_size.is_some_and(|parent_size| {
+        let min = state.layout_area.position;
+        let max = state.layout_area.max_point();
+        min.x < 0.0 || min.y < 0.0 || max.x > parent_size.width || max.y > parent_size.height
+    });
+    overlay.outline(area, overflowing);
+
+    if let Some(baseline_offset) = state.layout_baseline_offset {
+        let baseline_y = area.max_point().y - baseline_offset;
+        overlay.baseline(Point::new(area.position.x, baseline_y), area.size.width);
+    }
+
+    regions.clear();
+    node.object.layout_regions(regions);
+    for region in regions.drain(..) {
+        overlay.region(LayoutRegion {
+            area: state.global_transform.transform_area(region.area),
+            ..region
+        });
+    }
+
+    for child_id in node.object.children_ids() {
+        let child = node
+            .children
+            .get(child_id)
+            .expect("Object::children_ids produced an invalid child ID");
+        overlay_object(child, Some(state.layout_area.size), overlay, regions);
+    }
+}
+
+/// A visualization of the [object tree's](ObjectTree) layout. See
+/// [`layout_overlay_pass`].
+pub trait LayoutOverlay {
+    /// Outline an object's global area, flagging it if it overflows its
+    /// parent.
+    fn outline(&mut self, area: Area, overflowing: bool);
+    /// Draw an object's text baseline, starting at `start` and extending to
+    /// the right by `width`.
+    fn baseline(&mut self, start: Point, width: f32);
+    /// Highlight a region reported by [`Object::layout_regions`]. The region's
+    /// area is global.
+    fn region(&mut self, region: LayoutRegion);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LayoutRegion {
+    pub kind: LayoutRegionKind,
+    pub area: Area,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LayoutRegionKind {
+    /// Fixed space between two children.
+    Gap,
+    /// Flexible space that isn't taken up by any child.
+    Spacer,
 }
 
 
 
 pub fn layout_pass(tree: &mut ObjectTree, measure_context: &mut dyn MeasureContext) {
+    let span = tree.interaction.profiler.begin(SpanKind::LayoutPass, None);
     let size = tree.size();
-    let node = tree.root_node_mut();
-    layout_object(measure_context, node, size);
+    layout_object(measure_context, tree.root_node_mut().node_mut(), size);
+    tree.interaction.profiler.end(span);
 }
 
 fn layout_object(
@@ -603,13 +917,23 @@ fn layout_object(
     state.needs_layout = false;
 
     state.layout_area.size = size;
+    state.layout_baseline_offset = None;
 
-    object.layout(&mut LayoutPass {
+    let mut context = TextureRecorder::new(measure_context);
+    let mut pass = LayoutPass {
         state,
         children,
         size,
-        context: measure_context,
-    });
+        context: &mut context,
+    };
+    let span = pass
+        .children
+        .interaction
+        .profiler
+        .begin(SpanKind::Layout, Some(pass.state.id));
+    object.layout(&mut pass);
+    pass.children.interaction.profiler.end(span);
+    state.add_texture_dependencies(context.texture_ids);
 
     state.needs_compose = true;
     state.wants_compose = true;
@@ -633,24 +957,39 @@ pub struct LayoutPass<'tree> {
 
 impl LayoutPass<'_> {
     pub fn do_layout(&mut self, child: &mut ChildObject, size: Size) {
-        let mut node = self
-            .children
-            .get_mut(child.id())
+        let mut child = child
+            .id()
+            .and_then(|id| self.children.get_mut(id))
             .expect("invalid child passed to LayoutPass::do_layout");
+        let mut node = child.node_mut();
         layout_object(self.context, node.reborrow_mut(), size);
-        self.state.merge_with_child(&node.state);
+        self.state.merge_with_child(node.state);
     }
 
     pub fn place_child(&mut self, child: &mut ChildObject, position: Point) {
         place_object(
-            &mut self
-                .children
-                .get_mut(child.id())
+            child
+                .id()
+                .and_then(|id| self.children.get_mut(id))
                 .expect("invalid child passed to LayoutPass::place_child")
+                .node_mut()
                 .state,
             position,
         );
     }
+
+    /// Set the distance from the bottom of this object to its text baseline.
+    ///
+    /// Objects without a baseline are aligned by their bottom edge instead.
+    #[inline]
+    pub const fn set_baseline_offset(&mut self, baseline_offset: f32) {
+        self.state.layout_baseline_offset = Some(baseline_offset);
+    }
+
+    #[inline]
+    pub const fn clear_baseline_offset(&mut self) {
+        self.state.layout_baseline_offset = None;
+    }
 }
 
 
@@ -674,11 +1013,79 @@ fn resolve_axis_measurement(
         Length::FitContent(max_size) => LengthRequest::FitContent(max_size),
         Length::Exact(amount) => return amount,
     };
-    object.measure(pass, axis, length_request, cross_length)
+
+    let key = MeasureKey {
+        axis,
+        length_request,
+        cross_length,
+    };
+    if let Some(length) = pass.state.cached_measurement(key) {
+        return length;
+    }
+
+    let span = pass
+        .children
+        .interaction
+        .profiler
+        .begin(SpanKind::Measure, Some(pass.state.id));
+    let length = object.measure(pass, axis, length_request, cross_length);
+    pass.children.interaction.profiler.end(span);
+
+    pass.state.cache_measurement(key, length);
+
+    length
 }
 
 pub trait MeasureContext {
     fn text_size(&mut self, content: &str, font_size: f32) -> Size;
+
+    /// The distance from the bottom of the provided text to the baseline of
+    /// its first line.
+    ///
+    /// *Defaults to a fifth of the font size below the text's height.*
+    fn text_baseline_offset(&mut self, content: &str, font_size: f32) -> f32 {
+        self.text_size(content, font_size).height - font_size * 0.8
+    }
+
+    /// The size in pixels of the texture with the provided ID, if it has
+    /// finished loading.
+    ///
+    /// *Defaults to `None`.*
+    fn texture_size(&mut self, texture_id: u64) -> Option<Size> {
+        let _ = texture_id;
+        None
+    }
+}
+
+/// Forwards to another [`MeasureContext`], keeping track of the textures whose
+/// sizes were asked for.
+struct TextureRecorder<'a> {
+    context: &'a mut dyn MeasureContext,
+    texture_ids: Vec<u64>,
+}
+
+impl<'a> TextureRecorder<'a> {
+    fn new(context: &'a mut dyn MeasureContext) -> Self {
+        Self {
+            context,
+            texture_ids: Vec::new(),
+        }
+    }
+}
+
+impl MeasureContext for TextureRecorder<'_> {
+    fn text_size(&mut self, content: &str, font_size: f32) -> Size {
+        self.context.text_size(content, font_size)
+    }
+
+    fn text_baseline_offset(&mut self, content: &str, font_size: f32) -> f32 {
+        self.context.text_baseline_offset(content, font_size)
+    }
+
+    fn texture_size(&mut self, texture_id: u64) -> Option<Size> {
+        self.texture_ids.push(texture_id);
+        self.context.texture_size(texture_id)
+    }
 }
 
 impl MeasureContext for () {
@@ -709,7 +1116,7 @@ impl Length {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 #[repr(C)]
 pub enum LengthRequest {
     MaxContent,
@@ -717,9 +1124,9 @@ pub enum LengthRequest {
     FitContent(f32),
 }
 
-impl Into<Length> for LengthRequest {
-    fn into(self) -> Length {
-        match self {
+impl From<LengthRequest> for Length {
+    fn from(value: LengthRequest) -> Self {
+        match value {
             LengthRequest::MaxContent => Length::MaxContent,
             LengthRequest::MinContent => Length::MinContent,
             LengthRequest::FitContent(max_size) => Length::FitContent(max_size),
@@ -737,11 +1144,11 @@ pub struct ComposePass<'tree> {
 impl ComposePass<'_> {
     pub fn set_child_scroll(&mut self, child: &mut ChildObject, translation: Point) {
         let translation = translation.round();
-        let child_state = &mut self
-            .children
-            .get_mut(child.id())
-            .expect("invalid child passed to `ComposePass::set_child_scroll`")
-            .state;
+        let mut child = child
+            .id()
+            .and_then(|id| self.children.get_mut(id))
+            .expect("invalid child passed to `ComposePass::set_child_scroll`");
+        let child_state = child.node_mut().state;
         if translation != child_state.scroll_translation {
             child_state.scroll_translation = translation;
             child_state.transformed = true;
@@ -750,8 +1157,9 @@ impl ComposePass<'_> {
 }
 
 pub fn compose_pass(view: &mut ObjectTree) {
-    let node = view.root_node_mut();
-    compose_object(node, Affine::IDENTITY, false);
+    let span = view.interaction.profiler.begin(SpanKind::ComposePass, None);
+    compose_object(view.root_node_mut().node_mut(), Affine::IDENTITY, false);
+    view.interaction.profiler.end(span);
 }
 
 fn compose_object(
@@ -770,11 +1178,17 @@ fn compose_object(
     }
 
     let local_translation = state.scroll_translation + state.layout_area.position;
+    let previous_area = state.global_area;
     state.global_transform =
         parent_global_transform * state.local_transform.with_translation(local_translation);
     state.global_area = state
         .global_transform
         .transform_area(Area::from_size(state.layout_area.size));
+    if state.global_area != previous_area {
+        // Both where the object was and where it is now need repainting.
+        children.interaction.damage.push(previous_area);
+        children.interaction.damage.push(state.global_area);
+    }
 
     if state.wants_compose {
         object.compose(&mut ComposePass {
@@ -794,7 +1208,7 @@ fn compose_object(
             parent_state.global_transform,
             transformed,
         );
-        parent_state.merge_with_child(&node.state);
+        parent_state.merge_with_child(node.state);
     });
 }
 
@@ -809,7 +1223,8 @@ fn for_each_child_object(
         callback(
             children
                 .get_mut(child_id)
-                .expect("Object::children_ids produced an invalid child ID"),
+                .expect("Object::children_ids produced an invalid child ID")
+                .node_mut(),
         );
     }
 }
@@ -857,7 +1272,22 @@ multi_impl! {
 
         #[inline]
         pub fn request_layout(&mut self) {
-            self.state.needs_layout = true;
+            self.state.request_layout();
+        }
+
+        /// Re-render this object during the next
+        /// [damaged render pass](render_pass_damaged), even if its area wasn't
+        /// otherwise damaged.
+        #[inline]
+        pub fn request_paint(&mut self) {
+            self.state.needs_paint = true;
+        }
+
+        /// Call [`Object::update_children`] during the next
+        /// [update pass](update_pass), so that children can be added or removed.
+        #[inline]
+        pub fn request_update_children(&mut self) {
+            self.state.children_changed = true;
         }
 
         #[inline]
@@ -894,28 +1324,33 @@ multi_impl! {
     {
         pub fn resolve_length(
             &mut self,
-            child_id: u64,
+            child: &ChildObject,
             axis: Axis,
             fallback_length: Length,
             cross_length: Option<f32>,
         ) -> f32 {
-            let mut child = self
-                .children
-                .get_mut(child_id)
-                .expect("invalid child ID provided to resolve_length");
+            let mut child = child
+                .id()
+                .and_then(|id| self.children.get_mut(id))
+                .expect("invalid child provided to resolve_length");
+            let mut child = child.node_mut();
             let object = &mut **child.object;
             let state = &mut child.state;
             let children = child.children;
 
+            let mut context = TextureRecorder::new(self.context);
             let mut pass = MeasurePass {
                 state,
                 children,
-                context: self.context,
+                context: &mut context,
             };
 
-            fallback_length.exact().unwrap_or_else(|| {
+            let length = fallback_length.exact().unwrap_or_else(|| {
                 resolve_axis_measurement(&mut pass, object, axis, fallback_length, cross_length)
-            })
+            });
+            state.add_texture_dependencies(context.texture_ids);
+
+            length
         }
 
         #[inline]
@@ -0,0 +1,265 @@
+//! # Program Manifest
+//!
+//! Programs are compiled separately from the host, against their own build of
+//! this crate. Every type passed between the two must have the same layout on
+//! both sides, which nothing checks when a program is loaded. Programs
+//! therefore export a [`ProgramManifest`] describing the build they were
+//! compiled against:
+//!
+//! ```ignore
+//! #[unsafe(no_mangle)]
+//! pub static __PROGRAM_MANIFEST: ProgramManifest = PROGRAM_MANIFEST;
+//! ```
+//!
+//! This is one of the symbols exported by [`export_program!`].
+//!
+//! The host compares it with its own before calling into the program, which
+//! it then only does through [`ProgramManifest::catch_panic`].
+
+use std::{
+    any::TypeId,
+    fmt,
+    mem::{align_of, size_of},
+};
+
+use crate::*;
+
+
+
+/// The name of the symbol programs export their [`ProgramManifest`] as.
+pub const PROGRAM_MANIFEST_SYMBOL: &[u8] = b"__PROGRAM_MANIFEST";
+
+/// The version of the [`ProgramManifest`] layout itself. Must be bumped
+/// whenever its fields change.
+pub const MANIFEST_VERSION: u32 = 2;
+
+/// The manifest of this build of the crate.
+pub const PROGRAM_MANIFEST: ProgramManifest = ProgramManifest {
+    manifest_version: MANIFEST_VERSION,
+    base_version: ManifestString::new(env!("CARGO_PKG_VERSION")),
+    rustc_version: ManifestString::new(env!("BASE_RUSTC_VERSION")),
+    source_hash: SOURCE_HASH,
+    layout_hash: layout_hash(),
+    object_type_id: OBJECT_TYPE_ID,
+    catch_panic,
+};
+
+/// Describes the build of this crate that a program was compiled against.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct ProgramManifest {
+    /// Always the first field, so that it can be read from manifests of any
+    /// version.
+    pub manifest_version: u32,
+    pub base_version: ManifestString,
+    pub rustc_version: ManifestString,
+    /// See [`SOURCE_HASH`].
+    pub source_hash: u64,
+    /// A hash of the sizes and alignments of the types passed between the
+    /// host and programs.
+    pub layout_hash: u64,
+    pub object_type_id: TypeId,
+    /// The program's build of [`catch_panic`], which must wrap every call
+    /// into the program. Not compared by [`ProgramManifest::check`].
+    pub catch_panic: fn(&mut dyn FnMut()) -> Result<(), ProgramPanic>,
+}
+
+impl ProgramManifest {
+    /// Check that a program built with this manifest is compatible with a
+    /// host built with the `expected` one.
+    ///
+    /// Only the first mismatch is reported, in order of importance.
+    pub fn check(&self, expected: &Self) -> Result<(), ManifestMismatch> {
+        let mismatch = |field, found: String, expected: String| {
+            Err(ManifestMismatch {
+                field,
+                found,
+                expected,
+            })
+        };
+
+        if self.manifest_version != expected.manifest_version {
+            return mismatch(
+                "manifest version",
+                self.manifest_version.to_string(),
+                expected.manifest_version.to_string(),
+            );
+        }
+        if self.rustc_version != expected.rustc_version {
+            return mismatch(
+                "rustc version",
+                self.rustc_version.to_string(),
+                expected.rustc_version.to_string(),
+            );
+        }
+        if self.base_version != expected.base_version {
+            return mismatch(
+                "base version",
+                self.base_version.to_string(),
+                expected.base_version.to_string(),
+            );
+        }
+        if self.source_hash != expected.source_hash {
+            return mismatch(
+                "base source hash",
+                format!("{:016x}", self.source_hash),
+                format!("{:016x}", expected.source_hash),
+            );
+        }
+        if self.layout_hash != expected.layout_hash {
+            return mismatch(
+                "layout hash",
+                format!("{:016x}", self.layout_hash),
+                format!("{:016x}", expected.layout_hash),
+            );
+        }
+        if self.object_type_id != expected.object_type_id {
+            return mismatch(
+                "object type ID",
+                format!("{:?}", self.object_type_id),
+                format!("{:?}", expected.object_type_id),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// A short string stored inline, so that a [`ProgramManifest`] doesn't
+/// point into the program's memory.
+#[derive(Clone, Copy, Eq, PartialEq)]
+#[repr(C)]
+pub struct ManifestString {
+    len: u8,
+    bytes: [u8; 63],
+}
+
+impl ManifestString {
+    /// Create a manifest string, truncating the provided string to 63 bytes.
+    pub const fn new(string: &str) -> Self {
+        let mut bytes = [0; 63];
+        let mut len = 0;
+        while len < string.len() && len < bytes.len() {
+            bytes[len] = string.as_bytes()[len];
+            len += 1;
+        }
+
+        Self {
+            len: len as u8,
+            bytes,
+        }
+    }
+
+    /// The contents of the string, or an empty string if it was truncated in
+    /// the middle of a character.
+    pub fn as_str(&self) -> &str {
+        let len = (self.len as usize).min(self.bytes.len());
+        std::str::from_utf8(&self.bytes[..len]).unwrap_or_default()
+    }
+}
+
+impl fmt::Debug for ManifestString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for ManifestString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A difference between the [`ProgramManifest`] of a program and that of
+/// the host.
+#[derive(Clone, Debug)]
+pub struct ManifestMismatch {
+    pub field: &'static str,
+    pub found: String,
+    pub expected: String,
+}
+
+impl fmt::Display for ManifestMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the program was built with {} {}, but the host expects {}",
+            self.field, self.found, self.expected,
+        )
+    }
+}
+
+impl std::error::Error for ManifestMismatch {}
+
+/// Hash the layouts of the types passed between the host and programs.
+const fn layout_hash() -> u64 {
+    macro_rules! layouts {
+        ($($ty:ty),* $(,)?) => {
+            [$((size_of::<$ty>(), align_of::<$ty>())),*]
+        };
+    }
+
+    let layouts = layouts![
+        Area,
+        Affine,
+        CursorIcon,
+        DrawCommand,
+        Length,
+        LengthRequest,
+        ObjectState,
+        ObjectTree,
+        Point,
+        PointerButton,
+        PointerEvent,
+        ProgramPanic,
+        Rgba,
+        ScrollDelta,
+        Size,
+        Box<dyn Object>,
+        &mut dyn Renderer,
+        &mut dyn MeasureContext,
+        &mut dyn ViewContext,
+        &mut dyn UpdateContext,
+        Box<dyn ProgramState>,
+    ];
+
+    // FNV-1a, as the standard hashers can't be used in constants.
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    let mut i = 0;
+    while i < layouts.len() {
+        let (size, align) = layouts[i];
+        hash = (hash ^ size as u64).wrapping_mul(0x0100_0000_01b3);
+        hash = (hash ^ align as u64).wrapping_mul(0x0100_0000_01b3);
+        i += 1;
+    }
+
+    hash
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_mismatches_are_reported() {
+        assert!(PROGRAM_MANIFEST.check(&PROGRAM_MANIFEST).is_ok());
+
+        let other = ProgramManifest {
+            layout_hash: PROGRAM_MANIFEST.layout_hash ^ 1,
+            ..PROGRAM_MANIFEST
+        };
+        let mismatch = other.check(&PROGRAM_MANIFEST).unwrap_err();
+        assert_eq!(mismatch.field, "layout hash");
+
+        let other = ProgramManifest {
+            rustc_version: ManifestString::new("rustc 1.0.0"),
+            ..other
+        };
+        // The rustc version is reported before any layout differences.
+        let mismatch = other.check(&PROGRAM_MANIFEST).unwrap_err();
+        assert_eq!(mismatch.field, "rustc version");
+        assert_eq!(mismatch.found, "rustc 1.0.0");
+    }
+}
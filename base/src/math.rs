@@ -88,6 +88,18 @@ impl Area {
             && max.y > point.y
     }
 
+    /// Whether the two areas overlap. Areas that only share an edge don't
+    /// intersect.
+    #[inline]
+    pub const fn intersects(&self, other: Self) -> bool {
+        let max = self.max_point();
+        let other_max = other.max_point();
+        self.position.x < other_max.x
+            && other.position.x < max.x
+            && self.position.y < other_max.y
+            && other.position.y < max.y
+    }
+
     #[inline]
     pub const fn max_point(&self) -> Point {
         self.position.add_size(self.size)
@@ -152,6 +164,36 @@ impl Mul for Size {
 
 
 
+/// Distances inwards from each edge of an area.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Insets {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl Insets {
+    pub const ZERO: Self = Self::uniform(0.0);
+
+    #[inline]
+    pub const fn new(top: f32, right: f32, bottom: f32, left: f32) -> Self {
+        Self {
+            top,
+            right,
+            bottom,
+            left,
+        }
+    }
+
+    #[inline]
+    pub const fn uniform(inset: f32) -> Self {
+        Self::new(inset, inset, inset, inset)
+    }
+}
+
+
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Point {
     pub x: f32,
@@ -2,104 +2,75 @@
 //!
 //! See [`ObjectTree`] for details.
 
-use std::{
-    cell::UnsafeCell,
-    collections::HashMap,
-    sync::atomic::{AtomicU64, Ordering},
-};
+use std::collections::HashMap;
 
-use crate::{CursorIcon, MeasureContext, Object, ObjectState, Point, PointerEvent, Size};
+use crate::{
+    Area, CursorIcon, MeasureContext, Object, ObjectState, Point, PointerEvent, Profiler, Size,
+    SpanKind,
+    arena::{Arena, ArenaKey},
+};
 
 
 
 /// A tree of [objects](Object) representing a user interface.
+///
+/// Nodes live in a flat [arena](Arena) of slots addressed by generational
+/// keys, which double as object IDs, so finding a node is a single slot lookup
+/// no matter how deep it is. A node is taken out of its slot while it's
+/// borrowed mutably, see [`DetachedNode`], so that its children can be
+/// borrowed, added or removed through the arena at the same time.
 pub struct ObjectTree {
-    root: u64,
-    nodes: HashMap<u64, Box<UnsafeCell<ObjectNode>>>,
-    parents: HashMap<u64, Option<u64>>,
+    root_key: ArenaKey,
+    nodes: Nodes,
     pub(super) interaction: InteractionState,
 }
 
 impl ObjectTree {
-    /// Create a new object tree with a single node containing the provided root
-    /// [object](Object).
-    pub fn new(root_object: Box<dyn Object>) -> Self {
-        let root_id = 0;
-
-        let mut nodes = HashMap::new();
-        let mut parents = HashMap::new();
-
-        parents.insert(root_id, None);
-        nodes.insert(
-            root_id,
-            Box::new(UnsafeCell::new(ObjectNode {
-                object: root_object,
-                state: ObjectState::new(root_id),
-                children: Vec::new(),
-            })),
-        );
+    /// Create a new object tree containing the provided root [object](Object)
+    /// and the children it instantiates during the first [update
+    /// pass](crate::update_pass).
+    ///
+    /// Fails if more than one of those objects was given the same [stable
+    /// key](ObjectBuilder::with_key).
+    pub fn new(root_object: Box<dyn Object>) -> Result<Self, DuplicateKeyError> {
+        let mut nodes = Nodes::default();
+        let root_key = nodes.insert(None, root_object, None);
 
         let mut this = Self {
-            root: root_id,
+            root_key,
             nodes,
-            parents,
             interaction: InteractionState::default(),
         };
 
         crate::update_pass(&mut this);
 
-        this
+        match this.take_duplicate_key() {
+            Some(error) => Err(error),
+            None => Ok(this),
+        }
+    }
+
+    /// Take the first [stable key](ObjectBuilder::with_key) which was given to
+    /// an object instantiated since the last call, while another object in the
+    /// tree already had it.
+    ///
+    /// Such objects are still instantiated, but without their key. The host
+    /// should check this after the passes which instantiate objects.
+    pub fn take_duplicate_key(&mut self) -> Option<DuplicateKeyError> {
+        let key = self.nodes.duplicate_keys.drain(..).next()?;
+        Some(DuplicateKeyError { key })
     }
 
     /// Get a shared (immutable) reference to this tree's root [object](Object)
     /// instance.
     pub fn root_node(&self) -> ObjectNodeRef<'_> {
-        let node = unsafe {
-            self.nodes
-                .get(&self.root)
-                .expect("root exists")
-                .get()
-                .as_ref()
-                .expect("never null")
-        };
-
-        ObjectNodeRef {
-            parent_id: None,
-            object: &node.object,
-            state: &node.state,
-            children: ObjectChildrenRef {
-                parent_id: Some(self.root),
-                all_nodes: &self.nodes,
-                all_parents: &self.parents,
-                interaction: &self.interaction,
-            },
-        }
+        self.node(self.root_key).expect("root exists")
     }
 
     /// Get an exclusive (mutable) reference to this tree's root
     /// [object](Object) instance.
-    pub fn root_node_mut(&mut self) -> ObjectNodeMut<'_> {
-        let node = unsafe {
-            self.nodes
-                .get(&self.root)
-                .expect("root exists")
-                .get()
-                .as_mut()
-                .expect("never null")
-        };
-
-        ObjectNodeMut {
-            parent_id: None,
-            object: &mut node.object,
-            state: &mut node.state,
-            children: ObjectChildrenMut {
-                parent_id: Some(self.root),
-                children: &mut node.children,
-                all_nodes: &mut self.nodes,
-                all_parents: &mut self.parents,
-                interaction: &mut self.interaction,
-            },
-        }
+    pub fn root_node_mut(&mut self) -> DetachedNode<'_> {
+        self.node_mut(self.root_key).expect("root exists")
     }
 
     /// Get the current [cursor icon](CursorIcon) indicated by the
@@ -126,43 +97,73 @@ impl ObjectTree {
         crate::compose_pass(self);
     }
 
+    /// Lay out the objects which were measured or laid out from the sizes of
+    /// the provided textures again, along with their ancestors, discarding
+    /// their cached measurements.
+    ///
+    /// This should be called whenever the [size](MeasureContext::texture_size)
+    /// of a texture changes, like once it has finished loading.
+    pub fn invalidate_textures(
+        &mut self,
+        texture_ids: &[u64],
+        measure_context: &mut dyn MeasureContext,
+    ) {
+        let dependents = self
+            .nodes
+            .slots
+            .iter()
+            .filter(|(_, slot)| slot.data().state.depends_on_texture(texture_ids))
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        if dependents.is_empty() {
+            return;
+        }
+
+        for key in dependents {
+            self.request_layout_with_ancestors(key);
+        }
+        crate::layout_pass(self, measure_context);
+        crate::update_pass(self);
+        crate::compose_pass(self);
+    }
+
     /// Get a shared (immutable) reference to the [object](Object) instance with
     /// the provided ID, if it exists.
     pub fn find(&self, id: u64) -> Option<ObjectNodeRef<'_>> {
-        let parent_id = *self.parents.get(&id)?;
-        let node = unsafe { self.nodes.get(&id)?.get().as_ref()? };
-
-        Some(ObjectNodeRef {
-            parent_id,
-            object: &node.object,
-            state: &node.state,
-            children: ObjectChildrenRef {
-                parent_id: Some(id),
-                all_nodes: &self.nodes,
-                all_parents: &self.parents,
-                interaction: &self.interaction,
-            },
-        })
+        self.node(ArenaKey::from_bits(id))
     }
 
     /// Get an exclusive (mutable) reference to the [object](Object) instance
     /// with the provided ID, if it exists.
-    pub fn find_mut(&mut self, id: u64) -> Option<ObjectNodeMut<'_>> {
-        let parent_id = *self.parents.get(&id)?;
-        let node = unsafe { self.nodes.get(&id)?.get().as_mut()? };
-
-        Some(ObjectNodeMut {
-            parent_id,
-            object: &mut node.object,
-            state: &mut node.state,
-            children: ObjectChildrenMut {
-                parent_id: Some(id),
-                children: &mut node.children,
-                all_nodes: &mut self.nodes,
-                all_parents: &mut self.parents,
-                interaction: &mut self.interaction,
-            },
-        })
+    ///
+    /// Changes made through the returned reference, like with
+    /// [`Image::set_tint`](crate::Image::set_tint), are only picked up by the
+    /// next passes once they're requested with
+    /// [`request_layout`](Self::request_layout) or
+    /// [`request_paint`](Self::request_paint).
+    pub fn find_mut(&mut self, id: u64) -> Option<DetachedNode<'_>> {
+        self.node_mut(ArenaKey::from_bits(id))
+    }
+
+    /// Lay out the object with the provided ID again during the next
+    /// [layout pass](crate::layout_pass), along with its ancestors, and
+    /// repaint it.
+    pub fn request_layout(&mut self, id: u64) {
+        self.request_layout_with_ancestors(ArenaKey::from_bits(id));
+    }
+
+    /// Re-render the object with the provided ID during the next
+    /// [damaged render pass](crate::render_pass_damaged).
+    pub fn request_paint(&mut self, id: u64) {
+        if let Some(slot) = self.nodes.slots.get_mut(ArenaKey::from_bits(id)) {
+            slot.data_mut().state.needs_paint = true;
+        }
+    }
+
+    /// Get a shared (immutable) reference to the [object](Object) instance
+    /// given the provided [stable key](ObjectBuilder::with_key), if it exists.
+    pub fn find_by_key(&self, key: &str) -> Option<ObjectNodeRef<'_>> {
+        self.find(*self.nodes.stable_keys.get(key)?)
     }
 
     /// Get the path of object IDs from `id` to `start_id` (or the root ID if
@@ -172,14 +173,17 @@ impl ObjectTree {
     pub fn get_id_path(&self, id: u64, start_id: Option<u64>) -> Vec<u64> {
         let mut path = Vec::new();
 
-        if !self.parents.contains_key(&id) {
+        let key = ArenaKey::from_bits(id);
+        if self.nodes.slots.get(key).is_none() {
             return path;
         }
 
-        let mut current_id = Some(id);
-        while let Some(current) = current_id {
-            path.push(current);
-            current_id = *self.parents.get(&current).unwrap();
+        let mut current = Some(key);
+        let mut current_id = None;
+        while let Some(key) = current {
+            path.push(key.to_bits());
+            current = self.nodes.slots.get(key).expect("parent exists").parent;
+            current_id = current.map(ArenaKey::to_bits);
             if current_id == start_id {
                 break;
             }
@@ -205,17 +209,18 @@ impl ObjectTree {
             self.interaction.pointer_position = position;
         }
 
+        let span = self.interaction.profiler.begin(SpanKind::HitTest, None);
         let pointer_target = self.get_pointer_target();
+        self.interaction.profiler.end(span);
 
         // Clear the focus if the user clicked outside the focused object.
         if matches!(event, PointerEvent::Down { .. })
             && let Some(target_id) = pointer_target
+            && let Some(id) = self.interaction.focused_object
         {
-            if let Some(id) = self.interaction.focused_object {
-                // Focused object isn't an ancestor of the pointer target.
-                if !self.get_id_path(target_id, None).contains(&id) {
-                    self.interaction.next_focused_object = None;
-                }
+            // Focused object isn't an ancestor of the pointer target.
+            if !self.get_id_path(target_id, None).contains(&id) {
+                self.interaction.next_focused_object = None;
             }
         }
 
@@ -237,6 +242,48 @@ impl ObjectTree {
         crate::compose_pass(self);
     }
 
+    /// Get the tree's [profiler](Profiler).
+    #[inline]
+    pub const fn profiler(&self) -> &Profiler {
+        &self.interaction.profiler
+    }
+
+    /// Get an exclusive (mutable) reference to the tree's [profiler](Profiler).
+    #[inline]
+    pub const fn profiler_mut(&mut self) -> &mut Profiler {
+        &mut self.interaction.profiler
+    }
+
+    /// Get the ID of this tree's root [object](Object).
+    #[inline]
+    pub const fn root_id(&self) -> u64 {
+        self.root_key.to_bits()
+    }
+
+    /// Get the ID of the deepest [object](Object) whose area contains the
+    /// provided position, regardless of whether it accepts pointer events.
+    pub fn find_at(&self, position: Point) -> Option<u64> {
+        let mut node = self.root_node();
+        if !node.state.area().contains(position) {
+            return None;
+        }
+
+        'descend: loop {
+            for child_id in node.object.children_ids().iter().rev() {
+                let child = node
+                    .children
+                    .get(*child_id)
+                    .expect("Object::children_ids produced an invalid child ID");
+                if child.state.area().contains(position) {
+                    node = child;
+                    continue 'descend;
+                }
+            }
+
+            return Some(node.state.id());
+        }
+    }
+
     fn get_pointer_target(&self) -> Option<u64> {
         if let Some(capture_target) = self.interaction.pointer_capture_target
             && self.find(capture_target).is_some()
@@ -251,6 +298,31 @@ impl ObjectTree {
 
         None
     }
+
+    fn node(&self, key: ArenaKey) -> Option<ObjectNodeRef<'_>> {
+        let slot = self.nodes.slots.get(key)?;
+
+        Some(
+            slot.data()
+                .as_ref(key, slot.parent, &self.nodes, &self.interaction),
+        )
+    }
+
+    fn node_mut(&mut self, key: ArenaKey) -> Option<DetachedNode<'_>> {
+        self.nodes.detach(key, &mut self.interaction)
+    }
+
+    /// Request layout for the node with the provided key, and for every
+    /// ancestor so that the layout pass reaches it.
+    fn request_layout_with_ancestors(&mut self, key: ArenaKey) {
+        let mut current = Some(key);
+        while let Some(key) = current
+            && let Some(slot) = self.nodes.slots.get_mut(key)
+        {
+            slot.data_mut().state.request_layout();
+            current = slot.parent;
+        }
+    }
 }
 
 pub struct InteractionState {
@@ -262,6 +334,11 @@ pub struct InteractionState {
     pub(super) hovered_path: Vec<u64>,
     pub(super) focused_path: Vec<u64>,
     pub(super) cursor_icon: CursorIcon,
+    /// Lives here so that it can be reached from within every pass.
+    pub(super) profiler: Profiler,
+    /// The global areas that changed since the last
+    /// [damaged render pass](crate::render_pass_damaged).
+    pub(super) damage: Vec<Area>,
 }
 
 impl Default for InteractionState {
@@ -275,20 +352,250 @@ impl Default for InteractionState {
             hovered_path: Vec::new(),
             focused_path: Vec::new(),
             cursor_icon: CursorIcon::Default,
+            profiler: Profiler::default(),
+            damage: Vec::new(),
+        }
+    }
+}
+
+impl InteractionState {
+    /// Drop any references to an object which was removed from the tree.
+    fn forget(&mut self, id: u64) {
+        if self.pointer_capture_target == Some(id) {
+            self.pointer_capture_target = None;
+        }
+        if self.focused_object == Some(id) {
+            self.focused_object = None;
+        }
+        if self.next_focused_object == Some(id) {
+            self.next_focused_object = None;
         }
     }
 }
 
-struct ObjectNode {
+/// The nodes of an [`ObjectTree`].
+///
+/// Object IDs are the nodes' packed [arena keys](ArenaKey), so they are
+/// allocated by the tree in instantiation order, and an ID is never reused for
+/// another object once its object is removed.
+///
+/// ## Borrowing
+///
+/// Every slot owns its node's data, except while the node is borrowed mutably
+/// through a [`DetachedNode`], which holds the data until it's dropped. Nodes
+/// are only ever detached through `&mut ObjectTree`, or through the
+/// [`ObjectChildrenMut`] of their parent, so the only nodes detached at the
+/// same time are a node and its ancestors, which are never looked up through
+/// the node's children.
+#[derive(Default)]
+struct Nodes {
+    slots: Arena<NodeSlot>,
+    /// The IDs of the objects that were given a [stable
+    /// key](ObjectBuilder::with_key).
+    stable_keys: HashMap<String, u64>,
+    /// Keys which were already taken when an object was given them, see
+    /// [`ObjectTree::take_duplicate_key`].
+    duplicate_keys: Vec<String>,
+}
+
+struct NodeSlot {
+    parent: Option<ArenaKey>,
+    children: Vec<ArenaKey>,
+    /// The position of the node within its parent's children.
+    child_index: usize,
+    /// Only `None` while the node is [detached](DetachedNode).
+    data: Option<NodeData>,
+}
+
+impl NodeSlot {
+    fn data(&self) -> &NodeData {
+        self.data.as_ref().expect("node is detached")
+    }
+
+    fn data_mut(&mut self) -> &mut NodeData {
+        self.data.as_mut().expect("node is detached")
+    }
+}
+
+struct NodeData {
     object: Box<dyn Object>,
     state: ObjectState,
-    children: Vec<u64>,
+}
+
+impl Nodes {
+    /// Add a new node, allocating its ID.
+    ///
+    /// If the stable key is already taken, the node is added without it and
+    /// the key is recorded as a duplicate.
+    fn insert(
+        &mut self,
+        parent: Option<ArenaKey>,
+        object: Box<dyn Object>,
+        mut stable_key: Option<String>,
+    ) -> ArenaKey {
+        if let Some(taken) = stable_key.take_if(|key| self.stable_keys.contains_key(key.as_str())) {
+            self.duplicate_keys.push(taken);
+        }
+        let child_index = parent.map_or(0, |parent| {
+            self.slots
+                .get(parent)
+                .expect("parent exists")
+                .children
+                .len()
+        });
+        let key = self.slots.insert_with(|key| {
+            let mut state = ObjectState::new(key.to_bits());
+            state.key = stable_key.clone();
+
+            NodeSlot {
+                parent,
+                children: Vec::new(),
+                child_index,
+                data: Some(NodeData { object, state }),
+            }
+        });
+        if let Some(parent) = parent {
+            self.slots
+                .get_mut(parent)
+                .expect("parent exists")
+                .children
+                .push(key);
+        }
+
+        if let Some(stable_key) = stable_key {
+            self.stable_keys.insert(stable_key, key.to_bits());
+        }
+
+        key
+    }
+
+    /// Take the data of the node with the provided key out of its slot, until
+    /// the returned [`DetachedNode`] is dropped.
+    fn detach<'tree>(
+        &'tree mut self,
+        key: ArenaKey,
+        interaction: &'tree mut InteractionState,
+    ) -> Option<DetachedNode<'tree>> {
+        let slot = self.slots.get_mut(key)?;
+        let parent = slot.parent;
+        let data = slot.data.take().expect("node is detached");
+
+        Some(DetachedNode {
+            key,
+            parent,
+            data: Some(data),
+            nodes: self,
+            interaction,
+        })
+    }
+
+    /// Remove the node with the provided key and all of its descendants,
+    /// returning the node's data.
+    ///
+    /// Neither the node nor its descendants may be detached.
+    fn remove_subtree(
+        &mut self,
+        key: ArenaKey,
+        interaction: &mut InteractionState,
+    ) -> Option<NodeData> {
+        let mut slot = self.slots.remove(key)?;
+        if let Some(parent) = slot.parent {
+            let siblings = &mut self.slots.get_mut(parent).expect("parent exists").children;
+            siblings.swap_remove(slot.child_index);
+            if let Some(&moved) = siblings.get(slot.child_index) {
+                self.slots
+                    .get_mut(moved)
+                    .expect("sibling exists")
+                    .child_index = slot.child_index;
+            }
+        }
+
+        let mut descendants = std::mem::take(&mut slot.children);
+        while let Some(key) = descendants.pop() {
+            let descendant = self.slots.remove(key).expect("child exists");
+            descendants.extend_from_slice(&descendant.children);
+            self.forget(descendant, interaction);
+        }
+
+        Some(self.forget(slot, interaction))
+    }
+
+    /// Take a removed slot's data, forgetting about its object.
+    fn forget(&mut self, slot: NodeSlot, interaction: &mut InteractionState) -> NodeData {
+        let data = slot.data.expect("node is detached");
+        if let Some(stable_key) = &data.state.key {
+            self.stable_keys.remove(stable_key);
+        }
+        interaction.forget(data.state.id);
+
+        data
+    }
+}
+
+impl NodeData {
+    fn as_ref<'tree>(
+        &'tree self,
+        key: ArenaKey,
+        parent: Option<ArenaKey>,
+        nodes: &'tree Nodes,
+        interaction: &'tree InteractionState,
+    ) -> ObjectNodeRef<'tree> {
+        ObjectNodeRef {
+            parent_id: parent.map(ArenaKey::to_bits),
+            object: &*self.object,
+            state: &self.state,
+            children: ObjectChildrenRef {
+                parent_key: key,
+                nodes,
+                interaction,
+            },
+        }
+    }
+}
+
+/// An [object](Object) instance taken out of the [`ObjectTree`] while it's
+/// borrowed mutably, so that its children can be borrowed through the tree at
+/// the same time. It's put back into the tree when dropped.
+pub struct DetachedNode<'tree> {
+    key: ArenaKey,
+    parent: Option<ArenaKey>,
+    /// Only `None` once dropped.
+    data: Option<NodeData>,
+    nodes: &'tree mut Nodes,
+    interaction: &'tree mut InteractionState,
+}
+
+impl DetachedNode<'_> {
+    /// Get an exclusive (mutable) reference to the object instance.
+    pub fn node_mut(&mut self) -> ObjectNodeMut<'_> {
+        let data = self.data.as_mut().expect("node isn't dropped");
+
+        ObjectNodeMut {
+            parent_id: self.parent.map(ArenaKey::to_bits),
+            object: &mut data.object,
+            state: &mut data.state,
+            children: ObjectChildrenMut {
+                parent_key: self.key,
+                nodes: self.nodes,
+                interaction: self.interaction,
+            },
+        }
+    }
+}
+
+impl Drop for DetachedNode<'_> {
+    fn drop(&mut self) {
+        // Also runs while unwinding, so a panicking pass leaves the tree whole.
+        if let Some(slot) = self.nodes.slots.get_mut(self.key) {
+            slot.data = self.data.take();
+        }
+    }
 }
 
 /// A shared (immutable) reference to an [object](Object) instance.
 pub struct ObjectNodeRef<'tree> {
     pub parent_id: Option<u64>,
-    pub object: &'tree Box<dyn Object>,
+    pub object: &'tree dyn Object,
     pub state: &'tree ObjectState,
     pub children: ObjectChildrenRef<'tree>,
 }
@@ -316,7 +623,7 @@ impl ObjectNodeMut<'_> {
     pub fn reborrow(&self) -> ObjectNodeRef<'_> {
         ObjectNodeRef {
             parent_id: self.parent_id,
-            object: self.object,
+            object: &**self.object,
             state: self.state,
             children: self.children.reborrow(),
         }
@@ -335,51 +642,29 @@ impl ObjectNodeMut<'_> {
 /// A shared (immutable) reference to the children of an [object](Object)
 /// instance.
 pub struct ObjectChildrenRef<'tree> {
-    parent_id: Option<u64>,
-    all_nodes: &'tree HashMap<u64, Box<UnsafeCell<ObjectNode>>>,
-    all_parents: &'tree HashMap<u64, Option<u64>>,
+    parent_key: ArenaKey,
+    nodes: &'tree Nodes,
     pub(super) interaction: &'tree InteractionState,
 }
 
 impl<'tree> ObjectChildrenRef<'tree> {
     pub fn has(&self, id: u64) -> bool {
-        let child_id = id.into();
-        let parent_id = self.parent_id;
-
-        self.all_parents
-            .get(&child_id)
-            .is_some_and(|parent| *parent == parent_id)
+        child_slot(self.nodes, self.parent_key, id).is_some()
     }
 
     pub fn get(&self, id: u64) -> Option<ObjectNodeRef<'tree>> {
-        if self.has(id) {
-            let parent_id = *self.all_parents.get(&id)?;
-            let ObjectNode { object, state, .. } =
-                unsafe { self.all_nodes.get(&id)?.get().as_ref() }?;
-
-            let children = ObjectChildrenRef {
-                parent_id: Some(id),
-                all_nodes: self.all_nodes,
-                all_parents: self.all_parents,
-                interaction: self.interaction,
-            };
+        let (key, slot) = child_slot(self.nodes, self.parent_key, id)?;
 
-            Some(ObjectNodeRef {
-                parent_id,
-                object,
-                state,
-                children,
-            })
-        } else {
-            None
-        }
+        Some(
+            slot.data()
+                .as_ref(key, slot.parent, self.nodes, self.interaction),
+        )
     }
 
     pub fn reborrow(&self) -> ObjectChildrenRef<'tree> {
         ObjectChildrenRef {
-            parent_id: self.parent_id,
-            all_nodes: self.all_nodes,
-            all_parents: self.all_parents,
+            parent_key: self.parent_key,
+            nodes: self.nodes,
             interaction: self.interaction,
         }
     }
@@ -388,109 +673,93 @@ impl<'tree> ObjectChildrenRef<'tree> {
 /// An exclusive (mutable) reference to the children of an [object](Object)
 /// instance.
 pub struct ObjectChildrenMut<'tree> {
-    parent_id: Option<u64>,
-    children: &'tree mut Vec<u64>,
-    all_nodes: &'tree mut HashMap<u64, Box<UnsafeCell<ObjectNode>>>,
-    all_parents: &'tree mut HashMap<u64, Option<u64>>,
+    parent_key: ArenaKey,
+    nodes: &'tree mut Nodes,
     pub(super) interaction: &'tree mut InteractionState,
 }
 
 impl ObjectChildrenMut<'_> {
     pub fn has(&self, id: u64) -> bool {
-        let child_id = id.into();
-        let parent_id = self.parent_id;
-
-        self.all_parents
-            .get(&child_id)
-            .is_some_and(|parent| *parent == parent_id)
+        child_slot(self.nodes, self.parent_key, id).is_some()
     }
 
     pub fn get(&self, id: u64) -> Option<ObjectNodeRef<'_>> {
-        if self.has(id) {
-            let parent_id = *self.all_parents.get(&id)?;
-            let ObjectNode { object, state, .. } =
-                unsafe { self.all_nodes.get(&id)?.get().as_ref() }?;
-
-            let children = ObjectChildrenRef {
-                parent_id: Some(id),
-                all_nodes: self.all_nodes,
-                all_parents: self.all_parents,
-                interaction: self.interaction,
-            };
-
-            Some(ObjectNodeRef {
-                parent_id,
-                object,
-                state,
-                children,
-            })
-        } else {
-            None
-        }
+        self.reborrow().get(id)
     }
 
-    pub fn get_mut(&mut self, id: u64) -> Option<ObjectNodeMut<'_>> {
-        if self.has(id) {
-            let parent_id = *self.all_parents.get(&id)?;
-            let ObjectNode {
-                object,
-                state,
-                children,
-            } = unsafe { self.all_nodes.get(&id)?.get().as_mut() }?;
-
-            let children = ObjectChildrenMut {
-                parent_id: Some(id),
-                children,
-                all_nodes: self.all_nodes,
-                all_parents: self.all_parents,
-                interaction: self.interaction,
-            };
+    pub fn get_mut(&mut self, id: u64) -> Option<DetachedNode<'_>> {
+        let (key, _) = child_slot(self.nodes, self.parent_key, id)?;
 
-            Some(ObjectNodeMut {
-                parent_id,
-                object,
-                state,
-                children,
-            })
-        } else {
-            None
-        }
+        self.nodes.detach(key, self.interaction)
     }
 
-    pub fn push(&mut self, id: u64, object: Box<dyn Object>, state: ObjectState) {
-        self.all_parents.insert(id, self.parent_id);
+    /// Instantiate a new child, returning the ID allocated for it.
+    pub fn push(&mut self, builder: ObjectBuilder) -> u64 {
+        let ObjectBuilder { object, key } = builder;
 
-        self.children.push(id);
+        self.nodes
+            .insert(Some(self.parent_key), object, key)
+            .to_bits()
+    }
 
-        let node = ObjectNode {
-            object,
-            state,
-            children: Vec::new(),
-        };
+    /// Remove the child with the provided ID and all of its descendants from
+    /// the tree, returning the child's [object](Object).
+    pub fn remove(&mut self, id: u64) -> Option<Box<dyn Object>> {
+        let (key, _) = child_slot(self.nodes, self.parent_key, id)?;
+        // The child can't be detached, since it's only reachable through
+        // `&mut self`.
+        let data = self.nodes.remove_subtree(key, self.interaction)?;
+        self.interaction.damage.push(data.state.global_area);
 
-        self.all_nodes.insert(id, Box::new(UnsafeCell::new(node)));
+        Some(data.object)
     }
 
     pub fn reborrow(&self) -> ObjectChildrenRef<'_> {
         ObjectChildrenRef {
-            parent_id: self.parent_id,
-            all_nodes: self.all_nodes,
-            all_parents: self.all_parents,
+            parent_key: self.parent_key,
+            nodes: self.nodes,
             interaction: self.interaction,
         }
     }
 
     pub fn reborrow_mut(&mut self) -> ObjectChildrenMut<'_> {
         ObjectChildrenMut {
-            parent_id: self.parent_id,
-            children: self.children,
-            all_nodes: self.all_nodes,
-            all_parents: self.all_parents,
+            parent_key: self.parent_key,
+            nodes: self.nodes,
             interaction: self.interaction,
         }
     }
 }
 
+/// Get the slot of the child with the provided ID, if it's a child of the
+/// provided parent.
+#[inline]
+fn child_slot(nodes: &Nodes, parent_key: ArenaKey, id: u64) -> Option<(ArenaKey, &NodeSlot)> {
+    let key = ArenaKey::from_bits(id);
+    let slot = nodes.slots.get(key)?;
+
+    (slot.parent == Some(parent_key)).then_some((key, slot))
+}
+
+/// An error returned when more than one [object](Object) in the same
+/// [`ObjectTree`] was given the same [stable key](ObjectBuilder::with_key).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateKeyError {
+    pub key: String,
+}
+
+impl std::fmt::Display for DuplicateKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "multiple objects in the same tree were given the key {:?}",
+            self.key,
+        )
+    }
+}
+
+impl std::error::Error for DuplicateKeyError {}
+
 /// A handle to a potentially uninstantiated child [object](Object) instance.
 ///
 /// This object will be instantiated during the next [update
@@ -498,52 +767,68 @@ impl ObjectChildrenMut<'_> {
 /// [`update_child`](crate::UpdatePass::update_child) during its
 /// [`update_children`](Object::update_children) method.
 pub struct ChildObject {
-    id: u64,
     inner: ChildObjectInner,
 }
 
 impl ChildObject {
-    /// A unique identifier for this [object](Object).
+    /// A unique identifier for this [object](Object), allocated by the
+    /// [`ObjectTree`] when the object is instantiated, or `None` if it hasn't
+    /// been instantiated yet.
     #[inline]
-    pub fn id(&self) -> u64 {
-        self.id
+    pub fn id(&self) -> Option<u64> {
+        match self.inner {
+            ChildObjectInner::Existing(id) => Some(id),
+            ChildObjectInner::New(_) => None,
+        }
     }
 
     /// Whether this [object](Object) has been instantiated into the
     /// [`ObjectTree`].
     pub fn exists(&self) -> bool {
-        matches!(self.inner, ChildObjectInner::Existing)
+        matches!(self.inner, ChildObjectInner::Existing(_))
     }
 
-    pub(super) fn take_inner(&mut self) -> Option<ObjectBuilder> {
-        match std::mem::replace(&mut self.inner, ChildObjectInner::Existing) {
-            ChildObjectInner::New(builder) => Some(builder),
-            ChildObjectInner::Existing => None,
-        }
+    /// Instantiate the object with the provided function, which returns the
+    /// ID allocated for it. Does nothing if the object already exists.
+    pub(super) fn instantiate(&mut self, instantiate: impl FnOnce(ObjectBuilder) -> u64) {
+        self.inner = match std::mem::replace(&mut self.inner, ChildObjectInner::Existing(0)) {
+            ChildObjectInner::New(builder) => ChildObjectInner::Existing(instantiate(builder)),
+            existing => existing,
+        };
     }
 }
 
 enum ChildObjectInner {
-    Existing,
+    Existing(u64),
     New(ObjectBuilder),
 }
 
 /// A tool for building [objects](Object) to be placed into the [`ObjectTree`].
 pub struct ObjectBuilder {
-    pub(super) id: u64,
-    pub(super) object: Box<dyn Object>,
+    object: Box<dyn Object>,
+    key: Option<String>,
 }
 
 impl ObjectBuilder {
     /// Create a new [object](Object) builder.
     pub fn new<E: Object + 'static>(object: E) -> Self {
-        static NEXT_OBJECT_ID: AtomicU64 = AtomicU64::new(1);
-        let id = NEXT_OBJECT_ID.fetch_add(1, Ordering::Relaxed);
+        Self::from_box(Box::new(object))
+    }
 
-        Self {
-            id,
-            object: Box::new(object),
-        }
+    /// Create a new [object](Object) builder from an already boxed object.
+    pub fn from_box(object: Box<dyn Object>) -> Self {
+        Self { object, key: None }
+    }
+
+    /// Give the object a key which is unique within its tree, so that it can be
+    /// found with [`ObjectTree::find_by_key`] no matter which ID it is
+    /// allocated.
+    ///
+    /// Keys which are already taken are reported with a [`DuplicateKeyError`],
+    /// see [`ObjectTree::take_duplicate_key`].
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = Some(key.into());
+        self
     }
 
     /// Convert this object builder into an uninstantiated [`ChildObject`],
@@ -551,7 +836,6 @@ impl ObjectBuilder {
     /// pass](crate::update_pass).
     pub fn into_child(self) -> ChildObject {
         ChildObject {
-            id: self.id,
             inner: ChildObjectInner::New(self),
         }
     }
@@ -577,7 +861,7 @@ mod tests {
 
         impl Object for A {
             fn children_ids(&self) -> Vec<u64> {
-                vec![self.b.id(), self.d.id()]
+                self.b.id().into_iter().chain(self.d.id()).collect()
             }
 
             fn update_children(&mut self, pass: &mut crate::UpdatePass<'_>) {
@@ -606,7 +890,7 @@ mod tests {
 
         impl Object for B {
             fn children_ids(&self) -> Vec<u64> {
-                vec![self.c.id()]
+                self.c.id().into_iter().collect()
             }
 
             fn update_children(&mut self, pass: &mut crate::UpdatePass<'_>) {
@@ -660,9 +944,315 @@ mod tests {
             })
             .into_child(),
             d: ObjectBuilder::new(D).into_child(),
-        }));
+        }))
+        .unwrap();
 
         assert_eq!(INIT_NUM.load(Ordering::SeqCst), 4);
         assert_eq!(READY_NUM.load(Ordering::SeqCst), 4);
     }
+
+    /// A row of 10×10 children, which removes its last child when clicked
+    /// anywhere that isn't covered by a child.
+    struct Row {
+        children: Vec<ChildObject>,
+        remove_last: bool,
+    }
+
+    impl Object for Row {
+        fn children_ids(&self) -> Vec<u64> {
+            self.children.iter().filter_map(ChildObject::id).collect()
+        }
+
+        fn update_children(&mut self, pass: &mut crate::UpdatePass<'_>) {
+            for child in &mut self.children {
+                pass.update_child(child);
+            }
+            if std::mem::take(&mut self.remove_last)
+                && let Some(child) = self.children.pop()
+            {
+                pass.remove_child(child);
+            }
+        }
+
+        fn layout(&mut self, pass: &mut crate::LayoutPass<'_>) {
+            for (index, child) in self.children.iter_mut().enumerate() {
+                pass.do_layout(child, Size::new(10.0, 10.0));
+                pass.place_child(child, Point::new(index as f32 * 10.0, 0.0));
+            }
+        }
+
+        fn on_pointer_event(&mut self, pass: &mut crate::EventPass<'_>, event: &PointerEvent) {
+            if let PointerEvent::Down { .. } = event {
+                self.remove_last = true;
+                pass.request_update_children();
+            }
+        }
+    }
+
+    /// Takes focus when clicked.
+    struct Leaf;
+
+    impl Object for Leaf {
+        fn render(&self, pass: &mut crate::RenderPass<'_>, renderer: &mut dyn crate::Renderer) {
+            renderer.quad(pass.position(), pass.size(), crate::Rgba::rgb(0, 0, 0));
+        }
+
+        fn on_pointer_event(&mut self, pass: &mut crate::EventPass<'_>, event: &PointerEvent) {
+            if let PointerEvent::Down { .. } = event {
+                pass.request_focus();
+                pass.capture_pointer();
+                pass.set_handled();
+            }
+        }
+    }
+
+    /// Counts the quads drawn into it.
+    struct QuadCounter(u32);
+
+    impl crate::Renderer for QuadCounter {
+        fn text(&mut self, _: &str, _: Point, _: f32, _: crate::Rgba) {}
+
+        fn quad(&mut self, _: Point, _: Size, _: crate::Rgba) {
+            self.0 += 1;
+        }
+
+        fn image(&mut self, _: u64, _: Point, _: Size) {}
+
+        fn image_region(&mut self, _: u64, _: Point, _: Size, _: crate::Area, _: crate::Rgba) {}
+
+        fn icon(&mut self, _: u64, _: Point, _: Size, _: crate::Rgba) {}
+    }
+
+    /// Counts the objects outlined by the layout overlay.
+    struct OutlineCounter(u32);
+
+    impl crate::LayoutOverlay for OutlineCounter {
+        fn outline(&mut self, _: crate::Area, _: bool) {
+            self.0 += 1;
+        }
+
+        fn baseline(&mut self, _: Point, _: f32) {}
+
+        fn region(&mut self, _: crate::LayoutRegion) {}
+    }
+
+    fn click(tree: &mut ObjectTree, x: f32, y: f32) {
+        let button = crate::PointerButton::Primary;
+        let position = Some(Point::new(x, y));
+        tree.handle_pointer_event(PointerEvent::Move { position }, &mut ());
+        tree.handle_pointer_event(PointerEvent::Down { button }, &mut ());
+        tree.handle_pointer_event(PointerEvent::Up { button }, &mut ());
+    }
+
+    #[test]
+    fn passes_over_tree_with_removed_children() {
+        let children = (0..3)
+            .map(|_| ObjectBuilder::new(Leaf).into_child())
+            .collect::<Vec<_>>();
+        let mut tree = ObjectTree::new(Box::new(Row {
+            children,
+            remove_last: false,
+        }))
+        .unwrap();
+        let ids = tree.root_node().object.children_ids();
+        tree.resize(Size::new(100.0, 100.0), &mut ());
+
+        for (index, id) in ids.iter().enumerate() {
+            let node = tree.find(*id).unwrap();
+            assert_eq!(node.parent_id, Some(tree.root_id()));
+            assert_eq!(node.state.area().position.x, index as f32 * 10.0);
+            assert_eq!(tree.get_id_path(*id, None), vec![*id, tree.root_id()]);
+        }
+
+        // Focus and hover the last child.
+        click(&mut tree, 25.0, 5.0);
+        let last = tree.find(ids[2]).unwrap();
+        assert!(last.state.is_focused() && last.state.is_hovered());
+
+        let mut display_list = crate::DisplayList::default();
+        let mut quads = QuadCounter(0);
+        crate::render_pass(&mut tree, &mut quads);
+        assert_eq!(quads.0, 3);
+        assert!(crate::render_pass_damaged(&mut tree, &mut display_list));
+
+        // Clicking the row removes the focused child.
+        click(&mut tree, 50.0, 5.0);
+        assert!(tree.find(ids[2]).is_none());
+        assert!(tree.find_mut(ids[2]).is_none());
+        assert!(tree.get_id_path(ids[2], None).is_empty());
+        assert!(!tree.root_node().children.has(ids[2]));
+        assert!(tree.find(ids[1]).is_some());
+
+        // The removed child's area is damaged, and it's no longer rendered.
+        assert!(crate::render_pass_damaged(&mut tree, &mut display_list));
+        let mut quads = QuadCounter(0);
+        display_list.replay(&mut quads);
+        assert_eq!(quads.0, 2);
+
+        // Moving over where the child used to be hovers the row instead.
+        let position = Some(Point::new(25.0, 5.0));
+        tree.handle_pointer_event(PointerEvent::Move { position }, &mut ());
+        assert!(tree.root_node().state.is_hovered());
+        assert!(!tree.find(ids[1]).unwrap().state.is_hovered());
+
+        let mut outlines = OutlineCounter(0);
+        crate::layout_overlay_pass(&tree, &mut outlines);
+        assert_eq!(outlines.0, 3);
+    }
+
+    fn keyed_tree() -> ObjectTree {
+        let children = ["a", "b", "c"]
+            .into_iter()
+            .map(|key| ObjectBuilder::new(Leaf).with_key(key).into_child())
+            .collect();
+
+        ObjectTree::new(Box::new(Row {
+            children,
+            remove_last: false,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn ids_are_deterministic_and_never_reused() {
+        let mut tree = keyed_tree();
+        let ids = tree.root_node().object.children_ids();
+        assert_eq!(tree.root_id(), 0);
+
+        // Identical trees allocate identical IDs.
+        assert_eq!(keyed_tree().root_node().object.children_ids(), ids);
+        assert_eq!(tree.find_by_key("b").unwrap().state.id(), ids[1]);
+        assert_eq!(tree.find_by_key("b").unwrap().state.key(), Some("b"));
+
+        // Remove `c` and add a new child, which reuses its slot but not its ID.
+        let mut root = tree.root_node_mut();
+        let mut node = root.node_mut();
+        node.children.remove(ids[2]);
+        let new_id = node.children.push(ObjectBuilder::new(Leaf).with_key("c"));
+        drop(root);
+        assert_ne!(new_id, ids[2]);
+        assert!(tree.find(ids[2]).is_none());
+        assert_eq!(tree.find_by_key("c").unwrap().state.id(), new_id);
+        assert!(tree.find_by_key("d").is_none());
+    }
+
+    #[test]
+    fn duplicate_keys_are_reported() {
+        let children = ["a", "b", "a"]
+            .into_iter()
+            .map(|key| ObjectBuilder::new(Leaf).with_key(key).into_child())
+            .collect();
+        let error = ObjectTree::new(Box::new(Row {
+            children,
+            remove_last: false,
+        }))
+        .err()
+        .unwrap();
+        assert_eq!(error.key, "a");
+
+        // Objects added later keep the tree, but lose their key.
+        let mut tree = keyed_tree();
+        let a_id = tree.find_by_key("a").unwrap().state.id();
+        let new_id = tree
+            .root_node_mut()
+            .node_mut()
+            .children
+            .push(ObjectBuilder::new(Leaf).with_key("a"));
+        assert_eq!(tree.find_by_key("a").unwrap().state.id(), a_id);
+        assert_eq!(tree.find(new_id).unwrap().state.key(), None);
+        assert_eq!(
+            tree.take_duplicate_key().unwrap().to_string(),
+            "multiple objects in the same tree were given the key \"a\"",
+        );
+        assert!(tree.take_duplicate_key().is_none());
+
+        // Removing the new object leaves the original's key alone.
+        tree.root_node_mut().node_mut().children.remove(new_id);
+        assert_eq!(tree.find_by_key("a").unwrap().state.id(), a_id);
+    }
+
+    #[test]
+    fn panicking_pass_leaves_tree_whole() {
+        struct Panics;
+
+        impl Object for Panics {
+            fn layout(&mut self, _pass: &mut crate::LayoutPass<'_>) {
+                panic!("layout failed");
+            }
+        }
+
+        let mut tree = ObjectTree::new(Box::new(Row {
+            children: vec![ObjectBuilder::new(Panics).into_child()],
+            remove_last: false,
+        }))
+        .unwrap();
+        let id = tree.root_node().object.children_ids()[0];
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            tree.resize(Size::new(100.0, 100.0), &mut ());
+        }));
+        assert!(result.is_err());
+
+        // Every node detached by the pass was put back while unwinding.
+        assert_eq!(tree.find(id).unwrap().parent_id, Some(tree.root_id()));
+        assert!(tree.find_mut(id).is_some());
+    }
+
+    /// Build 100 chains of 1000 nested objects each, and print how long looking
+    /// up every node and sending an event to the deepest ones take. Nothing is
+    /// compared, the durations are only meant to be compared by hand across
+    /// changes to how nodes are stored.
+    ///
+    /// Run with `cargo test --release -p base -- --ignored --nocapture`.
+    #[test]
+    #[ignore = "timing test"]
+    fn lookup_deep_tree() {
+        use {crate::Flex, std::time::Instant};
+
+        const CHAINS: usize = 100;
+        const DEPTH: usize = 1_000;
+
+        let mut root = Flex::row();
+        for _ in 0..CHAINS {
+            let mut chain = Flex::column().with(Leaf, 0.0);
+            for _ in 2..DEPTH {
+                chain = Flex::column().with(chain, 0.0);
+            }
+            root = root.with(chain, 0.0);
+        }
+        let mut tree = ObjectTree::new(Box::new(root)).unwrap();
+
+        let mut ids = Vec::new();
+        let mut deepest = Vec::new();
+        let mut stack = vec![tree.root_id()];
+        while let Some(id) = stack.pop() {
+            ids.push(id);
+            let children = tree.find(id).unwrap().object.children_ids();
+            if children.is_empty() {
+                deepest.push(id);
+            }
+            stack.extend(children);
+        }
+        assert_eq!(ids.len(), CHAINS * DEPTH + 1);
+
+        let start = Instant::now();
+        for id in &ids {
+            assert_eq!(tree.find_mut(*id).unwrap().node_mut().state.id(), *id);
+        }
+        let lookup_duration = start.elapsed();
+
+        // Events bubble up from the deepest objects, finding every ancestor.
+        let start = Instant::now();
+        for id in &deepest {
+            crate::event_pass(&mut tree, Some(*id), |_, _| {});
+        }
+        let event_duration = start.elapsed();
+
+        println!(
+            "{} lookups: {lookup_duration:?}, \
+             {} events through {DEPTH} ancestors: {event_duration:?}",
+            ids.len(),
+            deepest.len(),
+        );
+    }
 }
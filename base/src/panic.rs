@@ -0,0 +1,98 @@
+//! # Panics
+//!
+//! Programs are loaded into the host as shared objects, each with its own copy
+//! of the standard library. A panic can only be caught by the copy it was
+//! raised with, so the host can't catch panics in programs itself. Instead, it
+//! calls into a program through the program's own build of [`catch_panic`],
+//! which it finds in the program's [manifest](crate::ProgramManifest).
+
+use std::{
+    any::Any,
+    cell::RefCell,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::Once,
+};
+
+
+
+thread_local! {
+    /// The latest panic on this thread, as recorded by the panic hook.
+    static LAST_PANIC: RefCell<Option<ProgramPanic>> = const { RefCell::new(None) };
+}
+
+/// A panic caught by [`catch_panic`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProgramPanic {
+    pub message: String,
+    /// Where the panic was raised, as `file:line:column`.
+    pub location: Option<String>,
+}
+
+impl fmt::Display for ProgramPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "panicked at {location}:\n{}", self.message),
+            None => write!(f, "panicked:\n{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for ProgramPanic {}
+
+/// Run `f`, catching any panic it raises.
+///
+/// The first call installs a panic hook recording where panics are raised,
+/// which defers to the previous hook.
+pub fn catch_panic(f: &mut dyn FnMut()) -> Result<(), ProgramPanic> {
+    static INSTALL_HOOK: Once = Once::new();
+    INSTALL_HOOK.call_once(|| {
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            LAST_PANIC.set(Some(ProgramPanic {
+                message: payload_message(info.payload()),
+                location: info.location().map(ToString::to_string),
+            }));
+            previous_hook(info);
+        }));
+    });
+
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        LAST_PANIC.take().unwrap_or_else(|| ProgramPanic {
+            message: payload_message(&*payload),
+            location: None,
+        })
+    })
+}
+
+fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panics_are_caught_with_their_location() {
+        assert_eq!(catch_panic(&mut || {}), Ok(()));
+
+        let line = line!() + 1;
+        let panic = catch_panic(&mut || panic!("oops {}", 42)).unwrap_err();
+        assert_eq!(panic.message, "oops 42");
+        assert!(
+            panic
+                .location
+                .unwrap()
+                .starts_with(&format!("base/src/panic.rs:{line}:"))
+        );
+    }
+}
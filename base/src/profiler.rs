@@ -0,0 +1,299 @@
+//! # Profiler
+//!
+//! See [`Profiler`] for details.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+
+
+/// Optional instrumentation for the passes over an
+/// [`ObjectTree`](crate::ObjectTree).
+///
+/// When enabled, every pass and every call to [`Object::measure`],
+/// [`Object::layout`] and [`Object::render`] is recorded as a
+/// [span](ProfileSpan) within the current [frame](FrameProfile).
+///
+/// [`Object::measure`]: crate::Object::measure
+/// [`Object::layout`]: crate::Object::layout
+/// [`Object::render`]: crate::Object::render
+pub struct Profiler {
+    enabled: bool,
+    frame_start: Instant,
+    /// The number of frames started so far, used to number frames and to
+    /// reject span tokens from earlier frames.
+    frame_count: u64,
+    current: FrameProfile,
+    depth: u32,
+    history: VecDeque<FrameProfile>,
+    history_capacity: usize,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frame_start: Instant::now(),
+            frame_count: 0,
+            current: FrameProfile::default(),
+            depth: 0,
+            history: VecDeque::new(),
+            history_capacity: 240,
+        }
+    }
+}
+
+impl Profiler {
+    #[inline]
+    pub const fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enable or disable recording. Disabling the profiler clears its history.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.current = FrameProfile::default();
+            self.history.clear();
+        }
+    }
+
+    /// The recorded frames, from oldest to newest.
+    #[inline]
+    pub fn history(&self) -> &VecDeque<FrameProfile> {
+        &self.history
+    }
+
+    /// Start recording a new frame, discarding anything recorded since the last
+    /// call to [`end_frame`](Self::end_frame).
+    pub fn begin_frame(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        self.frame_start = Instant::now();
+        self.frame_count += 1;
+        self.current = FrameProfile {
+            number: self.frame_count,
+            ..FrameProfile::default()
+        };
+        self.depth = 0;
+    }
+
+    /// Finish recording the current frame and move it into the history. Frames
+    /// in which nothing happened are dropped.
+    pub fn end_frame(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        let mut frame = std::mem::take(&mut self.current);
+        if frame.spans.is_empty() {
+            return;
+        }
+        frame.duration = self.frame_start.elapsed();
+
+        if self.history.len() >= self.history_capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(frame);
+    }
+
+    /// Start a span, which must be passed to [`end`](Self::end) once the
+    /// profiled work is done.
+    #[inline]
+    pub(crate) fn begin(&mut self, kind: SpanKind, object_id: Option<u64>) -> Option<SpanToken> {
+        if !self.enabled {
+            return None;
+        }
+
+        if kind == SpanKind::Measure {
+            self.current.measure_calls += 1;
+        }
+
+        let index = self.current.spans.len();
+        self.current.spans.push(ProfileSpan {
+            kind,
+            object_id,
+            depth: self.depth,
+            start: self.frame_start.elapsed(),
+            duration: Duration::ZERO,
+        });
+        self.depth += 1;
+
+        Some(SpanToken {
+            frame: self.current.number,
+            index,
+        })
+    }
+
+    #[inline]
+    pub(crate) fn end(&mut self, token: Option<SpanToken>) {
+        let Some(SpanToken { frame, index }) = token else {
+            return;
+        };
+        // The frame may have been restarted in the middle of the span, in which
+        // case the index points into a different frame.
+        if frame != self.current.number {
+            return;
+        }
+        let Some(span) = self.current.spans.get_mut(index) else {
+            return;
+        };
+
+        span.duration = self.frame_start.elapsed().saturating_sub(span.start);
+        self.depth = self.depth.saturating_sub(1);
+    }
+}
+
+pub(crate) struct SpanToken {
+    /// The [number](FrameProfile::number) of the frame the span was started in.
+    frame: u64,
+    index: usize,
+}
+
+/// Everything recorded by the [`Profiler`] between a call to
+/// [`begin_frame`](Profiler::begin_frame) and
+/// [`end_frame`](Profiler::end_frame).
+#[derive(Clone, Debug, Default)]
+pub struct FrameProfile {
+    /// The position of this frame among all frames recorded by the profiler,
+    /// starting at 1. Unlike an index into the
+    /// [history](Profiler::history), this stays the same as older frames are
+    /// dropped.
+    pub number: u64,
+    /// All spans in the order they were started.
+    pub spans: Vec<ProfileSpan>,
+    /// The number of times [`Object::measure`](crate::Object::measure) was
+    /// called.
+    pub measure_calls: u32,
+    pub duration: Duration,
+}
+
+impl FrameProfile {
+    /// The total time spent in spans of the given kind.
+    pub fn total(&self, kind: SpanKind) -> Duration {
+        self.spans
+            .iter()
+            .filter(|span| span.kind == kind)
+            .map(|span| span.duration)
+            .sum()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProfileSpan {
+    pub kind: SpanKind,
+    /// The object being called into, for per-object spans.
+    pub object_id: Option<u64>,
+    /// How many spans this one is nested in.
+    pub depth: u32,
+    /// When this span started, relative to the start of the frame.
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SpanKind {
+    UpdatePass,
+    EventPass,
+    HitTest,
+    LayoutPass,
+    ComposePass,
+    RenderPass,
+    Measure,
+    Layout,
+    Render,
+}
+
+impl SpanKind {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::UpdatePass => "update pass",
+            Self::EventPass => "event pass",
+            Self::HitTest => "hit test",
+            Self::LayoutPass => "layout pass",
+            Self::ComposePass => "compose pass",
+            Self::RenderPass => "render pass",
+            Self::Measure => "measure",
+            Self::Layout => "layout",
+            Self::Render => "render",
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{Flex, Label, ObjectTree, Size},
+    };
+
+    #[test]
+    fn record_nested_spans() {
+        let mut tree = ObjectTree::new(Box::new(
+            Flex::column()
+                .with(Label::new("A"), 0.0)
+                .with(Flex::row().with(Label::new("B"), 1.0), 0.0),
+        ))
+        .unwrap();
+        tree.profiler_mut().set_enabled(true);
+
+        tree.profiler_mut().begin_frame();
+        tree.resize(Size::new(100.0, 100.0), &mut ());
+        tree.profiler_mut().end_frame();
+
+        let frame = tree.profiler().history().back().unwrap();
+        let layout_pass = frame
+            .spans
+            .iter()
+            .find(|span| span.kind == SpanKind::LayoutPass)
+            .unwrap();
+        assert_eq!(layout_pass.depth, 0);
+        assert!(
+            frame.spans.iter().any(|span| {
+                span.kind == SpanKind::Layout && span.depth > layout_pass.depth + 1
+            })
+        );
+        assert!(frame.measure_calls > 0);
+        assert_eq!(
+            frame.measure_calls as usize,
+            frame
+                .spans
+                .iter()
+                .filter(|span| span.kind == SpanKind::Measure)
+                .count(),
+        );
+
+        // Frames without any passes aren't recorded.
+        tree.profiler_mut().begin_frame();
+        tree.profiler_mut().end_frame();
+        assert_eq!(tree.profiler().history().len(), 1);
+    }
+
+    #[test]
+    fn stale_tokens_are_ignored() {
+        let mut profiler = Profiler::default();
+        profiler.set_enabled(true);
+
+        profiler.begin_frame();
+        let stale = profiler.begin(SpanKind::LayoutPass, None);
+
+        // Restart the frame in the middle of the span, then start a new span at
+        // the same index.
+        profiler.begin_frame();
+        let current = profiler.begin(SpanKind::RenderPass, None);
+        std::thread::sleep(Duration::from_millis(1));
+        profiler.end(stale);
+        assert_eq!(profiler.current.spans[0].duration, Duration::ZERO);
+        assert_eq!(profiler.depth, 1);
+
+        profiler.end(current);
+        profiler.end_frame();
+        let frame = profiler.history().back().unwrap();
+        assert_eq!(frame.number, 2);
+        assert!(frame.spans[0].duration > Duration::ZERO);
+    }
+}
@@ -0,0 +1,70 @@
+//! # Program State
+//!
+//! Programs which only build a view export a `view` function. Programs which
+//! need to hold on to data, like models or the results of background work,
+//! export a [`ProgramState`] instead:
+//!
+//! ```ignore
+//! base::export_program!(state: MyState::default());
+//! ```
+//!
+//! The state lives as long as the build of the program it came from. When the
+//! program is rebuilt, the old state can hand a blob over to the new one, see
+//! [`ProgramState::on_unload`].
+
+use std::time::Duration;
+
+use crate::*;
+
+
+
+/// The state of a program, which builds its view.
+#[allow(unused)]
+pub trait ProgramState {
+    /// Called once when the program is first loaded, before anything else.
+    fn init(&mut self) {}
+
+    /// Called once instead of [`init`](ProgramState::init) when the program
+    /// replaces a previous build, with the blob the previous build returned
+    /// from [`on_unload`](ProgramState::on_unload).
+    ///
+    /// The blob may come from a build with a different layout of the state.
+    /// Blobs that can't be read should be discarded.
+    ///
+    /// *Defaults to discarding the blob and calling
+    /// [`init`](ProgramState::init).*
+    fn on_reload(&mut self, blob: Vec<u8>) {
+        self.init();
+    }
+
+    /// Build the root object of the program's tree. Called after
+    /// [`init`](ProgramState::init) or [`on_reload`](ProgramState::on_reload),
+    /// and whenever the view is rebuilt from
+    /// [`update`](ProgramState::update).
+    fn view(&mut self, context: &mut dyn ViewContext) -> Box<dyn Object>;
+
+    /// Called every frame the program is shown, with the time since the
+    /// previous update.
+    fn update(&mut self, context: &mut dyn UpdateContext, dt: Duration) {}
+
+    /// Called before the program is replaced by a new build. The returned
+    /// blob, if any, is passed to the new build's
+    /// [`on_reload`](ProgramState::on_reload). Builds which fail to load are
+    /// skipped, the blob is passed to the next one that loads.
+    ///
+    /// Not called if the program panicked, as its state can't be trusted.
+    fn on_unload(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+/// Lets a [`ProgramState`] affect the host while it's being updated.
+pub trait UpdateContext {
+    /// Rebuild the program's tree from [`ProgramState::view`] after this
+    /// update.
+    fn rebuild_view(&mut self);
+
+    /// Update the program again after the provided delay, even if nothing
+    /// happens in the host in the meantime.
+    fn request_update_after(&mut self, delay: Duration);
+}
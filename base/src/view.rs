@@ -0,0 +1,991 @@
+//! # Declarative Views
+//!
+//! A small markup for describing trees of built-in [objects](Object) without
+//! compiling any Rust code. See [`ViewRegistry`] for details.
+//!
+//! ```text
+//! // Comments start with two slashes.
+//! Flex(column, gap: 5) {
+//!     Label("Title", font_size: 40) [flex: 0]
+//!     Spacer [flex: 1]
+//!     Label("Footer", color: #737389) [key: "footer"]
+//! }
+//! ```
+//!
+//! Each node is a type name, followed by optional arguments in parentheses,
+//! optional parameters for its parent in square brackets, and optional
+//! children in braces. Children of a `Flex` can be given a `key` parameter,
+//! see [`ObjectBuilder::with_key`]. Unknown arguments and parameters are
+//! reported as errors.
+//!
+//! Compiled programs can load views from files with [`ViewRegistry::load`],
+//! and register their own objects, so that only changes to their logic need
+//! recompiling.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    Axis, AxisAlignment, CrossAlignment, Flex, FlexBasis, FlexParams, Icon, Image, ImageFit, Label,
+    Object, ObjectBuilder, Rgba, ViewContext,
+};
+
+
+
+/// A function that builds an [object](Object) from a [`ViewNode`].
+pub type ViewBuilderFn = fn(&ViewNode, &mut ViewBuilder<'_>) -> Result<Box<dyn Object>, ViewError>;
+
+/// A mapping of type names to the functions that build them.
+pub struct ViewRegistry {
+    builders: HashMap<String, ViewBuilderFn>,
+}
+
+impl Default for ViewRegistry {
+    fn default() -> Self {
+        Self::with_builtins()
+    }
+}
+
+impl ViewRegistry {
+    /// Create a new registry without any registered types.
+    pub fn empty() -> Self {
+        Self {
+            builders: HashMap::new(),
+        }
+    }
+
+    /// Create a new registry with all of the built-in types registered.
+    pub fn with_builtins() -> Self {
+        let mut this = Self::empty();
+        this.register("Flex", build_flex);
+        this.register("Label", build_label);
+        this.register("Image", build_image);
+        this.register("Icon", build_icon);
+        this
+    }
+
+    /// Register a builder function for the given type name, replacing any
+    /// previously registered builder.
+    pub fn register(&mut self, name: impl Into<String>, builder: ViewBuilderFn) {
+        self.builders.insert(name.into(), builder);
+    }
+
+    /// Whether a builder has been registered for the given type name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.builders.contains_key(name)
+    }
+
+    /// Read the view at the given path through the [`ViewContext`], and build
+    /// its root node. The host builds the view again whenever the file
+    /// changes, see [`ViewContext::read_view`].
+    pub fn load(
+        &self,
+        path: &str,
+        context: &mut dyn ViewContext,
+    ) -> Result<Box<dyn Object>, ViewError> {
+        let source = context.read_view(path).map_err(|error| ViewError {
+            message: format!("failed to read the view: {error}"),
+            path: Some(path.to_string()),
+            line: 0,
+            column: 0,
+        })?;
+
+        self.build_source(&source, context)
+            .map_err(|error| ViewError {
+                path: Some(path.to_string()),
+                ..error
+            })
+    }
+
+    /// Parse the given source and build its root node.
+    pub fn build_source(
+        &self,
+        source: &str,
+        context: &mut dyn ViewContext,
+    ) -> Result<Box<dyn Object>, ViewError> {
+        let node = ViewNode::parse(source)?;
+        self.build(&node, context)
+    }
+
+    /// Build the given node (and all of its children).
+    pub fn build(
+        &self,
+        node: &ViewNode,
+        context: &mut dyn ViewContext,
+    ) -> Result<Box<dyn Object>, ViewError> {
+        // Parameters are read by a node's parent, and the root has none.
+        if let Some((name, _)) = node.params.first() {
+            return Err(node.error(format!(
+                "unknown parameter `{name}`, the root node has no parent"
+            )));
+        }
+
+        ViewBuilder {
+            registry: self,
+            context,
+            keys: HashSet::new(),
+        }
+        .build(node)
+    }
+}
+
+/// The state passed to a [`ViewBuilderFn`].
+pub struct ViewBuilder<'a> {
+    registry: &'a ViewRegistry,
+    context: &'a mut dyn ViewContext,
+    /// The [stable keys](crate::ObjectBuilder::with_key) given to objects in
+    /// the view so far.
+    keys: HashSet<String>,
+}
+
+impl ViewBuilder<'_> {
+    /// Build the given node with the registered builder for its type.
+    pub fn build(&mut self, node: &ViewNode) -> Result<Box<dyn Object>, ViewError> {
+        let Some(builder) = self.registry.builders.get(&node.kind) else {
+            return Err(node.error(format!("unknown object type `{}`", node.kind)));
+        };
+
+        builder(node, self)
+    }
+
+    /// Claim a [stable key](crate::ObjectBuilder::with_key) for the given
+    /// node, failing if another object in the view was already given it.
+    pub fn claim_key(&mut self, node: &ViewNode, key: &str) -> Result<(), ViewError> {
+        if !self.keys.insert(key.to_string()) {
+            return Err(node.error(format!(
+                "the key {key:?} was already given to another object"
+            )));
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn context(&mut self) -> &mut dyn ViewContext {
+        self.context
+    }
+}
+
+
+
+/// A single parsed node in a declarative view.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ViewNode {
+    pub kind: String,
+    pub args: Vec<ViewValue>,
+    pub named_args: Vec<(String, ViewValue)>,
+    pub params: Vec<(String, ViewValue)>,
+    pub children: Vec<ViewNode>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl ViewNode {
+    /// Parse a single root node from the given source.
+    pub fn parse(source: &str) -> Result<Self, ViewError> {
+        let mut parser = Parser::new(source);
+        parser.skip_trivia();
+        let node = parser.node()?;
+        parser.skip_trivia();
+        if parser.peek().is_some() {
+            return Err(parser.error("expected end of input after the root node"));
+        }
+
+        Ok(node)
+    }
+
+    /// Get the positional argument at `index`, falling back to the named
+    /// argument `name`.
+    pub fn arg(&self, index: usize, name: &str) -> Option<&ViewValue> {
+        self.named(name).or_else(|| self.args.get(index))
+    }
+
+    /// Get the named argument `name`.
+    pub fn named(&self, name: &str) -> Option<&ViewValue> {
+        self.named_args
+            .iter()
+            .find_map(|(key, value)| (key == name).then_some(value))
+    }
+
+    /// Get the parent parameter `name`.
+    pub fn param(&self, name: &str) -> Option<&ViewValue> {
+        self.params
+            .iter()
+            .find_map(|(key, value)| (key == name).then_some(value))
+    }
+
+    /// Create an error pointing at this node.
+    pub fn error(&self, message: impl Into<String>) -> ViewError {
+        ViewError {
+            message: message.into(),
+            path: None,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    /// Get the named argument `name` as a number.
+    pub fn number(&self, name: &str) -> Result<Option<f32>, ViewError> {
+        self.named(name)
+            .map(|value| {
+                value
+                    .as_number()
+                    .ok_or_else(|| self.expected(name, "a number"))
+            })
+            .transpose()
+    }
+
+    /// Get the named argument `name` as a color.
+    pub fn color(&self, name: &str) -> Result<Option<Rgba>, ViewError> {
+        self.named(name)
+            .map(|value| {
+                value
+                    .as_color()
+                    .ok_or_else(|| self.expected(name, "a color"))
+            })
+            .transpose()
+    }
+
+    /// Get the named argument `name` as an identifier.
+    pub fn ident(&self, name: &str) -> Result<Option<&str>, ViewError> {
+        self.named(name)
+            .map(|value| {
+                value
+                    .as_ident()
+                    .ok_or_else(|| self.expected(name, "an identifier"))
+            })
+            .transpose()
+    }
+
+    /// Check that this node was only given the provided arguments, the first
+    /// of which can be passed by position.
+    pub fn check_args(&self, positional: &[&str], named: &[&str]) -> Result<(), ViewError> {
+        if self.args.len() > positional.len() {
+            return Err(self.error(format!(
+                "`{}` takes at most {} positional argument(s), but was given {}",
+                self.kind,
+                positional.len(),
+                self.args.len(),
+            )));
+        }
+        for (index, (name, _)) in self.named_args.iter().enumerate() {
+            if !positional.contains(&name.as_str()) && !named.contains(&name.as_str()) {
+                return Err(self.error(format!("unknown argument `{name}` for `{}`", self.kind)));
+            }
+            let positional_index = positional.iter().position(|arg| arg == name);
+            if positional_index.is_some_and(|position| position < self.args.len())
+                || self.named_args[..index]
+                    .iter()
+                    .any(|(other, _)| other == name)
+            {
+                return Err(self.error(format!("argument `{name}` was given more than once")));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that this node was only given the provided parent parameters.
+    pub fn check_params(&self, params: &[&str]) -> Result<(), ViewError> {
+        match self
+            .params
+            .iter()
+            .find(|(name, _)| !params.contains(&name.as_str()))
+        {
+            Some((name, _)) => Err(self.error(format!("unknown parameter `{name}`"))),
+            None => Ok(()),
+        }
+    }
+
+    /// Check that this node has no children.
+    pub fn check_no_children(&self) -> Result<(), ViewError> {
+        if self.children.is_empty() {
+            Ok(())
+        } else {
+            Err(self.error(format!("`{}` can't have children", self.kind)))
+        }
+    }
+
+    fn expected(&self, name: &str, what: &str) -> ViewError {
+        self.error(format!("expected `{name}` to be {what}"))
+    }
+}
+
+/// A value within a [`ViewNode`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ViewValue {
+    Number(f32),
+    String(String),
+    Color(Rgba),
+    Ident(String),
+}
+
+impl ViewValue {
+    pub fn as_number(&self) -> Option<f32> {
+        if let Self::Number(number) = self {
+            Some(*number)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        if let Self::String(string) = self {
+            Some(string)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_color(&self) -> Option<Rgba> {
+        if let Self::Color(color) = self {
+            Some(*color)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_ident(&self) -> Option<&str> {
+        if let Self::Ident(ident) = self {
+            Some(ident)
+        } else {
+            None
+        }
+    }
+}
+
+/// An error produced while parsing or building a declarative view.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ViewError {
+    pub message: String,
+    /// The path of the view, if it was [loaded](ViewRegistry::load) from a
+    /// file.
+    pub path: Option<String>,
+    /// The line of the error, starting at 1, or 0 if the error isn't about
+    /// the source.
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for ViewError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.path, self.line) {
+            (Some(path), 0) => write!(f, "{path}: ")?,
+            (Some(path), line) => write!(f, "{path}:{line}:{}: ", self.column)?,
+            (None, 0) => {}
+            (None, line) => write!(f, "{line}:{}: ", self.column)?,
+        }
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ViewError {}
+
+
+
+struct Parser<'src> {
+    chars: std::iter::Peekable<std::str::Chars<'src>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'src> Parser<'src> {
+    fn new(source: &'src str) -> Self {
+        Self {
+            chars: source.chars().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.chars.next()?;
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(ch)
+    }
+
+    fn error(&self, message: impl Into<String>) -> ViewError {
+        ViewError {
+            message: message.into(),
+            path: None,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn skip_trivia(&mut self) {
+        while let Some(ch) = self.peek() {
+            if ch.is_whitespace() {
+                self.bump();
+            } else if ch == '/' {
+                // Line comment; a lone slash is left for the caller to report.
+                let mut lookahead = self.chars.clone();
+                lookahead.next();
+                if lookahead.next() != Some('/') {
+                    return;
+                }
+                while self.peek().is_some_and(|ch| ch != '\n') {
+                    self.bump();
+                }
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_trivia();
+        if self.peek() == Some(expected) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ViewError> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{expected}`")))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ViewError> {
+        self.skip_trivia();
+        let mut ident = String::new();
+        while let Some(ch) = self.peek().filter(|ch| ch.is_alphanumeric() || *ch == '_') {
+            if ident.is_empty() && ch.is_numeric() {
+                break;
+            }
+            ident.push(ch);
+            self.bump();
+        }
+        if ident.is_empty() {
+            return Err(self.error("expected an identifier"));
+        }
+
+        Ok(ident)
+    }
+
+    fn node(&mut self) -> Result<ViewNode, ViewError> {
+        self.skip_trivia();
+        let (line, column) = (self.line, self.column);
+        let kind = self.ident()?;
+
+        let mut args = Vec::new();
+        let mut named_args = Vec::new();
+        if self.eat('(') {
+            while !self.eat(')') {
+                self.skip_trivia();
+                let value = self.value()?;
+                if let ViewValue::Ident(name) = &value
+                    && self.eat(':')
+                {
+                    named_args.push((name.clone(), self.value()?));
+                } else if named_args.is_empty() {
+                    args.push(value);
+                } else {
+                    return Err(self.error("positional arguments must come before named ones"));
+                }
+                if !self.eat(',') {
+                    self.expect(')')?;
+                    break;
+                }
+            }
+        }
+
+        let mut params = Vec::new();
+        if self.eat('[') {
+            while !self.eat(']') {
+                let name = self.ident()?;
+                self.expect(':')?;
+                params.push((name, self.value()?));
+                if !self.eat(',') {
+                    self.expect(']')?;
+                    break;
+                }
+            }
+        }
+
+        let mut children = Vec::new();
+        if self.eat('{') {
+            while !self.eat('}') {
+                if self.peek().is_none() {
+                    return Err(self.error(format!("unclosed children of `{kind}`")));
+                }
+                children.push(self.node()?);
+            }
+        }
+
+        Ok(ViewNode {
+            kind,
+            args,
+            named_args,
+            params,
+            children,
+            line,
+            column,
+        })
+    }
+
+    fn value(&mut self) -> Result<ViewValue, ViewError> {
+        self.skip_trivia();
+        match self.peek() {
+            Some('"') => {
+                self.bump();
+                let mut string = String::new();
+                loop {
+                    match self.bump() {
+                        Some('"') => break,
+                        Some('\\') => match self.bump() {
+                            Some('n') => string.push('\n'),
+                            Some('t') => string.push('\t'),
+                            Some(ch @ ('"' | '\\')) => string.push(ch),
+                            _ => return Err(self.error("invalid escape sequence")),
+                        },
+                        Some(ch) => string.push(ch),
+                        None => return Err(self.error("unterminated string")),
+                    }
+                }
+                Ok(ViewValue::String(string))
+            }
+            Some('#') => {
+                self.bump();
+                let mut hex = String::new();
+                while let Some(ch) = self.peek().filter(char::is_ascii_hexdigit) {
+                    hex.push(ch);
+                    self.bump();
+                }
+                parse_hex_color(&hex)
+                    .map(ViewValue::Color)
+                    .ok_or_else(|| self.error("expected a color in the form `#rrggbb[aa]`"))
+            }
+            Some(ch) if ch.is_ascii_digit() || ch == '-' || ch == '.' => {
+                let mut number = String::new();
+                while let Some(ch) = self
+                    .peek()
+                    .filter(|ch| ch.is_ascii_digit() || matches!(ch, '-' | '.'))
+                {
+                    number.push(ch);
+                    self.bump();
+                }
+                number
+                    .parse()
+                    .map(ViewValue::Number)
+                    .map_err(|_| self.error(format!("invalid number `{number}`")))
+            }
+            Some(_) => self.ident().map(ViewValue::Ident),
+            None => Err(self.error("expected a value")),
+        }
+    }
+}
+
+fn parse_hex_color(hex: &str) -> Option<Rgba> {
+    let channel = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+    match hex.len() {
+        6 => Some(Rgba::rgb(channel(0)?, channel(2)?, channel(4)?)),
+        8 => Some(Rgba::new(
+            channel(0)?,
+            channel(2)?,
+            channel(4)?,
+            channel(6)?,
+        )),
+        _ => None,
+    }
+}
+
+
+
+fn build_flex(
+    node: &ViewNode,
+    builder: &mut ViewBuilder<'_>,
+) -> Result<Box<dyn Object>, ViewError> {
+    node.check_args(&["axis"], &["gap", "main_align", "cross_align"])?;
+    let axis = match node.arg(0, "axis") {
+        None => Axis::Vertical,
+        Some(value) => match value.as_ident() {
+            Some("row" | "horizontal") => Axis::Horizontal,
+            Some("column" | "vertical") => Axis::Vertical,
+            _ => return Err(node.error("expected `axis` to be `row` or `column`")),
+        },
+    };
+
+    let mut flex = Flex::new(axis);
+    if let Some(gap) = node.number("gap")? {
+        flex = flex.gap(gap);
+    }
+    if let Some(alignment) = node.ident("main_align")? {
+        flex = flex.main_align(match alignment {
+            "start" => AxisAlignment::Start,
+            "center" => AxisAlignment::Center,
+            "end" => AxisAlignment::End,
+            "space_between" => AxisAlignment::SpaceBetween,
+            "space_evenly" => AxisAlignment::SpaceEvenly,
+            "space_around" => AxisAlignment::SpaceAround,
+            other => return Err(node.error(format!("unknown main alignment `{other}`"))),
+        });
+    }
+    if let Some(alignment) = node.ident("cross_align")? {
+        flex = flex.cross_align(parse_cross_alignment(node, alignment)?);
+    }
+
+    for child in &node.children {
+        let flex_factor = match child.param("flex") {
+            None => 0.0,
+            Some(value) => value
+                .as_number()
+                .ok_or_else(|| child.error("expected `flex` to be a number"))?,
+        };
+
+        if child.kind == "Spacer" {
+            child.check_args(&[], &[])?;
+            child.check_params(&["flex"])?;
+            child.check_no_children()?;
+            flex = flex.with_spacer(flex_factor);
+            continue;
+        }
+        child.check_params(&["flex", "basis", "align", "key"])?;
+
+        let basis = match child.param("basis").map(ViewValue::as_ident) {
+            None => None,
+            Some(Some("auto")) => Some(FlexBasis::Auto),
+            Some(Some("zero")) => Some(FlexBasis::Zero),
+            Some(_) => return Err(child.error("expected `basis` to be `auto` or `zero`")),
+        };
+        let alignment = match child.param("align") {
+            None => None,
+            Some(value) => {
+                let alignment = value
+                    .as_ident()
+                    .ok_or_else(|| child.error("expected `align` to be an identifier"))?;
+                Some(parse_cross_alignment(child, alignment)?)
+            }
+        };
+
+        let mut child_builder = ObjectBuilder::from_box(builder.build(child)?);
+        if let Some(key) = child.param("key") {
+            let key = key
+                .as_str()
+                .ok_or_else(|| child.error("expected `key` to be a string"))?;
+            builder.claim_key(child, key)?;
+            child_builder = child_builder.with_key(key);
+        }
+        flex = flex.with_builder(
+            child_builder,
+            FlexParams::new(flex_factor, basis, alignment),
+        );
+    }
+
+    Ok(Box::new(flex))
+}
+
+fn parse_cross_alignment(node: &ViewNode, alignment: &str) -> Result<CrossAlignment, ViewError> {
+    Ok(match alignment {
+        "start" => CrossAlignment::Start,
+        "center" => CrossAlignment::Center,
+        "end" => CrossAlignment::End,
+        "baseline" => CrossAlignment::Baseline,
+        "stretch" => CrossAlignment::Stretch,
+        other => return Err(node.error(format!("unknown cross alignment `{other}`"))),
+    })
+}
+
+fn build_label(
+    node: &ViewNode,
+    _builder: &mut ViewBuilder<'_>,
+) -> Result<Box<dyn Object>, ViewError> {
+    node.check_args(&["content"], &["font_size", "color"])?;
+    node.check_no_children()?;
+    let content = node
+        .arg(0, "content")
+        .and_then(ViewValue::as_str)
+        .ok_or_else(|| node.error("expected `Label` to have string content"))?;
+
+    let mut label = Label::new(content);
+    if let Some(font_size) = node.number("font_size")? {
+        label = label.font_size(font_size);
+    }
+    if let Some(color) = node.color("color")? {
+        label = label.color(color);
+    }
+
+    Ok(Box::new(label))
+}
+
+/// Load the texture at the path given as the first argument of the provided
+/// node.
+fn load_texture(node: &ViewNode, builder: &mut ViewBuilder<'_>) -> Result<u64, ViewError> {
+    let path = node
+        .arg(0, "path")
+        .and_then(ViewValue::as_str)
+        .ok_or_else(|| node.error(format!("expected `{}` to have a string path", node.kind)))?;
+
+    Ok(builder.context().load_texture(path))
+}
+
+fn build_image(
+    node: &ViewNode,
+    builder: &mut ViewBuilder<'_>,
+) -> Result<Box<dyn Object>, ViewError> {
+    node.check_args(&["path"], &["fit", "tint"])?;
+    node.check_no_children()?;
+    let mut image = Image::new(load_texture(node, builder)?);
+    if let Some(fit) = node.ident("fit")? {
+        image = image.fit(match fit {
+            "contain" => ImageFit::Contain,
+            "cover" => ImageFit::Cover,
+            "fill" => ImageFit::Fill,
+            "none" => ImageFit::None,
+            other => return Err(node.error(format!("unknown image fit `{other}`"))),
+        });
+    }
+    if let Some(tint) = node.color("tint")? {
+        image = image.tint(tint);
+    }
+
+    Ok(Box::new(image))
+}
+
+fn build_icon(
+    node: &ViewNode,
+    builder: &mut ViewBuilder<'_>,
+) -> Result<Box<dyn Object>, ViewError> {
+    node.check_args(&["path"], &["size", "color"])?;
+    node.check_no_children()?;
+    let mut icon = Icon::new(load_texture(node, builder)?);
+    if let Some(size) = node.number("size")? {
+        icon = icon.size(size);
+    }
+    if let Some(color) = node.color("color")? {
+        icon = icon.color(color);
+    }
+
+    Ok(Box::new(icon))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoTextures;
+
+    impl ViewContext for NoTextures {
+        fn load_texture(&mut self, _path: &str) -> u64 {
+            0
+        }
+
+        fn embed_asset(&mut self, _path: &str, _content: &[u8]) {}
+
+        fn read_view(&mut self, path: &str) -> Result<String, String> {
+            match path {
+                "header.view" => Ok("Flex(row) {\n    Title [key: \"title\"]\n}".into()),
+                "broken.view" => Ok("Flex {\n    Label(\"A\", size: 2)\n}".into()),
+                _ => Err("no such file".into()),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_nested_nodes() {
+        let node = ViewNode::parse(
+            r#"
+            // A comment.
+            Flex(row, gap: 5) {
+                Label("Hello, \"world\"", color: #ff000080) [flex: 1, align: end]
+                Spacer [flex: 0.5]
+            }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(node.kind, "Flex");
+        assert_eq!(node.args, vec![ViewValue::Ident("row".into())]);
+        assert_eq!(node.number("gap"), Ok(Some(5.0)));
+        assert_eq!(node.children.len(), 2);
+
+        let label = &node.children[0];
+        assert_eq!(
+            label.arg(0, "content").and_then(ViewValue::as_str),
+            Some("Hello, \"world\"")
+        );
+        assert_eq!(label.color("color"), Ok(Some(Rgba::new(255, 0, 0, 128))));
+        assert_eq!(label.param("flex"), Some(&ViewValue::Number(1.0)));
+        assert_eq!(label.param("align"), Some(&ViewValue::Ident("end".into())));
+        assert_eq!((label.line, label.column), (4, 17));
+
+        assert_eq!(node.children[1].kind, "Spacer");
+    }
+
+    #[test]
+    fn report_error_locations() {
+        let error = ViewNode::parse("Flex {\n    Label(\"unterminated)\n}").unwrap_err();
+        assert_eq!(error.message, "unterminated string");
+        assert_eq!(error.line, 3);
+
+        let error = ViewRegistry::with_builtins()
+            .build_source("Flex {\n    Button(\"Nope\")\n}", &mut NoTextures)
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "2:5: unknown object type `Button`");
+    }
+
+    #[test]
+    fn build_builtins() {
+        let registry = ViewRegistry::with_builtins();
+        let object = registry
+            .build_source(
+                "Flex(column, main_align: center) { Label(\"A\", font_size: 20) Spacer }",
+                &mut NoTextures,
+            )
+            .unwrap();
+        // Child IDs are allocated once the objects are in a tree.
+        let tree = crate::ObjectTree::new(object).unwrap();
+        assert_eq!(tree.root_node().object.children_ids().len(), 1);
+
+        let tree = crate::ObjectTree::new(
+            registry
+                .build_source("Flex(row) { Label(\"A\") [key: \"a\"] }", &mut NoTextures)
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(tree.find_by_key("a").is_some());
+
+        let error = registry
+            .build_source("Flex(diagonal)", &mut NoTextures)
+            .err()
+            .unwrap();
+        assert_eq!(error.message, "expected `axis` to be `row` or `column`");
+    }
+
+    #[test]
+    fn build_images_and_icons() {
+        let registry = ViewRegistry::with_builtins();
+        let object = registry
+            .build_source(
+                "Flex(row) {\n    Image(\"logo.png\", fit: cover, tint: #ffffff80)\n    \
+                 Icon(path: \"close.svg\", size: 24, color: #ff0000)\n}",
+                &mut NoTextures,
+            )
+            .unwrap();
+        let tree = crate::ObjectTree::new(object).unwrap();
+        assert_eq!(tree.root_node().object.children_ids().len(), 2);
+
+        let error = |source: &str| {
+            registry
+                .build_source(source, &mut NoTextures)
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            error("Image(\"a.png\", fit: stretch)"),
+            "1:1: unknown image fit `stretch`",
+        );
+        assert_eq!(
+            error("Image(\"a.png\", color: #ffffff)"),
+            "1:1: unknown argument `color` for `Image`",
+        );
+        assert_eq!(
+            error("Icon(size: 16)"),
+            "1:1: expected `Icon` to have a string path",
+        );
+        assert_eq!(
+            error("Icon(\"a.svg\", color: red)"),
+            "1:1: expected `color` to be a color",
+        );
+        assert_eq!(
+            error("Icon(\"a.svg\", tint: #ffffff)"),
+            "1:1: unknown argument `tint` for `Icon`",
+        );
+    }
+
+    #[test]
+    fn reject_duplicate_keys() {
+        let registry = ViewRegistry::with_builtins();
+        let error = registry
+            .build_source(
+                "Flex {\n    Label(\"A\") [key: \"x\"]\n    Flex {\n        Label(\"B\") [key: \"x\"]\n    }\n}",
+                &mut NoTextures,
+            )
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "4:9: the key \"x\" was already given to another object",
+        );
+    }
+
+    #[test]
+    fn reject_unknown_arguments() {
+        let registry = ViewRegistry::with_builtins();
+        let error = |source: &str| {
+            registry
+                .build_source(source, &mut NoTextures)
+                .err()
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(
+            error("Flex {\n    Label(\"A\", fnt_size: 12)\n}"),
+            "2:5: unknown argument `fnt_size` for `Label`",
+        );
+        assert_eq!(
+            error("Label(\"A\", \"B\")"),
+            "1:1: `Label` takes at most 1 positional argument(s), but was given 2",
+        );
+        assert_eq!(
+            error("Label(\"A\", content: \"B\")"),
+            "1:1: argument `content` was given more than once",
+        );
+        assert_eq!(
+            error("Flex {\n    Spacer [flex: 1, grow: 2]\n}"),
+            "2:5: unknown parameter `grow`",
+        );
+        assert_eq!(
+            error("Label(\"A\") [flex: 1]"),
+            "1:1: unknown parameter `flex`, the root node has no parent",
+        );
+        assert_eq!(
+            error("Label(\"A\") { Label(\"B\") }"),
+            "1:1: `Label` can't have children",
+        );
+    }
+
+    #[test]
+    fn load_views_with_custom_objects() {
+        let mut registry = ViewRegistry::with_builtins();
+        registry.register("Title", |node, _builder| {
+            node.check_args(&[], &[])?;
+            Ok(Box::new(Label::new("Title").font_size(40.0)))
+        });
+
+        let tree =
+            crate::ObjectTree::new(registry.load("header.view", &mut NoTextures).unwrap()).unwrap();
+        assert!(tree.find_by_key("title").is_some());
+
+        let error = registry.load("broken.view", &mut NoTextures).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "broken.view:2:5: unknown argument `size` for `Label`",
+        );
+        let error = registry
+            .load("missing.view", &mut NoTextures)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "missing.view: failed to read the view: no such file",
+        );
+    }
+}
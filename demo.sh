@@ -3,5 +3,4 @@
 
 set -ex
 
-cargo build --package base
 cargo run
@@ -5,17 +5,19 @@
 
 extern crate base;
 
-use std::any::TypeId;
-
 use base::*;
 
 
-#[unsafe(no_mangle)]
-pub static __OBJECT_TYPE_ID: TypeId = OBJECT_TYPE_ID;
+base::export_program!(view: view);
 
-#[unsafe(no_mangle)]
-pub extern "Rust" fn view(context: &mut dyn ViewContext) -> Box<dyn Object> {
+fn view(context: &mut dyn ViewContext) -> Box<dyn Object> {
     let texture_id = context.load_texture("res/light.png");
+    let note_icon = context.load_texture("res/note.svg");
+    let note = ViewRegistry::with_builtins()
+        .load("res/note.view", context)
+        .unwrap_or_else(|error| {
+            Box::new(Label::new(error.to_string()).color(Rgba::rgb(0xff, 0x60, 0x60)))
+        });
     Box::new(
         Flex::column()
             .gap(5.0)
@@ -24,32 +26,19 @@ pub extern "Rust" fn view(context: &mut dyn ViewContext) -> Box<dyn Object> {
                     .gap(10.0)
                     .main_align(AxisAlignment::SpaceEvenly)
                     .with(
-                        Label::new("2/17/2026, 7:00 AM")
-                            .font_size(12.0)
+                        Icon::new(note_icon)
+                            .size(18.0)
                             .color(Rgba::rgb(0x73, 0x73, 0x89)),
                         0.0,
                     )
                     .with(
-                        Label::new("This is a note, or something like that...")
-                            .font_size(18.0)
-                            .color(Rgba::rgb(0xaa, 0xaa, 0xad)),
-                        1.0,
-                    ),
-                0.0,
-            )
-            .with(Label::new("Another").font_size(40.0), 0.0)
-            .with(
-                Flex::row()
-                    .gap(10.0)
-                    .main_align(AxisAlignment::SpaceEvenly)
-                    .with(
-                        Label::new("2/17/2026, 7:01 AM")
+                        Label::new("2/17/2026, 7:00 AM")
                             .font_size(12.0)
                             .color(Rgba::rgb(0x73, 0x73, 0x89)),
                         0.0,
                     )
                     .with(
-                        Label::new("And this is another note...")
+                        Label::new("This is a note, or something like that...")
                             .font_size(18.0)
                             .color(Rgba::rgb(0xaa, 0xaa, 0xad)),
                         1.0,
@@ -57,8 +46,17 @@ pub extern "Rust" fn view(context: &mut dyn ViewContext) -> Box<dyn Object> {
                 0.0,
             )
             .with(Label::new("Another").font_size(40.0), 0.0)
+            .with_builder(ObjectBuilder::from_box(note), 0.0)
+            .with(Label::new("Another").font_size(40.0), 0.0)
+            .with(TestingObject::new(texture_id), 0.0)
             .with(TestingObject::new(texture_id), 0.0)
-            .with(TestingObject::new(texture_id), 0.0),
+            .with(
+                Image::new(texture_id)
+                    .fit(ImageFit::None)
+                    .alignment(ImageAlignment::LEFT)
+                    .tint(Rgba::rgb(0xaa, 0xaa, 0xad)),
+                0.0,
+            ),
     )
 }
 
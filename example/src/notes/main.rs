@@ -0,0 +1,67 @@
+//! # Notes
+//!
+//! An example of a program split across several modules, which keeps its
+//! state across rebuilds.
+
+extern crate base;
+
+mod note;
+
+use {
+    base::*,
+    note::note,
+    std::time::Duration,
+};
+
+
+base::export_program!(state: Notes::default());
+
+
+
+#[derive(Default)]
+struct Notes {
+    /// How long the notes have been open for, across rebuilds.
+    open_for: Duration,
+    /// The time since the view was last rebuilt.
+    since_rebuild: Duration,
+}
+
+impl ProgramState for Notes {
+    fn on_reload(&mut self, blob: Vec<u8>) {
+        // Blobs of any other length come from an incompatible build.
+        if blob.len() == 8 {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&blob);
+            self.open_for = Duration::from_millis(u64::from_le_bytes(bytes));
+        }
+    }
+
+    fn view(&mut self, _context: &mut dyn ViewContext) -> Box<dyn Object> {
+        Box::new(
+            Flex::column()
+                .gap(5.0)
+                .with(note("2/17/2026, 7:00 AM", "This is a note, or something like that..."), 0.0)
+                .with(note("2/17/2026, 7:01 AM", "And this is another note..."), 0.0)
+                .with(
+                    Label::new(format!("Open for {} s", self.open_for.as_secs()))
+                        .font_size(12.0)
+                        .color(Rgba::rgb(0x73, 0x73, 0x89)),
+                    0.0,
+                ),
+        )
+    }
+
+    fn update(&mut self, context: &mut dyn UpdateContext, dt: Duration) {
+        self.open_for += dt;
+        self.since_rebuild += dt;
+        if self.since_rebuild >= Duration::from_secs(1) {
+            self.since_rebuild = Duration::ZERO;
+            context.rebuild_view();
+        }
+        context.request_update_after(Duration::from_secs(1) - self.since_rebuild);
+    }
+
+    fn on_unload(&mut self) -> Option<Vec<u8>> {
+        Some((self.open_for.as_millis() as u64).to_le_bytes().to_vec())
+    }
+}
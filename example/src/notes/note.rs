@@ -0,0 +1,22 @@
+use base::*;
+
+
+
+/// A single note, along with when it was written.
+pub fn note(date: &str, text: &str) -> Flex {
+    Flex::row()
+        .gap(10.0)
+        .main_align(AxisAlignment::SpaceEvenly)
+        .with(
+            Label::new(date)
+                .font_size(12.0)
+                .color(Rgba::rgb(0x73, 0x73, 0x89)),
+            0.0,
+        )
+        .with(
+            Label::new(text)
+                .font_size(18.0)
+                .color(Rgba::rgb(0xaa, 0xaa, 0xad)),
+            1.0,
+        )
+}
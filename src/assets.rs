@@ -0,0 +1,414 @@
+//! # Assets
+//!
+//! See [`AssetManager`] for details.
+
+use {
+    crate::{
+        svg::Svg,
+        vfs::{self, Vfs, VfsFile},
+    },
+    eframe::egui,
+    std::{
+        collections::{HashMap, HashSet},
+        path::{Path, PathBuf},
+        sync::mpsc::{self, Receiver, Sender},
+    },
+};
+
+
+
+/// The largest size, in pixels, vector images are rasterized at.
+const MAX_RASTER_SIZE: u32 = 4096;
+
+/// The assets loaded by a program, which are decoded on background threads.
+/// Assets are images, either raster images or SVGs.
+///
+/// Assets are read from a [`Vfs`], and identified by the IDs handed out by
+/// [`AssetManager::load`], which are valid straight away. Until a texture is
+/// ready, and if it fails to load, a placeholder is drawn in its place.
+///
+/// The manager is owned by the program rather than by one of its builds, so
+/// assets survive reloads and keep their IDs. Modified asset files are loaded
+/// again under the same ID, see [`AssetManager::on_file_changed`].
+pub struct AssetManager {
+    egui_context: egui::Context,
+    vfs: Vfs,
+    /// The ID of every asset, by path.
+    ids: HashMap<String, u64>,
+    assets: HashMap<u64, Asset>,
+    next_id: u64,
+    /// The files on disk of the assets loaded since the last call to
+    /// [`AssetManager::take_unwatched`].
+    unwatched: Vec<PathBuf>,
+    /// The canonical paths of the views which were read, which the view is
+    /// built from.
+    views: HashSet<PathBuf>,
+    sender: Sender<Decoded>,
+    receiver: Receiver<Decoded>,
+}
+
+/// The result of decoding an asset on a background thread.
+struct Decoded {
+    id: u64,
+    /// The [generation](Asset::generation) of the load this is the result of.
+    generation: u64,
+    result: Result<AssetState, String>,
+}
+
+pub struct Asset {
+    /// The path of the asset in the [`Vfs`].
+    pub path: String,
+    /// The canonical path of the asset's file, if it's on disk and exists,
+    /// which file changes are matched against.
+    canonical_path: Option<PathBuf>,
+    pub state: AssetState,
+    /// How many times the asset started loading. Loads can finish out of
+    /// order, so only the result of the latest one is kept.
+    generation: u64,
+    /// Whether the latest build of the program's view loaded this asset.
+    pub used: bool,
+}
+
+pub enum AssetState {
+    Loading,
+    Loaded(egui::TextureHandle),
+    Vector(VectorImage),
+    Failed(String),
+}
+
+/// An SVG, along with the rasters it's drawn with.
+pub struct VectorImage {
+    pub svg: Svg,
+    /// The texture of every raster, by size and by whether it's a mask,
+    /// along with whether it was drawn since the last eviction.
+    rasters: HashMap<([u32; 2], bool), (egui::TextureHandle, bool)>,
+}
+
+impl AssetManager {
+    /// Create a manager for assets relative to the provided directory.
+    pub fn new(egui_context: egui::Context, root: PathBuf) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            egui_context,
+            vfs: Vfs::new(root),
+            ids: HashMap::new(),
+            assets: HashMap::new(),
+            next_id: 0,
+            unwatched: Vec::new(),
+            views: HashSet::new(),
+            sender,
+            receiver,
+        }
+    }
+
+    /// Start loading the asset at the provided path, unless it's already
+    /// loaded or loading, and get its ID. Equivalent paths, like `./x.svg`
+    /// and `x.svg`, share an asset.
+    pub fn load(&mut self, path: &str) -> u64 {
+        let path = vfs::normalize(path);
+        if let Some(&id) = self.ids.get(&path) {
+            self.assets.get_mut(&id).unwrap().used = true;
+            return id;
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.ids.insert(path.clone(), id);
+        self.assets.insert(
+            id,
+            Asset {
+                path,
+                canonical_path: None,
+                state: AssetState::Loading,
+                generation: 0,
+                used: true,
+            },
+        );
+        self.start_loading(id);
+
+        id
+    }
+
+    /// Embed the asset at the provided path, so that it's read from the
+    /// provided content instead of from disk. Loaded assets are loaded again
+    /// if their content changed.
+    pub fn embed(&mut self, path: &str, content: &[u8]) {
+        if self.vfs.embed(path, content)
+            && let Some(&id) = self.ids.get(&vfs::normalize(path))
+        {
+            self.start_loading(id);
+        }
+    }
+
+    /// Change the directory asset paths are relative to, loading every asset
+    /// again if it changed.
+    pub fn set_root(&mut self, root: PathBuf) {
+        if self.vfs.set_root(root) {
+            let ids = self.assets.keys().copied().collect::<Vec<_>>();
+            for id in ids {
+                self.start_loading(id);
+            }
+        }
+    }
+
+    /// Read the declarative view at the provided path, which is watched like
+    /// the files of assets.
+    pub fn read_view(&mut self, path: &str) -> Result<String, String> {
+        let file = self.vfs.open(path);
+        if let Some(disk_path) = file.disk_path() {
+            self.unwatched
+                .push(std::path::absolute(disk_path).unwrap_or_else(|_| disk_path.to_path_buf()));
+            if let Ok(canonical_path) = disk_path.canonicalize() {
+                self.views.insert(canonical_path);
+            }
+        }
+
+        let content = file.read().map_err(|error| error.to_string())?;
+        String::from_utf8(content.into_owned()).map_err(|error| error.to_string())
+    }
+
+    /// Load the asset with the provided ID from its current file. The asset
+    /// keeps being drawn as it was until it's ready.
+    fn start_loading(&mut self, id: u64) {
+        let asset = self.assets.get_mut(&id).unwrap();
+        let file = self.vfs.open(&asset.path);
+        asset.canonical_path = file
+            .disk_path()
+            .and_then(|disk_path| disk_path.canonicalize().ok());
+        if let Some(disk_path) = file.disk_path() {
+            self.unwatched
+                .push(std::path::absolute(disk_path).unwrap_or_else(|_| disk_path.to_path_buf()));
+        }
+
+        asset.generation += 1;
+
+        let generation = asset.generation;
+        let name = asset.path.clone();
+        let sender = self.sender.clone();
+        let egui_context = self.egui_context.clone();
+        std::thread::spawn(move || {
+            let result = decode(&name, &file, &egui_context);
+            // The assets may have been dropped along with their program.
+            let _ = sender.send(Decoded {
+                id,
+                generation,
+                result,
+            });
+            egui_context.request_repaint();
+        });
+    }
+
+    /// Pick up the assets which finished loading, returning their IDs.
+    pub fn poll(&mut self) -> Vec<u64> {
+        let mut finished = Vec::new();
+        for decoded in self.receiver.try_iter() {
+            // Results of loads superseded by a later one are stale.
+            if let Some(asset) = self.assets.get_mut(&decoded.id)
+                && asset.generation == decoded.generation
+            {
+                asset.state = decoded.result.unwrap_or_else(AssetState::Failed);
+                finished.push(decoded.id);
+            }
+        }
+
+        finished
+    }
+
+    #[inline]
+    pub fn get(&self, id: u64) -> Option<&Asset> {
+        self.assets.get(&id)
+    }
+
+    /// The intrinsic size of the asset with the provided ID, in pixels for
+    /// raster images and in points for vector images, if it has loaded.
+    pub fn size(&self, id: u64) -> Option<[f32; 2]> {
+        match &self.assets.get(&id)?.state {
+            AssetState::Loaded(handle) => Some(handle.size().map(|length| length as f32)),
+            AssetState::Vector(vector) => Some([vector.svg.width, vector.svg.height]),
+            AssetState::Loading | AssetState::Failed(_) => None,
+        }
+    }
+
+    /// Get the texture to draw the asset with the provided ID with, if it has
+    /// loaded. Vector images are rasterized at the provided size in pixels,
+    /// in white if `mask` is set, so that they can be recolored.
+    pub fn texture(&mut self, id: u64, size: [u32; 2], mask: bool) -> Option<egui::TextureId> {
+        let asset = self.assets.get_mut(&id)?;
+        let vector = match &mut asset.state {
+            AssetState::Loaded(handle) => return Some(handle.id()),
+            AssetState::Vector(vector) => vector,
+            AssetState::Loading | AssetState::Failed(_) => return None,
+        };
+
+        let size = size.map(|length| length.clamp(1, MAX_RASTER_SIZE));
+        let (handle, drawn) = vector.rasters.entry((size, mask)).or_insert_with(|| {
+            let image = vector.svg.rasterize(size[0], size[1], mask);
+            let handle = self.egui_context.load_texture(
+                format!("{} ({}x{})", asset.path, size[0], size[1]),
+                image,
+                egui::TextureOptions::LINEAR,
+            );
+            (handle, false)
+        });
+        *drawn = true;
+
+        Some(handle.id())
+    }
+
+    /// Free the rasters of vector images which weren't drawn since the last
+    /// call. This should be called after drawing everything, as the rasters
+    /// drawn before are freed.
+    pub fn evict_rasters(&mut self) {
+        for asset in self.assets.values_mut() {
+            if let AssetState::Vector(vector) = &mut asset.state {
+                vector.rasters.retain(|_, (_, drawn)| std::mem::take(drawn));
+            }
+        }
+    }
+
+    /// Mark every asset as unused until it's loaded again. Called before the
+    /// program's view is built, so that the assets it no longer loads can be
+    /// reported.
+    pub fn start_view_build(&mut self) {
+        for asset in self.assets.values_mut() {
+            asset.used = false;
+        }
+    }
+
+    /// Load the asset with the file at the provided canonical path again, if
+    /// there is one. Returns whether the file is a view which was read, in
+    /// which case the view should be built again.
+    pub fn on_file_changed(&mut self, path: &Path) -> bool {
+        // Files which didn't exist when their asset was first loaded may have
+        // been created since.
+        for asset in self.assets.values_mut() {
+            if asset.canonical_path.is_none() {
+                asset.canonical_path = self
+                    .vfs
+                    .open(&asset.path)
+                    .disk_path()
+                    .and_then(|disk_path| disk_path.canonicalize().ok());
+            }
+        }
+
+        if let Some(id) = self
+            .assets
+            .iter()
+            .find_map(|(&id, asset)| (asset.canonical_path.as_deref() == Some(path)).then_some(id))
+        {
+            self.start_loading(id);
+        }
+
+        self.views.contains(path)
+    }
+
+    /// Get the paths of the assets loaded since the last call, which should
+    /// be watched for changes.
+    pub fn take_unwatched(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.unwatched)
+    }
+
+    /// The assets the latest build of the view loaded which failed to load,
+    /// along with why.
+    pub fn errors(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.assets
+            .values()
+            .filter(|asset| asset.used)
+            .filter_map(|asset| match &asset.state {
+                AssetState::Failed(error) => Some((asset.path.as_str(), error.as_str())),
+                _ => None,
+            })
+    }
+
+    /// The paths of the assets which the latest build of the view didn't load.
+    /// They're kept around in case a later build loads them again.
+    pub fn unused(&self) -> impl Iterator<Item = &str> {
+        self.assets
+            .values()
+            .filter(|asset| !asset.used)
+            .map(|asset| asset.path.as_str())
+    }
+}
+
+/// Decode the file of the asset at the provided path, uploading it right away
+/// unless it's a vector image.
+fn decode(path: &str, file: &VfsFile, egui_context: &egui::Context) -> Result<AssetState, String> {
+    let content = file.read().map_err(|error| error.to_string())?;
+    if Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("svg"))
+    {
+        return Ok(AssetState::Vector(VectorImage {
+            svg: Svg::parse(&content)?,
+            rasters: HashMap::new(),
+        }));
+    }
+
+    let image = image::load_from_memory(&content).map_err(|error| error.to_string())?;
+    let size = [image.width() as _, image.height() as _];
+    let image_buffer = image.to_rgba8();
+    let pixels = image_buffer.as_flat_samples();
+
+    Ok(AssetState::Loaded(egui_context.load_texture(
+        path,
+        egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice()),
+        egui::TextureOptions::LINEAR,
+    )))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wait for the assets in flight to finish loading.
+    fn poll_until_loaded(assets: &mut AssetManager, id: u64) {
+        for _ in 0..500 {
+            if assets.poll().contains(&id) {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("asset {id} didn't finish loading");
+    }
+
+    #[test]
+    fn equivalent_paths_share_an_asset() {
+        let mut assets = AssetManager::new(egui::Context::default(), PathBuf::from("/nonexistent"));
+        let id = assets.load("./icons/x.svg");
+        assert_eq!(assets.load("icons/x.svg"), id);
+        assert_eq!(assets.load("icons//x.svg"), id);
+        assert_eq!(assets.load("icons/../icons/x.svg"), id);
+        assert_ne!(assets.load("icons/y.svg"), id);
+        assert_eq!(assets.get(id).unwrap().path, "icons/x.svg");
+    }
+
+    #[test]
+    fn stale_loads_are_dropped() {
+        let mut assets = AssetManager::new(egui::Context::default(), PathBuf::from("/nonexistent"));
+        let id = assets.load("image.png");
+        assets.embed("image.png", b"not an image");
+        poll_until_loaded(&mut assets, id);
+        let AssetState::Failed(error) = &assets.get(id).unwrap().state else {
+            panic!("the asset shouldn't have loaded");
+        };
+        let latest_error = error.clone();
+
+        // A load that finishes after a later one is ignored.
+        assets
+            .sender
+            .send(Decoded {
+                id,
+                generation: 1,
+                result: Err("stale".to_string()),
+            })
+            .unwrap();
+        assert!(assets.poll().is_empty());
+        let AssetState::Failed(error) = &assets.get(id).unwrap().state else {
+            panic!("the asset shouldn't have loaded");
+        };
+        assert_eq!(*error, latest_error);
+        assert_ne!(latest_error, "stale");
+    }
+}
@@ -0,0 +1,293 @@
+//! # Compile Queue
+//!
+//! See [`CompileQueue`] for details.
+
+use {
+    crate::{
+        WORKSPACE_DIR,
+        compiler::{self, CompileInput, CompileOptions},
+    },
+    eframe::egui,
+    std::{
+        path::{Path, PathBuf},
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+            mpsc::{self, Receiver, TryRecvError},
+        },
+        time::{Duration, Instant},
+    },
+};
+
+
+
+/// Compiles the successive revisions of a single program's source.
+///
+/// At most one compilation runs at a time, so that builds of the same program
+/// never race to write its shared object. Requesting a new revision cancels
+/// the running compilation, and supersedes any revision still waiting for it
+/// to stop.
+pub struct CompileQueue {
+    output_path: PathBuf,
+    running: Option<RunningJob>,
+    queued: Option<CompileJob>,
+    /// Compiles a single job on a background thread, which is only replaced
+    /// in tests.
+    run: RunFn,
+    egui_context: egui::Context,
+}
+
+type RunFn = fn(&CompileInput, &Path, CompileOptions, &AtomicBool) -> anyhow::Result<()>;
+
+/// A revision of a program's source to compile.
+pub struct CompileJob {
+    pub revision: u64,
+    pub input: CompileInput,
+    pub options: CompileOptions,
+}
+
+/// The result of compiling a revision of a program's source.
+pub struct CompileOutput {
+    pub revision: u64,
+    /// The source text which was compiled.
+    pub content: String,
+    pub result: Result<(), String>,
+    /// How long the compilation took, including building `base`.
+    pub duration: Duration,
+    pub options: CompileOptions,
+}
+
+struct RunningJob {
+    revision: u64,
+    content: String,
+    options: CompileOptions,
+    cancel: Arc<AtomicBool>,
+    result: Receiver<(Result<(), String>, Duration)>,
+}
+
+impl CompileQueue {
+    pub fn new(output_name: &str, egui_context: egui::Context) -> Self {
+        Self {
+            output_path: PathBuf::from(format!("{WORKSPACE_DIR}/target/debug/{output_name}.so")),
+            running: None,
+            queued: None,
+            run: compiler::run,
+            egui_context,
+        }
+    }
+
+    /// Where successful compilations are written.
+    #[inline]
+    pub fn output_path(&self) -> &PathBuf {
+        &self.output_path
+    }
+
+    #[inline]
+    pub fn is_compiling(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// The revision being compiled, or waiting to be.
+    pub fn pending_revision(&self) -> Option<u64> {
+        self.queued
+            .as_ref()
+            .map(|job| job.revision)
+            .or(self.running.as_ref().map(|job| job.revision))
+    }
+
+    /// Compile a new revision, as soon as the running compilation, if any,
+    /// has stopped.
+    pub fn push(&mut self, job: CompileJob) {
+        match &self.running {
+            Some(running) => {
+                // The running compilation is already out of date.
+                running.cancel.store(true, Ordering::SeqCst);
+                self.queued = Some(job);
+            }
+            None => self.start(job),
+        }
+    }
+
+    /// Stop compiling, without reporting a result.
+    pub fn cancel(&mut self) {
+        self.queued = None;
+        if let Some(running) = &self.running {
+            running.cancel.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Get the result of the latest compilation once it finishes. Cancelled
+    /// and superseded compilations aren't reported.
+    pub fn poll(&mut self) -> Option<CompileOutput> {
+        let running = self.running.as_ref()?;
+        let (result, duration) = match running.result.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => (
+                Err("the compiler thread panicked".to_string()),
+                Duration::ZERO,
+            ),
+        };
+
+        let finished = self.running.take().unwrap();
+        if let Some(job) = self.queued.take() {
+            self.start(job);
+        }
+        if finished.cancel.load(Ordering::SeqCst) {
+            return None;
+        }
+
+        Some(CompileOutput {
+            revision: finished.revision,
+            content: finished.content,
+            result,
+            duration,
+            options: finished.options,
+        })
+    }
+
+    fn start(&mut self, job: CompileJob) {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (sender, result) = mpsc::channel();
+        self.running = Some(RunningJob {
+            revision: job.revision,
+            content: job.input.content.clone(),
+            options: job.options,
+            cancel: cancel.clone(),
+            result,
+        });
+
+        let output_path = self.output_path.clone();
+        let run = self.run;
+        let egui_context = self.egui_context.clone();
+        std::thread::spawn(move || {
+            let started = Instant::now();
+            let result = run(&job.input, &output_path, job.options, &cancel);
+            let _ = sender.send((result.map_err(|error| error.to_string()), started.elapsed()));
+            egui_context.request_repaint();
+        });
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compiles nothing. Sources containing `block` only finish once they're
+    /// cancelled, and sources containing `fail` fail.
+    fn fake_run(
+        input: &CompileInput,
+        _output_path: &Path,
+        _options: CompileOptions,
+        cancel: &AtomicBool,
+    ) -> anyhow::Result<()> {
+        if input.content.contains("block") {
+            let started = Instant::now();
+            while !cancel.load(Ordering::SeqCst) {
+                if started.elapsed() > Duration::from_secs(5) {
+                    anyhow::bail!("the compilation was never cancelled");
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            anyhow::bail!("cancelled");
+        }
+        if input.content.contains("fail") {
+            anyhow::bail!("failed");
+        }
+        Ok(())
+    }
+
+    fn queue() -> CompileQueue {
+        let mut queue = CompileQueue::new("test", egui::Context::default());
+        queue.run = fake_run;
+        queue
+    }
+
+    fn job(revision: u64, content: &str) -> CompileJob {
+        CompileJob {
+            revision,
+            input: CompileInput {
+                name: "test".to_string(),
+                entry: PathBuf::from("main.rs"),
+                content: content.to_string(),
+                dependencies: Vec::new(),
+            },
+            options: CompileOptions::default(),
+        }
+    }
+
+    /// Poll the queue until it stops compiling, collecting every reported
+    /// result.
+    fn poll_until_idle(queue: &mut CompileQueue) -> Vec<CompileOutput> {
+        let mut outputs = Vec::new();
+        let started = Instant::now();
+        while queue.is_compiling() {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "the queue never finished"
+            );
+            outputs.extend(queue.poll());
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        outputs
+    }
+
+    #[test]
+    fn report_the_latest_revision() {
+        let mut queue = queue();
+        queue.push(job(1, "fail"));
+        let outputs = poll_until_idle(&mut queue);
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].revision, 1);
+        assert_eq!(outputs[0].result, Err("failed".to_string()));
+
+        queue.push(job(2, "ok"));
+        let outputs = poll_until_idle(&mut queue);
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(
+            (outputs[0].revision, outputs[0].result.clone()),
+            (2, Ok(()))
+        );
+        assert_eq!(outputs[0].content, "ok");
+    }
+
+    #[test]
+    fn superseded_revisions_are_not_reported() {
+        let mut queue = queue();
+        queue.push(job(1, "block"));
+        assert_eq!(queue.pending_revision(), Some(1));
+
+        // The running revision only stops once it notices it was cancelled,
+        // after the next ones were queued. Only the latest one is started.
+        queue.push(job(2, "block"));
+        queue.push(job(3, "ok"));
+        assert_eq!(queue.pending_revision(), Some(3));
+        let outputs = poll_until_idle(&mut queue);
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(
+            (outputs[0].revision, outputs[0].result.clone()),
+            (3, Ok(()))
+        );
+
+        // Revisions which finished, but weren't polled before being
+        // superseded, aren't reported either.
+        queue.push(job(4, "ok"));
+        queue.push(job(5, "fail"));
+        let outputs = poll_until_idle(&mut queue);
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].revision, 5);
+    }
+
+    #[test]
+    fn cancelled_revisions_are_not_reported() {
+        let mut queue = queue();
+        queue.push(job(1, "block"));
+        queue.push(job(2, "ok"));
+        queue.cancel();
+        assert_eq!(queue.pending_revision(), Some(1));
+        assert!(poll_until_idle(&mut queue).is_empty());
+        assert_eq!(queue.pending_revision(), None);
+    }
+}
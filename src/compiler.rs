@@ -1,45 +1,445 @@
 //! # Compiler
 
-use anyhow::Result;
+use {
+    anyhow::{Result, anyhow, bail},
+    rustc_middle::ty::TyCtxt,
+    rustc_span::source_map::FileLoader,
+    std::{
+        ffi::OsString,
+        fmt,
+        io::{self, Read, Write},
+        path::{Path, PathBuf},
+        process::{Command, Stdio},
+        sync::{
+            Arc, RwLock,
+            atomic::{AtomicBool, AtomicU64, Ordering},
+        },
+        time::{Duration, Instant},
+    },
+};
 
-use crate::WORKSPACE_DIR;
+use crate::{WORKSPACE_DIR, program_config::Dependency};
 
 
 
-pub fn run(content: &str, input_filename: &str, output_filename: &str) -> Result<()> {
+/// The build of `base` that programs are linked against.
+const BASE_RLIB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/debug/libbase.rlib");
+
+/// Written while `base` and other workspace crates are being built, and read
+/// for as long as programs are compiled against them, since the libraries are
+/// read when linking too. This way no library is rewritten while a program is
+/// compiled against it.
+static WORKSPACE_BUILD_LOCK: RwLock<()> = RwLock::new(());
+
+/// Used to give every compilation its own build directory.
+static NEXT_BUILD_DIR: AtomicU64 = AtomicU64::new(0);
+
+/// The hidden subcommand the host runs itself with to compile a program in a
+/// child process. See [`child_main`].
+pub const COMPILE_SUBCOMMAND: &str = "__compile";
+
+/// How often to check on a compiler running in a child process.
+const CHILD_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Clone, Copy, Debug)]
+pub struct CompileOptions {
+    /// Whether to run the compiler in a child process, so that crashes don't
+    /// take down the host and no compiler state is shared between
+    /// compilations.
+    pub out_of_process: bool,
+    /// How long a compilation may take before it's killed. Only applies
+    /// out of process.
+    pub timeout: Duration,
+    pub profile: BuildProfile,
+    /// Whether to include debug info, so that panics in programs have
+    /// useful backtraces.
+    pub debuginfo: bool,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            out_of_process: true,
+            timeout: Duration::from_secs(120),
+            profile: BuildProfile::Debug,
+            debuginfo: true,
+        }
+    }
+}
+
+/// How programs are optimized, mirroring Cargo's profiles of the same names.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum BuildProfile {
+    /// Unoptimized and incremental, for quick iteration.
+    #[default]
+    Debug,
+    /// Fully optimized, for measuring performance.
+    Release,
+}
+
+impl BuildProfile {
+    pub const ALL: [Self; 2] = [Self::Debug, Self::Release];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Release => "release",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|profile| profile.name() == name)
+    }
+
+    fn opt_level(self) -> rustc_session::config::OptLevel {
+        match self {
+            Self::Debug => rustc_session::config::OptLevel::No,
+            Self::Release => rustc_session::config::OptLevel::Aggressive,
+        }
+    }
+}
+
+impl fmt::Display for BuildProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What to compile a program from.
+#[derive(Clone, Debug)]
+pub struct CompileInput {
+    /// The name of the program, which its crate is named after.
+    pub name: String,
+    /// The path of the program's root module.
+    pub entry: PathBuf,
+    /// The source of the root module, which may not have been saved yet.
+    /// Other modules are read from disk.
+    pub content: String,
+    pub dependencies: Vec<Dependency>,
+}
+
+/// A single run of the compiler, which can be passed to a child process.
+struct Invocation {
+    name: String,
+    entry: PathBuf,
+    output_path: PathBuf,
+    profile: BuildProfile,
+    debuginfo: bool,
+    /// Where to keep the incremental compilation cache, if anywhere.
+    incremental_dir: Option<PathBuf>,
+    /// The crates passed to the program, other than `base`, by name.
+    externs: Vec<(String, PathBuf)>,
+}
+
+impl Invocation {
+    fn to_args(&self) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec![
+            "--name".into(),
+            self.name.clone().into(),
+            "--entry".into(),
+            self.entry.clone().into(),
+            "--output".into(),
+            self.output_path.clone().into(),
+            "--profile".into(),
+            self.profile.name().into(),
+        ];
+        if self.debuginfo {
+            args.push("--debuginfo".into());
+        }
+        if let Some(dir) = &self.incremental_dir {
+            args.extend(["--incremental".into(), dir.clone().into()]);
+        }
+        for (name, path) in &self.externs {
+            let mut arg = OsString::from(format!("{name}="));
+            arg.push(path);
+            args.extend(["--extern".into(), arg]);
+        }
+
+        args
+    }
+
+    fn from_args(args: &[String]) -> Result<Self> {
+        let (mut name, mut entry, mut output_path, mut profile) = (None, None, None, None);
+        let mut debuginfo = false;
+        let mut incremental_dir = None;
+        let mut externs = Vec::new();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("missing value for compiler argument {arg}"))
+            };
+            match arg.as_str() {
+                "--name" => name = Some(value()?.clone()),
+                "--entry" => entry = Some(PathBuf::from(value()?)),
+                "--output" => output_path = Some(PathBuf::from(value()?)),
+                "--profile" => {
+                    let value = value()?;
+                    profile = Some(
+                        BuildProfile::from_name(value)
+                            .ok_or_else(|| anyhow!("unknown build profile {value}"))?,
+                    );
+                }
+                "--debuginfo" => debuginfo = true,
+                "--incremental" => incremental_dir = Some(PathBuf::from(value()?)),
+                "--extern" => {
+                    let value = value()?;
+                    let (name, path) = value
+                        .split_once('=')
+                        .ok_or_else(|| anyhow!("invalid extern {value}"))?;
+                    externs.push((name.to_string(), PathBuf::from(path)));
+                }
+                _ => bail!("unknown compiler argument {arg}"),
+            }
+        }
+
+        Ok(Self {
+            name: name.ok_or_else(|| anyhow!("missing --name"))?,
+            entry: entry.ok_or_else(|| anyhow!("missing --entry"))?,
+            output_path: output_path.ok_or_else(|| anyhow!("missing --output"))?,
+            profile: profile.ok_or_else(|| anyhow!("missing --profile"))?,
+            debuginfo,
+            incremental_dir,
+            externs,
+        })
+    }
+}
+
+/// Reads a program's modules from disk, except for its root module, whose
+/// source may not have been saved.
+struct ProgramFileLoader {
+    entry: PathBuf,
+    content: String,
+}
+
+impl FileLoader for ProgramFileLoader {
+    fn file_exists(&self, path: &Path) -> bool {
+        path == self.entry || path.exists()
+    }
+
+    fn read_file(&self, path: &Path) -> io::Result<String> {
+        if path == self.entry {
+            return Ok(self.content.clone());
+        }
+        std::fs::read_to_string(path)
+    }
+
+    fn read_binary_file(&self, path: &Path) -> io::Result<Arc<[u8]>> {
+        if path == self.entry {
+            return Ok(self.content.as_bytes().into());
+        }
+        Ok(std::fs::read(path)?.into())
+    }
+
+    fn current_directory(&self) -> io::Result<PathBuf> {
+        std::env::current_dir()
+    }
+}
+
+/// Compile a program into a shared object at `output_path`.
+///
+/// The shared object is built in a directory of its own, and only moved to
+/// `output_path` once it is complete. A failed or cancelled compilation
+/// leaves `output_path` untouched.
+///
+/// Setting `cancel` kills the compiler, but only when it runs out of process.
+pub fn run(
+    input: &CompileInput,
+    output_path: &Path,
+    options: CompileOptions,
+    cancel: &AtomicBool,
+) -> Result<()> {
+    let workspace_build = WORKSPACE_BUILD_LOCK
+        .write()
+        .unwrap_or_else(|error| error.into_inner());
+    build_workspace_crates(&input.dependencies)?;
+    drop(workspace_build);
+    // Other compilations may build the workspace crates again in between, but
+    // not while this one is reading them.
+    let _workspace_libraries = WORKSPACE_BUILD_LOCK
+        .read()
+        .unwrap_or_else(|error| error.into_inner());
+
+    let build_dir = PathBuf::from(format!(
+        "{WORKSPACE_DIR}/target/debug/programs/{}-{}",
+        std::process::id(),
+        NEXT_BUILD_DIR.fetch_add(1, Ordering::Relaxed),
+    ));
+    std::fs::create_dir_all(&build_dir)?;
+    let invocation = Invocation {
+        name: input.name.clone(),
+        entry: input.entry.clone(),
+        output_path: build_dir.join(output_path.file_name().unwrap_or("program.so".as_ref())),
+        profile: options.profile,
+        debuginfo: options.debuginfo,
+        // Like Cargo, only debug builds are incremental. The cache is kept
+        // per program and profile, so that it outlives the host.
+        incremental_dir: (options.profile == BuildProfile::Debug).then(|| {
+            Path::new(WORKSPACE_DIR)
+                .join("target/debug/programs/incremental")
+                .join(&input.name)
+                .join(options.profile.name())
+        }),
+        externs: input
+            .dependencies
+            .iter()
+            .map(|dependency| (dependency.name.clone(), dependency.path.clone()))
+            .collect(),
+    };
+    let content = &input.content;
+
+    let result = if options.out_of_process {
+        run_child(content, &invocation, options, cancel)
+    } else {
+        compile(content, &invocation)
+    }
+    .and_then(|()| {
+        if cancel.load(Ordering::SeqCst) {
+            bail!("compilation was cancelled");
+        }
+        // Both are in the target directory, so this is atomic.
+        std::fs::rename(&invocation.output_path, output_path)?;
+        Ok(())
+    });
+    let _ = std::fs::remove_dir_all(&build_dir);
+
+    result
+}
+
+/// The entry point of the child process started by [`run`], given the
+/// arguments after the subcommand. The program's source is read from stdin,
+/// and diagnostics are written to stderr.
+pub fn child_main(args: &[String]) -> Result<()> {
+    let invocation = Invocation::from_args(args)?;
+    let mut content = String::new();
+    std::io::stdin().read_to_string(&mut content)?;
+
+    compile(&content, &invocation)
+}
+
+fn run_child(
+    content: &str,
+    invocation: &Invocation,
+    options: CompileOptions,
+    cancel: &AtomicBool,
+) -> Result<()> {
+    let name = &invocation.name;
+    let mut child = Command::new(std::env::current_exe()?)
+        .arg(COMPILE_SUBCOMMAND)
+        .args(invocation.to_args())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| anyhow!("failed to start the compiler process: {error}"))?;
+
+    // The pipes are serviced on separate threads, so that a full pipe can't
+    // block the child while it's being waited on.
+    let mut stdin = child.stdin.take().unwrap();
+    let content = content.to_string();
+    std::thread::spawn(move || stdin.write_all(content.as_bytes()));
+    let mut stderr = child.stderr.take().unwrap();
+    let diagnostics = std::thread::spawn(move || {
+        let mut diagnostics = String::new();
+        let _ = stderr.read_to_string(&mut diagnostics);
+        diagnostics
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if cancel.load(Ordering::Relaxed) {
+            child.kill()?;
+            child.wait()?;
+            bail!("compilation was cancelled");
+        }
+        if started.elapsed() > options.timeout {
+            child.kill()?;
+            child.wait()?;
+            bail!(
+                "compilation timed out after {} seconds",
+                options.timeout.as_secs(),
+            );
+        }
+        std::thread::sleep(CHILD_POLL_INTERVAL);
+    };
+
+    let diagnostics = diagnostics.join().unwrap_or_default();
+    if !status.success() {
+        bail!("failed to compile {name} ({status}):\n{diagnostics}");
+    }
+
+    Ok(())
+}
+
+fn compile(content: &str, invocation: &Invocation) -> Result<()> {
+    let Invocation {
+        name,
+        entry,
+        output_path,
+        profile,
+        debuginfo,
+        incremental_dir,
+        externs,
+    } = invocation;
+
+    let extern_entry = |path: &Path| rustc_session::config::ExternEntry {
+        location: rustc_session::config::ExternLocation::ExactPaths(
+            [rustc_session::utils::CanonicalizedPath::new(path.into())].into(),
+        ),
+        is_private_dep: false,
+        add_prelude: true,
+        nounused_dep: false,
+        force: false,
+    };
+    let externs = [("base".to_string(), extern_entry(Path::new(BASE_RLIB)))]
+        .into_iter()
+        .chain(
+            externs
+                .iter()
+                .map(|(name, path)| (name.clone(), extern_entry(path))),
+        )
+        .collect();
+
     let result = std::panic::catch_unwind(|| {
         let config = rustc_interface::Config {
             opts: rustc_session::config::Options {
                 crate_types: vec![rustc_session::config::CrateType::Cdylib],
-                externs: rustc_session::config::Externs::new(
-                    [(
-                        "base".to_string(),
-                        rustc_session::config::ExternEntry {
-                            location: rustc_session::config::ExternLocation::ExactPaths(
-                                [rustc_session::utils::CanonicalizedPath::new(
-                                    format!("{WORKSPACE_DIR}/target/debug/libbase.rlib").into(),
-                                )]
-                                .into(),
-                            ),
-                            is_private_dep: false,
-                            add_prelude: true,
-                            nounused_dep: false,
-                            force: false,
-                        },
-                    )]
-                    .into(),
-                ),
-                incremental: None,
+                crate_name: Some(crate_name(name)),
+                externs: rustc_session::config::Externs::new(externs),
+                // Where the dependencies of workspace crates are found.
+                search_paths: vec![rustc_session::search_paths::SearchPath::new(
+                    rustc_session::search_paths::PathKind::Dependency,
+                    format!("{WORKSPACE_DIR}/target/debug/deps").into(),
+                )],
+                incremental: incremental_dir.clone(),
+                optimize: profile.opt_level(),
+                debug_assertions: *profile == BuildProfile::Debug,
+                debuginfo: if *debuginfo {
+                    rustc_session::config::DebugInfo::Full
+                } else {
+                    rustc_session::config::DebugInfo::None
+                },
                 output_types: rustc_session::config::OutputTypes::new(&[(
                     rustc_session::config::OutputType::Exe,
-                    Some(rustc_session::config::OutFileName::Real(
-                        output_filename.into(),
-                    )),
+                    Some(rustc_session::config::OutFileName::Real(output_path.into())),
                 )]),
                 cg: rustc_session::config::CodegenOptions {
-                    opt_level: "3".into(),
-                    panic: Some(rustc_target::spec::PanicStrategy::Abort),
-                    strip: rustc_session::config::Strip::Symbols,
+                    opt_level: match profile {
+                        BuildProfile::Debug => "0".into(),
+                        BuildProfile::Release => "3".into(),
+                    },
+                    // Panics are caught by the program's own runtime, so that they
+                    // don't take down the host.
+                    panic: Some(rustc_target::spec::PanicStrategy::Unwind),
+                    strip: if *debuginfo {
+                        rustc_session::config::Strip::None
+                    } else {
+                        rustc_session::config::Strip::Symbols
+                    },
                     ..Default::default()
                 },
                 verbose: true,
@@ -47,13 +447,14 @@ pub fn run(content: &str, input_filename: &str, output_filename: &str) -> Result
             },
             crate_cfg: Vec::new(),
             crate_check_cfg: Vec::new(),
-            input: rustc_session::config::Input::Str {
-                name: rustc_span::FileName::Custom(input_filename.into()),
-                input: content.into(),
-            },
-            output_dir: Some(format!("{WORKSPACE_DIR}/target/debug").into()),
+            input: rustc_session::config::Input::File(entry.clone()),
+            // Keep intermediate files apart from those of other compilations.
+            output_dir: output_path.parent().map(Into::into),
             output_file: None,
-            file_loader: None,
+            file_loader: Some(Box::new(ProgramFileLoader {
+                entry: entry.clone(),
+                content: content.to_string(),
+            })),
             locale_resources: rustc_driver::DEFAULT_LOCALE_RESOURCES.to_owned(),
             lint_caps: Default::default(),
             psess_created: None,
@@ -71,19 +472,187 @@ pub fn run(content: &str, input_filename: &str, output_filename: &str) -> Result
             let sess = &compiler.sess;
             let codegen_backend = &*compiler.codegen_backend;
             let krate = rustc_interface::passes::parse(sess);
-            let linker = rustc_interface::create_and_enter_global_ctxt(&compiler, krate, |tcx| {
-                rustc_interface::Linker::codegen_and_build_linker(tcx, codegen_backend)
-            });
+            let linker = rustc_interface::create_and_enter_global_ctxt(
+                compiler,
+                krate,
+                |tcx| -> Result<_> {
+                    check_base(tcx)?;
+                    // Codegen expects the crate to have been checked, and ICEs on errors otherwise.
+                    tcx.ensure_ok().analysis(());
+                    Ok(rustc_interface::Linker::codegen_and_build_linker(
+                        tcx,
+                        codegen_backend,
+                    ))
+                },
+            )?;
             linker.link(sess, codegen_backend);
-        });
+
+            Ok(())
+        })
     });
 
-    if result.is_ok() {
-        std::fs::rename(
-            output_filename,
-            format!("{WORKSPACE_DIR}/target/debug/{output_filename}"),
-        )?;
+    match result {
+        Ok(result) => result,
+        Err(_) => bail!("failed to compile {name}"),
+    }
+}
+
+/// Turn a program's name into a valid crate name.
+fn crate_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    if name.starts_with(|c: char| !c.is_ascii_alphabetic()) {
+        format!("program_{name}")
+    } else {
+        name
     }
+}
 
-    result.map_err(|_| anyhow::anyhow!("failed to compile {input_filename}"))
+/// Build `base`, along with the workspace crates among the provided
+/// dependencies, for programs to link against. Cargo only rebuilds them if
+/// they're missing or out of date.
+fn build_workspace_crates(dependencies: &[Dependency]) -> Result<()> {
+    let mut args = vec!["build", "--quiet", "--package", "base"];
+    for dependency in dependencies
+        .iter()
+        .filter(|dependency| dependency.workspace)
+    {
+        args.extend(["--package", &dependency.name]);
+    }
+
+    // Prefer the Cargo the host was started with, if any.
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let output = Command::new(cargo)
+        .args(args)
+        .current_dir(WORKSPACE_DIR)
+        .output()
+        .map_err(|error| anyhow!("failed to run cargo to build workspace crates: {error}"))?;
+    if !output.status.success() {
+        bail!(
+            "failed to build workspace crates:\n{}",
+            String::from_utf8_lossy(&output.stderr),
+        );
+    }
+
+    Ok(())
+}
+
+/// Make sure that the `base` a program is linked against was built from the
+/// same sources as the one linked into the host, as the two would otherwise
+/// disagree on the layout of the types passed between them.
+fn check_base(tcx: TyCtxt<'_>) -> Result<()> {
+    // Loading crates happens during name resolution.
+    let _ = tcx.resolutions(());
+
+    let Some(base) = tcx
+        .crates(())
+        .iter()
+        .copied()
+        .find(|&krate| tcx.crate_name(krate).as_str() == "base")
+    else {
+        // Nothing from `base` is used.
+        return Ok(());
+    };
+    let hash = tcx
+        .module_children(base.as_def_id())
+        .iter()
+        .find(|child| child.ident.name.as_str() == "SOURCE_HASH")
+        .and_then(|child| child.res.opt_def_id())
+        .and_then(|def_id| tcx.const_eval_poly(def_id).ok())
+        .and_then(|value| value.try_to_scalar_int())
+        .map(|value| value.to_u64());
+
+    if hash != Some(base::SOURCE_HASH) {
+        bail!(
+            "{BASE_RLIB} was built from different sources than the host. \
+            Rebuild and restart the host to pick up changes to base."
+        );
+    }
+
+    Ok(())
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn invocations_round_trip() {
+        let invocation = Invocation {
+            name: "demo".to_string(),
+            entry: PathBuf::from("/programs/demo/main.rs"),
+            output_path: PathBuf::from("/tmp/libdemo.so"),
+            profile: BuildProfile::Release,
+            debuginfo: true,
+            incremental_dir: Some(PathBuf::from("/tmp/incremental")),
+            externs: vec![
+                ("utils".to_string(), PathBuf::from("/lib/libutils.rlib")),
+                // Only the first `=` separates the name from the path.
+                ("odd".to_string(), PathBuf::from("/lib/a=b.rlib")),
+            ],
+        };
+        let invocation_args = invocation
+            .to_args()
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect::<Vec<_>>();
+        let parsed = Invocation::from_args(&invocation_args).unwrap();
+        assert_eq!(parsed.name, invocation.name);
+        assert_eq!(parsed.entry, invocation.entry);
+        assert_eq!(parsed.output_path, invocation.output_path);
+        assert_eq!(parsed.profile, invocation.profile);
+        assert_eq!(parsed.debuginfo, invocation.debuginfo);
+        assert_eq!(parsed.incremental_dir, invocation.incremental_dir);
+        assert_eq!(parsed.externs, invocation.externs);
+
+        let parsed = Invocation::from_args(&args(&[
+            "--name",
+            "demo",
+            "--entry",
+            "main.rs",
+            "--output",
+            "out.so",
+            "--profile",
+            "debug",
+        ]))
+        .unwrap();
+        assert!(!parsed.debuginfo);
+        assert_eq!(parsed.incremental_dir, None);
+        assert!(parsed.externs.is_empty());
+    }
+
+    #[test]
+    fn report_invalid_invocations() {
+        let error = |extra: &[&str]| {
+            let mut all = args(&["--name", "demo", "--entry", "main.rs", "--output", "out.so"]);
+            all.extend(args(extra));
+            Invocation::from_args(&all)
+                .err()
+                .expect("the arguments should be rejected")
+                .to_string()
+        };
+
+        assert_eq!(
+            error(&["--profile"]),
+            "missing value for compiler argument --profile",
+        );
+        assert_eq!(error(&["--profile", "fast"]), "unknown build profile fast",);
+        assert_eq!(
+            error(&["--profile", "debug", "--verbose"]),
+            "unknown compiler argument --verbose",
+        );
+        assert_eq!(
+            error(&["--profile", "debug", "--extern", "utils"]),
+            "invalid extern utils",
+        );
+        assert_eq!(error(&[]), "missing --profile");
+    }
 }
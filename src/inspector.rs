@@ -0,0 +1,228 @@
+//! # Object Tree Inspector
+
+use {
+    crate::program::ProgramGuard,
+    base::{Area, ObjectNodeRef, ObjectTree, Point},
+    eframe::egui,
+};
+
+
+
+/// A side panel that shows the contents of an [`ObjectTree`].
+#[derive(Default)]
+pub struct Inspector {
+    pub open: bool,
+    /// Whether to draw the [layout overlay](base::layout_overlay_pass) over the
+    /// program.
+    pub show_layout: bool,
+    /// Whether the next click on the program should select an object instead of
+    /// being passed to the program.
+    pub picking: bool,
+    pub selected: Option<u64>,
+    /// The object currently hovered, either in the panel's hierarchy or over
+    /// the program while picking.
+    pub highlighted: Option<u64>,
+    /// Whether the release of the click used to pick an object should be
+    /// withheld from the program.
+    pending_release: bool,
+}
+
+impl Inspector {
+    /// Show the panel, with the tree of the focused program if it's loaded.
+    /// The tree's objects are called through the program's guard.
+    pub fn show(&mut self, ui: &mut egui::Ui, tree: Option<(&ObjectTree, &mut ProgramGuard<'_>)>) {
+        ui.horizontal(|ui| {
+            ui.heading("Inspector");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                ui.toggle_value(&mut self.picking, "Pick")
+                    .on_hover_text("Select an object by clicking it");
+            });
+        });
+        ui.separator();
+
+        let Some((tree, guard)) = tree else {
+            ui.label("No program loaded.");
+            return;
+        };
+
+        // Only keep the highlight if something is hovered this frame.
+        if !self.picking {
+            self.highlighted = None;
+        }
+
+        egui::ScrollArea::vertical()
+            .id_salt("inspector_hierarchy")
+            .max_height(ui.available_height() * 0.6)
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                self.node_ui(ui, tree.root_node(), guard);
+            });
+
+        ui.separator();
+
+        let Some(node) = self.selected.and_then(|id| tree.find(id)) else {
+            ui.label("Nothing selected.");
+            return;
+        };
+        let Some(type_name) = guard.call(|| node.object.debug_name()) else {
+            return;
+        };
+
+        egui::Grid::new("inspector_state")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                let state = node.state;
+                ui.label("Type");
+                ui.label(type_name);
+                ui.end_row();
+                ui.label("ID");
+                ui.label(state.id().to_string());
+                ui.end_row();
+                ui.label("Key");
+                ui.label(state.key().unwrap_or("-"));
+                ui.end_row();
+                ui.label("Parent");
+                ui.label(node.parent_id.map_or("-".to_string(), |id| id.to_string()));
+                ui.end_row();
+                ui.label("Layout area");
+                ui.label(format_area(state.layout_area()));
+                ui.end_row();
+                ui.label("Global area");
+                ui.label(format_area(state.area()));
+                ui.end_row();
+                ui.label("Baseline offset");
+                ui.label(
+                    state
+                        .baseline_offset()
+                        .map_or("-".to_string(), |offset| format!("{offset:.1}")),
+                );
+                ui.end_row();
+                ui.label("Needs layout");
+                ui.label(state.needs_layout().to_string());
+                ui.end_row();
+                ui.label("Needs compose");
+                ui.label(state.needs_compose().to_string());
+                ui.end_row();
+                ui.label("Needs paint");
+                ui.label(state.needs_paint().to_string());
+                ui.end_row();
+                ui.label("Children changed");
+                ui.label(state.children_changed().to_string());
+                ui.end_row();
+                ui.label("Hovered");
+                ui.label(state.is_hovered().to_string());
+                ui.end_row();
+                ui.label("Focused");
+                ui.label(state.is_focused().to_string());
+                ui.end_row();
+            });
+    }
+
+    fn node_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        node: ObjectNodeRef<'_>,
+        guard: &mut ProgramGuard<'_>,
+    ) {
+        let Some((type_name, children_ids)) =
+            guard.call(|| (node.object.debug_name(), node.object.children_ids()))
+        else {
+            return;
+        };
+        let id = node.state.id();
+        let text = format!("{} #{id}", short_type_name(type_name));
+        let selected = self.selected == Some(id);
+
+        let header = |ui: &mut egui::Ui, this: &mut Self| {
+            let response = ui.selectable_label(selected, text);
+            if response.clicked() {
+                this.selected = Some(id);
+            }
+            if response.hovered() {
+                this.highlighted = Some(id);
+            }
+        };
+
+        if children_ids.is_empty() {
+            ui.horizontal(|ui| {
+                // Line up with the collapsing headers' text.
+                ui.add_space(ui.spacing().indent);
+                header(ui, self);
+            });
+            return;
+        }
+
+        egui::collapsing_header::CollapsingState::load_with_default_open(
+            ui.ctx(),
+            ui.make_persistent_id(("inspector_node", id)),
+            true,
+        )
+        .show_header(ui, |ui| header(ui, self))
+        .body(|ui| {
+            for child_id in children_ids {
+                if let Some(child) = node.children.get(child_id) {
+                    self.node_ui(ui, child, guard);
+                }
+            }
+        });
+    }
+
+    /// Select the picked object and stop picking.
+    pub fn pick(&mut self, id: Option<u64>) {
+        self.selected = id;
+        self.highlighted = None;
+        self.picking = false;
+        self.pending_release = true;
+    }
+
+    pub fn take_pending_release(&mut self) -> bool {
+        std::mem::take(&mut self.pending_release)
+    }
+
+    /// Draw the highlighted and selected objects' areas over the program, which
+    /// is rendered at `origin`.
+    pub fn paint_overlay(&self, painter: &egui::Painter, origin: Point, tree: &ObjectTree) {
+        let rect_for = |id: u64| {
+            tree.find(id).map(|node| {
+                let area = node.state.area();
+                egui::Rect::from_min_size(
+                    egui::pos2(origin.x + area.position.x, origin.y + area.position.y),
+                    egui::vec2(area.size.width, area.size.height),
+                )
+            })
+        };
+
+        if let Some(rect) = self.selected.and_then(rect_for) {
+            painter.rect_stroke(
+                rect,
+                0,
+                egui::Stroke::new(2.0, egui::Color32::from_rgb(0x4a, 0x9e, 0xff)),
+                egui::StrokeKind::Outside,
+            );
+        }
+        if let Some(rect) = self.highlighted.and_then(rect_for) {
+            painter.rect_filled(
+                rect,
+                0,
+                egui::Color32::from_rgba_unmultiplied(0x4a, 0x9e, 0xff, 0x40),
+            );
+        }
+    }
+}
+
+/// Strip the module path from a type name, e.g. `base::flex::Flex` becomes
+/// `Flex`.
+fn short_type_name(name: &str) -> &str {
+    let end = name.find('<').unwrap_or(name.len());
+    let start = name[..end].rfind("::").map_or(0, |index| index + 2);
+
+    &name[start..]
+}
+
+fn format_area(area: Area) -> String {
+    format!(
+        "({:.0}, {:.0}) {:.0}×{:.0}",
+        area.position.x, area.position.y, area.size.width, area.size.height,
+    )
+}
@@ -8,28 +8,56 @@ extern crate rustc_errors;
 extern crate rustc_feature;
 extern crate rustc_hir;
 extern crate rustc_interface;
+extern crate rustc_middle;
 extern crate rustc_session;
 extern crate rustc_span;
 extern crate rustc_target;
 
+mod assets;
+mod compile_queue;
 mod compiler;
+mod inspector;
+mod overlay;
+mod profiler;
+mod program;
+mod program_config;
+mod source_file;
+mod svg;
+mod vfs;
+mod watcher;
 
 use {
     anyhow::Result,
-    base::*,
+    compiler::{BuildProfile, CompileOptions},
     eframe::egui,
-    std::{
-        any::TypeId,
-        collections::HashMap,
-        sync::{Arc, atomic::AtomicBool},
-    },
+    inspector::Inspector,
+    profiler::ProfilerPanel,
+    program::{Program, ProgramKind},
+    program_config::ProgramConfig,
+    source_file::SourceFile,
+    std::path::{Path, PathBuf},
+    watcher::FileWatcher,
 };
 
 
 const WORKSPACE_DIR: &str = env!("CARGO_MANIFEST_DIR");
-const EXAMPLE_SRC: &str = include_str!("../example/src/example.rs");
+const BASE_SRC_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/base/src");
+const PROGRAM_TEMPLATE: &str = include_str!("../templates/program.rs");
+const VIEW_TEMPLATE: &str = include_str!("../templates/program.view");
 
 fn main() -> Result<()> {
+    let args = std::env::args().collect::<Vec<_>>();
+    // The host runs itself to compile programs out of process.
+    if let [_, subcommand, compiler_args @ ..] = args.as_slice()
+        && subcommand == compiler::COMPILE_SUBCOMMAND
+    {
+        if let Err(error) = compiler::child_main(compiler_args) {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     eframe::run_native(
         "Demo",
         eframe::NativeOptions {
@@ -40,9 +68,18 @@ fn main() -> Result<()> {
             ..Default::default()
         },
         Box::new(|cc| {
-            Ok(Box::new(App {
-                program: Program::load("example", EXAMPLE_SRC.to_string(), cc.egui_ctx.clone())?,
-            }))
+            let mut app = App::new(cc.egui_ctx.clone());
+
+            // A program or a directory of programs can be passed as the first argument.
+            match args.get(1).map(PathBuf::from) {
+                Some(path) if path.is_dir() && !ProgramConfig::is_program_dir(&path) => {
+                    app.program_dir = path;
+                }
+                Some(path) => app.open(&path)?,
+                None => app.open(&app.program_dir.join("example.rs"))?,
+            }
+
+            Ok(Box::new(app))
         }),
     )
     .map_err(|err| anyhow::anyhow!("{err}"))?;
@@ -53,421 +90,473 @@ fn main() -> Result<()> {
 
 
 struct App {
-    program: Program,
-}
-
-impl eframe::App for App {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            egui::ScrollArea::vertical()
-                .auto_shrink([false, false])
-                .show(ui, |ui| {
-                    self.program.update(ui).expect("failed to update program");
-                });
-        });
-    }
-}
-
-
-
-struct Program {
-    name: &'static str,
-    handle: Option<ProgramHandle>,
-    editing: bool,
-    waiting_on_recompile: bool,
-    compiling: Arc<AtomicBool>,
-    latest_compile_succeeded: Arc<AtomicBool>,
-    source: String,
     egui_context: egui::Context,
-    known_size: Size,
-    known_position: Point,
-    known_pointer_position: Option<Point>,
+    programs: Vec<Program>,
+    /// The indices of the programs shown side by side. There is only more than
+    /// one pane when the view is split.
+    panes: Vec<usize>,
+    /// The index into `panes` of the pane the tools and tabs apply to.
+    focused_pane: usize,
+    /// The index of a program with unsaved edits which is waiting for them to
+    /// be saved or discarded before it's closed.
+    closing: Option<usize>,
+    /// The directory programs are opened from and created in.
+    program_dir: PathBuf,
+    /// Used to give every program a distinct output file, even if several
+    /// programs share a name.
+    next_program_id: u32,
+    /// Reloads programs when their files, or the sources of `base`, change.
+    watcher: FileWatcher,
+    /// Whether the sources of `base` changed since the host was built.
+    /// Programs are checked against the `base` built into the host, so they
+    /// can only be loaded once it's rebuilt.
+    base_changed: bool,
+    /// The options new programs are compiled with. Isolation and the timeout
+    /// apply to every program, while the profile is chosen per program.
+    compile_options: CompileOptions,
+    inspector: Inspector,
+    profiler: ProfilerPanel,
 }
 
-impl Program {
-    fn load(name: &'static str, source: String, egui_context: egui::Context) -> Result<Self> {
-        let mut this = Self {
-            name,
-            handle: None,
-            editing: false,
-            waiting_on_recompile: false,
-            compiling: Arc::new(AtomicBool::new(false)),
-            latest_compile_succeeded: Arc::new(AtomicBool::new(true)),
-            source,
-            egui_context,
-            known_size: Size::ZERO,
-            known_position: Point::ZERO,
-            known_pointer_position: None,
-        };
-
-        this.start_compiling();
-
-        Ok(this)
-    }
+impl App {
+    fn new(egui_context: egui::Context) -> Self {
+        let mut watcher = FileWatcher::new(egui_context.clone());
+        watcher.watch_directory(Path::new(BASE_SRC_DIR));
 
-    fn start_compiling(&mut self) {
-        self.waiting_on_recompile = true;
-        self.compiling
-            .store(true, std::sync::atomic::Ordering::SeqCst);
-
-        let compiling = self.compiling.clone();
-        let latest_compile_succeeded = self.latest_compile_succeeded.clone();
-        let content = self.source.clone();
-        let input_filename = format!("{}.rs", self.name);
-        let output_filename = format!("{}.so", self.name);
-
-        std::thread::spawn(move || {
-            let result = compiler::run(&content, &input_filename, &output_filename);
-            if let Err(error) = &result {
-                println!("ERROR: {error}");
-            }
-            latest_compile_succeeded.swap(result.is_ok(), std::sync::atomic::Ordering::SeqCst);
-            compiling.swap(false, std::sync::atomic::Ordering::SeqCst);
-        });
+        Self {
+            egui_context,
+            watcher,
+            base_changed: false,
+            compile_options: CompileOptions::default(),
+            programs: Vec::new(),
+            panes: Vec::new(),
+            focused_pane: 0,
+            closing: None,
+            program_dir: PathBuf::from(format!("{WORKSPACE_DIR}/example/src")),
+            next_program_id: 0,
+            inspector: Inspector::default(),
+            profiler: ProfilerPanel::default(),
+        }
     }
 
-    fn reload(&mut self) -> Result<()> {
-        // We need to drop the previous shared object before reloading because `dlopen`
-        // won't load the new version if there are existing references to the old one.
-        drop(self.handle.take());
-
-        let handle = unsafe {
-            libloading::Library::new(
-                format!("{WORKSPACE_DIR}/target/debug/{}.so", self.name).as_str(),
-            )?
+    /// Open the program at the provided path in the focused pane, which is
+    /// either a single file or a directory with a `program.toml`.
+    fn open(&mut self, path: &Path) -> Result<()> {
+        let config = if path.is_dir() {
+            ProgramConfig::load(path)?
+        } else {
+            ProgramConfig::single_file(path)
         };
+        let source = SourceFile::open(&config.entry)?;
+        // Directory programs are named after their directory rather than their
+        // entry, which is usually `main.rs`.
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("program")
+            .to_string();
 
-        let object_type_id = unsafe { handle.get::<*const TypeId>(b"__OBJECT_TYPE_ID")? };
-        assert_eq!(unsafe { **object_type_id }, TypeId::of::<dyn Object>());
-
-        let mut textures = HashMap::new();
-        let view_fn = unsafe {
-            handle.get::<unsafe extern "Rust" fn(&mut dyn ViewContext) -> Box<dyn Object>>(b"view")
-        }?;
-        let root_object = unsafe {
-            (&*view_fn)(&mut ViewContextImpl {
-                egui_context: &self.egui_context,
-                textures: &mut textures,
-            })
-        };
-
-        let tree = ObjectTree::new(root_object);
-
-        self.handle = Some(ProgramHandle {
-            tree,
-            _textures: textures,
-            _handle: handle,
-        });
+        self.add(name, ProgramKind::from_path(&config.entry), source, config);
 
         Ok(())
     }
 
-    fn update(&mut self, ui: &mut egui::Ui) -> Result<()> {
-        if self.compiling.load(std::sync::atomic::Ordering::Relaxed) {
-            ui.centered_and_justified(|ui| {
-                ui.spinner();
-            });
-            return Ok(());
-        }
-
-        let compile_success = self
-            .latest_compile_succeeded
-            .load(std::sync::atomic::Ordering::Relaxed);
-
-        let recompiled = self.waiting_on_recompile && compile_success;
+    /// Create a new program from a template in the focused pane. It is saved
+    /// into the program directory once it first compiles.
+    fn create(&mut self, kind: ProgramKind) {
+        let (template, extension) = match kind {
+            ProgramKind::Compiled => (PROGRAM_TEMPLATE, "rs"),
+            ProgramKind::Declarative => (VIEW_TEMPLATE, "view"),
+        };
 
-        if self.waiting_on_recompile && compile_success {
-            self.waiting_on_recompile = false;
-            self.reload()?;
+        let mut name = "untitled".to_string();
+        let mut suffix = 1;
+        while self.programs.iter().any(|program| program.name == name)
+            || self
+                .program_dir
+                .join(format!("{name}.{extension}"))
+                .exists()
+        {
+            suffix += 1;
+            name = format!("untitled_{suffix}");
         }
 
-        ui.set_width(ui.available_width());
-        ui.set_height(ui.available_height());
-
-        if self.editing {
-            ui.allocate_ui_with_layout(
-                egui::vec2(
-                    ui.available_size_before_wrap().x,
-                    ui.spacing().interact_size.y,
-                ),
-                egui::Layout::right_to_left(egui::Align::Center).with_main_wrap(true),
-                |ui| {
-                    if ui.button("Confirm").clicked() {
-                        self.editing = false;
-                        self.start_compiling();
-                    }
-                    if ui.button("Cancel").clicked() {
-                        self.editing = false;
-                    }
-                },
-            );
-            ui.separator();
-
-            let mut layouter = |ui: &egui::Ui, text: &dyn egui::TextBuffer, wrap_width: f32| {
-                let mut layout_job: egui::text::LayoutJob =
-                    egui_extras::syntax_highlighting::highlight(
-                        ui.ctx(),
-                        ui.style(),
-                        &egui_extras::syntax_highlighting::CodeTheme::dark(16.0),
-                        text.as_str(),
-                        "rs",
-                    );
-                layout_job.wrap.max_width = wrap_width;
-
-                ui.fonts_mut(|f| f.layout_job(layout_job))
-            };
-            ui.add(
-                egui::TextEdit::multiline(&mut self.source)
-                    .layouter(&mut layouter)
-                    .desired_width(ui.available_width()),
-            );
+        let path = self.program_dir.join(format!("{name}.{extension}"));
+        self.add(
+            name,
+            kind,
+            SourceFile::unsaved(&path, template.to_string()),
+            ProgramConfig::single_file(path),
+        );
+    }
 
-            return Ok(());
+    fn add(&mut self, name: String, kind: ProgramKind, source: SourceFile, config: ProgramConfig) {
+        match &config.dir {
+            // Modules may be nested in subdirectories.
+            Some(dir) => self.watcher.watch_directory_recursive(dir),
+            None => self.watcher.watch_file(source.path()),
         }
+        let output_name = format!("{name}-{}", self.next_program_id);
+        self.next_program_id += 1;
 
-        ui.allocate_ui_with_layout(
-            egui::vec2(
-                ui.available_size_before_wrap().x,
-                ui.spacing().interact_size.y,
-            ),
-            egui::Layout::right_to_left(egui::Align::Center).with_main_wrap(true),
-            |ui| {
-                if ui.button("Edit").clicked() {
-                    self.editing = true;
-                }
-            },
-        );
-        ui.separator();
-        if !compile_success {
-            ui.centered_and_justified(|ui| {
-                ui.heading("Compilation failed, see logs");
-            });
-            return Ok(());
+        self.programs.push(Program::load(
+            name,
+            output_name,
+            kind,
+            source,
+            config,
+            self.compile_options,
+            self.egui_context.clone(),
+        ));
+        self.show(self.programs.len() - 1);
+    }
+
+    /// Show the program with the provided index in the focused pane.
+    fn show(&mut self, index: usize) {
+        match self.panes.get_mut(self.focused_pane) {
+            Some(pane) => *pane = index,
+            None => self.panes.push(index),
         }
+        self.set_focused_pane(self.focused_pane.min(self.panes.len() - 1));
+    }
 
-        let handle = self.handle.as_mut().unwrap();
-        let tree = &mut handle.tree;
-        let mut renderer = RendererImpl {
-            position: self.known_position,
-            painter: ui.painter(),
-        };
+    /// Focus the pane with the provided index, after the panes or the
+    /// programs they show changed. IDs are only meaningful within a single
+    /// tree, so the inspector's selection is cleared.
+    fn set_focused_pane(&mut self, pane: usize) {
+        self.focused_pane = pane;
+        self.inspector.selected = None;
+        self.inspector.highlighted = None;
+    }
 
-        let window_rect = ui.available_rect_before_wrap();
-        let window_size = convert_vec2_to_size(window_rect.size());
-        self.known_position = convert_pos2_to_point(window_rect.min);
-        if self.known_size != window_size || recompiled {
-            self.known_size = window_size;
-            tree.resize(
-                window_size,
-                &mut MeasureContextImpl {
-                    egui_context: ui.ctx(),
-                },
-            );
+    /// Close the program with the provided index, first asking whether to
+    /// save its unsaved edits if it has any.
+    fn request_close(&mut self, index: usize) {
+        if self.programs[index].is_dirty() {
+            self.closing = Some(index);
+        } else {
+            self.close(index);
         }
+    }
 
-        for event in ui.input(|i| {
-            i.filtered_events(&egui::EventFilter {
-                tab: true,
-                horizontal_arrows: true,
-                vertical_arrows: true,
-                escape: true,
-            })
-        }) {
-            match event {
-                egui::Event::PointerMoved(pos) => {
-                    let position = if window_rect.contains(pos) {
-                        Some(convert_pos2_to_point(pos) - self.known_position)
-                    } else {
-                        None
-                    };
-                    if self.known_pointer_position == position {
-                        continue;
-                    }
-                    self.known_pointer_position = position;
-                    tree.handle_pointer_event(
-                        PointerEvent::Move { position },
-                        &mut MeasureContextImpl {
-                            egui_context: ui.ctx(),
-                        },
-                    );
-                }
-                egui::Event::PointerButton {
-                    pos,
-                    button,
-                    pressed,
-                    ..
-                } => {
-                    if !window_rect.contains(pos) {
-                        continue;
-                    }
-                    let button = match button {
-                        egui::PointerButton::Primary => PointerButton::Primary,
-                        egui::PointerButton::Secondary => PointerButton::Secondary,
-                        egui::PointerButton::Middle => PointerButton::Auxiliary,
-                        egui::PointerButton::Extra1 => PointerButton::Back,
-                        egui::PointerButton::Extra2 => PointerButton::Forward,
-                    };
-                    let event = if pressed {
-                        PointerEvent::Down { button }
-                    } else {
-                        PointerEvent::Up { button }
-                    };
-                    tree.handle_pointer_event(
-                        event,
-                        &mut MeasureContextImpl {
-                            egui_context: ui.ctx(),
-                        },
-                    );
-                }
-                _ => {}
+    fn close(&mut self, index: usize) {
+        self.programs.remove(index);
+        self.panes.retain(|pane| *pane != index);
+        for pane in &mut self.panes {
+            if *pane > index {
+                *pane -= 1;
             }
         }
+        if self.panes.is_empty() && !self.programs.is_empty() {
+            self.panes.push(0);
+        }
+        self.set_focused_pane(self.focused_pane.min(self.panes.len().saturating_sub(1)));
+    }
 
-        if ui.ui_contains_pointer() {
-            ui.ctx()
-                .set_cursor_icon(convert_cursor_icon(tree.cursor_icon()));
+    /// Reload the programs and assets affected by files modified on disk.
+    fn handle_file_changes(&mut self) {
+        for program in &mut self.programs {
+            for path in program.take_unwatched_assets() {
+                self.watcher.watch_file(&path);
+            }
         }
 
-        render_pass(tree, &mut renderer);
+        let base_src_dir = Path::new(BASE_SRC_DIR).canonicalize().ok();
+        for path in self.watcher.poll() {
+            // Programs built against a changed `base` would be rejected when
+            // loaded, so the host must be rebuilt instead.
+            if base_src_dir
+                .as_ref()
+                .is_some_and(|dir| path.starts_with(dir))
+            {
+                self.base_changed = true;
+                continue;
+            }
 
-        Ok(())
+            for program in &mut self.programs {
+                program.on_file_changed(&path);
+            }
+        }
     }
-}
-
-struct ProgramHandle {
-    tree: ObjectTree,
-    _textures: HashMap<String, egui::TextureHandle>,
-    _handle: libloading::Library,
-}
-
 
+    /// Ask whether to save or discard the unsaved edits of the program being
+    /// closed.
+    fn closing_ui(&mut self, ctx: &egui::Context) {
+        let Some(index) = self.closing else {
+            return;
+        };
 
-struct ViewContextImpl<'pass> {
-    textures: &'pass mut HashMap<String, egui::TextureHandle>,
-    egui_context: &'pass egui::Context,
-}
+        let (mut save, mut discard) = (false, false);
+        let modal = egui::Modal::new(egui::Id::new("closing")).show(ctx, |ui| {
+            let program = &self.programs[index];
+            ui.heading(format!("Save changes to {}?", program.name));
+            ui.label("Your changes will be lost if you don't save them.");
+            if let Some(error) = program.file_error() {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            ui.separator();
 
-impl ViewContext for ViewContextImpl<'_> {
-    fn load_texture(&mut self, path: &str) -> u64 {
-        let egui::TextureId::Managed(id) = self
-            .textures
-            .entry(path.to_string())
-            .or_insert_with(|| {
-                let image = image::ImageReader::open(path).unwrap().decode().unwrap();
-                let size = [image.width() as _, image.height() as _];
-                let image_buffer = image.to_rgba8();
-                let pixels = image_buffer.as_flat_samples();
-
-                self.egui_context.load_texture(
-                    path,
-                    egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice()),
-                    egui::TextureOptions::LINEAR,
-                )
+            ui.horizontal(|ui| {
+                save = ui.button("Save").clicked();
+                discard = ui.button("Discard").clicked();
+                ui.button("Cancel").clicked()
             })
-            .id()
-        else {
-            unreachable!("load_texture should only produce managed IDs")
-        };
+            .inner
+        });
 
-        id
+        if save {
+            self.programs[index].save();
+            // The program stays open if it couldn't be saved.
+            discard = !self.programs[index].is_dirty();
+        }
+        if discard {
+            self.closing = None;
+            self.close(index);
+        } else if modal.inner || modal.should_close() {
+            self.closing = None;
+        }
     }
-}
-
-struct RendererImpl<'pass> {
-    position: Point,
-    painter: &'pass egui::Painter,
-}
 
-impl Renderer for RendererImpl<'_> {
-    fn text(&mut self, content: &str, position: Point, font_size: f32, color: Rgba) {
-        self.painter.text(
-            convert_point(self.position + position),
-            egui::Align2::LEFT_TOP,
-            content,
-            egui::FontId::proportional(font_size),
-            convert_color(color),
-        );
+    fn toggle_split(&mut self) {
+        if self.panes.len() > 1 {
+            self.panes.truncate(1);
+            self.set_focused_pane(0);
+        } else if let Some(&first) = self.panes.first() {
+            // Show the next program next to the current one.
+            self.panes.push((first + 1) % self.programs.len());
+            self.set_focused_pane(1);
+        }
     }
 
-    fn quad(&mut self, position: Point, size: Size, color: Rgba) {
-        self.painter.rect(
-            egui::Rect::from_min_size(convert_point(self.position + position), convert_size(size)),
-            0,
-            convert_color(color),
-            egui::Stroke::NONE,
-            egui::StrokeKind::Inside,
-        );
-    }
+    fn tabs_ui(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button("Open", |ui| {
+            let mut dir = self.program_dir.display().to_string();
+            if ui.text_edit_singleline(&mut dir).changed() {
+                self.program_dir = PathBuf::from(dir);
+            }
+            ui.separator();
 
-    fn image(&mut self, texture_id: u64, position: Point, size: Size) {
-        self.painter.image(
-            egui::TextureId::Managed(texture_id),
-            egui::Rect::from_min_size(convert_point(self.position + position), convert_size(size)),
-            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
-            egui::Color32::WHITE,
-        );
-    }
-}
+            let mut paths = std::fs::read_dir(&self.program_dir)
+                .map(|entries| {
+                    entries
+                        .filter_map(|entry| Some(entry.ok()?.path()))
+                        .filter(|path| {
+                            path.extension()
+                                .is_some_and(|ext| ext == "rs" || ext == "view")
+                                || ProgramConfig::is_program_dir(path)
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            paths.sort();
+            if paths.is_empty() {
+                ui.label("No programs in this directory.");
+            }
+            for path in paths {
+                let label = path.file_name().unwrap_or_default().to_string_lossy();
+                if ui.button(label).clicked() {
+                    if let Err(error) = self.open(&path) {
+                        println!("ERROR: failed to open {}: {error}", path.display());
+                    }
+                    ui.close();
+                }
+            }
+        });
+        ui.menu_button("New", |ui| {
+            if ui.button("Rust program").clicked() {
+                self.create(ProgramKind::Compiled);
+                ui.close();
+            }
+            if ui.button("Declarative view").clicked() {
+                self.create(ProgramKind::Declarative);
+                ui.close();
+            }
+        });
+        ui.menu_button("Build", |ui| {
+            let options = &mut self.compile_options;
+            let mut changed = ui
+                .checkbox(&mut options.out_of_process, "Compile out of process")
+                .on_hover_text("Isolate the host from compiler crashes")
+                .changed();
+            ui.add_enabled_ui(options.out_of_process, |ui| {
+                let mut timeout = options.timeout.as_secs();
+                ui.horizontal(|ui| {
+                    ui.label("Timeout");
+                    if ui
+                        .add(
+                            egui::DragValue::new(&mut timeout)
+                                .range(1..=3600)
+                                .suffix(" s"),
+                        )
+                        .changed()
+                    {
+                        options.timeout = std::time::Duration::from_secs(timeout);
+                        changed = true;
+                    }
+                });
+            });
+            if changed {
+                for program in &mut self.programs {
+                    program.compile_options.out_of_process = options.out_of_process;
+                    program.compile_options.timeout = options.timeout;
+                }
+            }
 
-struct MeasureContextImpl<'pass> {
-    egui_context: &'pass egui::Context,
-}
+            // The profile is chosen per program.
+            let Some(program) = self
+                .panes
+                .get(self.focused_pane)
+                .map(|&index| &mut self.programs[index])
+                .filter(|program| program.kind() == ProgramKind::Compiled)
+            else {
+                return;
+            };
+            ui.separator();
+            ui.label(format!("Build {} as", program.name));
+            let options = &mut program.compile_options;
+            let mut rebuild = false;
+            for profile in BuildProfile::ALL {
+                rebuild |= ui
+                    .radio_value(&mut options.profile, profile, profile.name())
+                    .changed();
+            }
+            rebuild |= ui
+                .checkbox(&mut options.debuginfo, "Debug info")
+                .on_hover_text("Give panics in the program useful backtraces")
+                .changed();
+            if rebuild {
+                program.start_compiling();
+            }
+        });
+        let split = self.panes.len() > 1;
+        if ui
+            .add_enabled(
+                self.programs.len() > 1,
+                egui::Button::selectable(split, "Split"),
+            )
+            .clicked()
+        {
+            self.toggle_split();
+        }
+        ui.separator();
 
-impl MeasureContext for MeasureContextImpl<'_> {
-    fn text_size(&mut self, content: &str, font_size: f32) -> Size {
-        convert_vec2_to_size(
-            self.egui_context
-                .fonts_mut(|f| {
-                    f.layout(
-                        content.to_string(),
-                        egui::FontId::proportional(font_size),
-                        egui::Color32::WHITE,
-                        f32::INFINITY,
-                    )
-                })
-                .rect
-                .size(),
-        )
+        let focused = self.panes.get(self.focused_pane).copied();
+        let (mut shown, mut closed) = (None, None);
+        for (index, program) in self.programs.iter().enumerate() {
+            let label = if program.is_dirty() {
+                format!("{} •", program.name)
+            } else {
+                program.name.clone()
+            };
+            if ui.selectable_label(Some(index) == focused, label).clicked()
+                && Some(index) != focused
+            {
+                shown = Some(index);
+            }
+            if ui.small_button("×").on_hover_text("Close").clicked() {
+                closed = Some(index);
+            }
+        }
+        if let Some(index) = closed {
+            self.request_close(index);
+        } else if let Some(index) = shown {
+            self.show(index);
+        }
     }
 }
 
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.handle_file_changes();
+        self.closing_ui(ctx);
+
+        let save_shortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::S);
+        if ctx.input_mut(|input| input.consume_shortcut(&save_shortcut))
+            && let Some(&index) = self.panes.get(self.focused_pane)
+        {
+            self.programs[index].save();
+        }
 
+        egui::TopBottomPanel::top("tools").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                self.tabs_ui(ui);
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.toggle_value(&mut self.profiler.open, "Profiler");
+                    ui.toggle_value(&mut self.inspector.show_layout, "Layout");
+                    ui.toggle_value(&mut self.inspector.open, "Inspector");
+                    if self.base_changed {
+                        ui.colored_label(
+                            ui.visuals().warn_fg_color,
+                            "The sources of `base` changed, restart the host to use them.",
+                        );
+                    }
+                });
+            });
+        });
 
-#[inline(always)]
-const fn convert_point(point: Point) -> egui::Pos2 {
-    egui::Pos2 {
-        x: point.x,
-        y: point.y,
-    }
-}
-
-#[inline(always)]
-const fn convert_size(size: Size) -> egui::Vec2 {
-    egui::Vec2 {
-        x: size.width,
-        y: size.height,
-    }
-}
+        if self.profiler.open {
+            egui::TopBottomPanel::bottom("profiler")
+                .resizable(true)
+                .default_height(240.0)
+                .show(ctx, |ui| {
+                    let index = self.panes.get(self.focused_pane).copied();
+                    let tree = index.and_then(|index| self.programs[index].tree_mut());
+                    self.profiler.show(ui, tree);
+                });
+        }
 
-#[inline(always)]
-const fn convert_color(rgba: Rgba) -> egui::Color32 {
-    egui::Color32::from_rgba_premultiplied(rgba.r, rgba.g, rgba.b, rgba.a)
-}
+        if self.inspector.open {
+            egui::SidePanel::right("inspector")
+                .resizable(true)
+                .default_width(320.0)
+                .show(ctx, |ui| {
+                    let index = self.panes.get(self.focused_pane).copied();
+                    let shown = index.and_then(|index| {
+                        self.programs[index]
+                            .with_tree(|tree, guard| self.inspector.show(ui, Some((tree, guard))))
+                    });
+                    if shown.is_none() {
+                        self.inspector.show(ui, None);
+                    }
+                });
+        }
 
-const fn convert_cursor_icon(icon: CursorIcon) -> egui::CursorIcon {
-    match icon {
-        CursorIcon::PointingHand => egui::CursorIcon::PointingHand,
-        CursorIcon::IBeam => egui::CursorIcon::Text,
-        _ => egui::CursorIcon::Default,
-    }
-}
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if self.panes.is_empty() {
+                ui.centered_and_justified(|ui| {
+                    ui.heading("No programs open.");
+                });
+                return;
+            }
 
-#[inline(always)]
-const fn convert_vec2_to_size(vec2: egui::Vec2) -> Size {
-    Size::new(vec2.x, vec2.y)
-}
+            let panes = self.panes.clone();
+            ui.columns(panes.len(), |columns| {
+                for (pane, (ui, index)) in columns.iter_mut().zip(panes).enumerate() {
+                    // Pressing anywhere in a pane focuses it.
+                    let rect = ui.max_rect();
+                    if pane != self.focused_pane
+                        && ui.input(|input| {
+                            input.pointer.any_pressed()
+                                && input
+                                    .pointer
+                                    .interact_pos()
+                                    .is_some_and(|pos| rect.contains(pos))
+                        })
+                    {
+                        self.set_focused_pane(pane);
+                    }
 
-#[inline(always)]
-const fn convert_pos2_to_point(pos2: egui::Pos2) -> Point {
-    Point::new(pos2.x, pos2.y)
+                    let inspector = (pane == self.focused_pane).then_some(&mut self.inspector);
+                    egui::ScrollArea::vertical()
+                        .id_salt(("program", pane))
+                        .auto_shrink([false, false])
+                        .show(ui, |ui| {
+                            self.programs[index]
+                                .update(ui, inspector)
+                                .expect("failed to update program");
+                        });
+                }
+            });
+        });
+    }
 }
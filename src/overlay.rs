@@ -0,0 +1,77 @@
+//! # Layout Debug Overlay
+
+use {
+    base::{Area, LayoutOverlay, LayoutRegion, LayoutRegionKind, Point},
+    eframe::egui,
+};
+
+
+
+/// A [`LayoutOverlay`] that paints directly over the rendered program.
+pub struct LayoutOverlayPainter<'pass> {
+    pub painter: &'pass egui::Painter,
+    /// Where the program is rendered on the screen.
+    pub origin: Point,
+}
+
+impl LayoutOverlayPainter<'_> {
+    fn rect(&self, area: Area) -> egui::Rect {
+        egui::Rect::from_min_size(
+            egui::pos2(
+                self.origin.x + area.position.x,
+                self.origin.y + area.position.y,
+            ),
+            egui::vec2(area.size.width, area.size.height),
+        )
+    }
+}
+
+impl LayoutOverlay for LayoutOverlayPainter<'_> {
+    fn outline(&mut self, area: Area, overflowing: bool) {
+        let rect = self.rect(area);
+        if overflowing {
+            self.painter.rect_stroke(
+                rect,
+                0,
+                egui::Stroke::new(2.0, egui::Color32::from_rgb(0xff, 0x40, 0x40)),
+                egui::StrokeKind::Inside,
+            );
+            self.painter.text(
+                rect.right_top(),
+                egui::Align2::RIGHT_TOP,
+                "overflow",
+                egui::FontId::monospace(10.0),
+                egui::Color32::from_rgb(0xff, 0x40, 0x40),
+            );
+        } else {
+            self.painter.rect_stroke(
+                rect,
+                0,
+                egui::Stroke::new(
+                    1.0,
+                    egui::Color32::from_rgba_unmultiplied(0x40, 0xe0, 0x80, 0xa0),
+                ),
+                egui::StrokeKind::Inside,
+            );
+        }
+    }
+
+    fn baseline(&mut self, start: Point, width: f32) {
+        let start = egui::pos2(self.origin.x + start.x, self.origin.y + start.y);
+        self.painter.hline(
+            start.x..=start.x + width,
+            start.y,
+            egui::Stroke::new(1.0, egui::Color32::from_rgb(0xe0, 0x40, 0xe0)),
+        );
+    }
+
+    fn region(&mut self, region: LayoutRegion) {
+        let color = match region.kind {
+            LayoutRegionKind::Gap => egui::Color32::from_rgba_unmultiplied(0xff, 0xc0, 0x40, 0x50),
+            LayoutRegionKind::Spacer => {
+                egui::Color32::from_rgba_unmultiplied(0x40, 0x80, 0xff, 0x50)
+            }
+        };
+        self.painter.rect_filled(self.rect(region.area), 0, color);
+    }
+}
@@ -0,0 +1,205 @@
+//! # Profiler Panel
+
+use {
+    base::{FrameProfile, ObjectTree, SpanKind},
+    eframe::egui,
+};
+
+
+
+/// A frame with more measure calls than this is probably re-measuring nested
+/// objects far more often than it needs to.
+const MEASURE_CALL_WARNING: u32 = 500;
+
+/// A bottom panel showing the per-frame history and a flame view of the
+/// [tree's profiler](ObjectTree::profiler).
+#[derive(Default)]
+pub struct ProfilerPanel {
+    pub open: bool,
+    /// The [number](FrameProfile::number) of the frame shown in the flame view.
+    /// The latest frame is shown when this is `None`, or once the selected
+    /// frame has been dropped from the history.
+    selected_frame: Option<u64>,
+}
+
+impl ProfilerPanel {
+    pub fn show(&mut self, ui: &mut egui::Ui, tree: Option<&mut ObjectTree>) {
+        let Some(tree) = tree else {
+            ui.label("No program loaded.");
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            ui.heading("Profiler");
+            let mut enabled = tree.profiler().is_enabled();
+            if ui.checkbox(&mut enabled, "Record").changed() {
+                tree.profiler_mut().set_enabled(enabled);
+                self.selected_frame = None;
+            }
+            if self.selected_frame.is_some() && ui.button("Follow latest").clicked() {
+                self.selected_frame = None;
+            }
+        });
+        ui.separator();
+
+        let history = tree.profiler().history();
+        if history.is_empty() {
+            ui.label("No frames recorded.");
+            return;
+        }
+
+        if let Some(number) = self.history_ui(ui, tree) {
+            self.selected_frame = Some(number);
+        }
+
+        let history = tree.profiler().history();
+        let selected = self
+            .selected_frame
+            .and_then(|number| history.iter().find(|frame| frame.number == number));
+        if selected.is_none() {
+            self.selected_frame = None;
+        }
+        let frame = selected.unwrap_or_else(|| history.back().unwrap());
+
+        ui.horizontal_wrapped(|ui| {
+            ui.label(format!(
+                "Frame {}: {:.3} ms",
+                frame.number,
+                ms(frame.duration)
+            ));
+            for kind in [
+                SpanKind::UpdatePass,
+                SpanKind::EventPass,
+                SpanKind::HitTest,
+                SpanKind::LayoutPass,
+                SpanKind::ComposePass,
+                SpanKind::RenderPass,
+            ] {
+                ui.separator();
+                ui.label(format!("{}: {:.3} ms", kind.name(), ms(frame.total(kind))));
+            }
+            ui.separator();
+            let measure_calls = format!("{} measure calls", frame.measure_calls);
+            if frame.measure_calls > MEASURE_CALL_WARNING {
+                ui.colored_label(ui.visuals().warn_fg_color, measure_calls);
+            } else {
+                ui.label(measure_calls);
+            }
+        });
+
+        flame_ui(ui, frame);
+    }
+
+    /// Draw a bar per recorded frame, returning the number of a clicked frame.
+    fn history_ui(&self, ui: &mut egui::Ui, tree: &ObjectTree) -> Option<u64> {
+        let history = tree.profiler().history();
+        let (response, painter) =
+            ui.allocate_painter(egui::vec2(ui.available_width(), 48.0), egui::Sense::click());
+        let rect = response.rect;
+        painter.rect_filled(rect, 2, ui.visuals().extreme_bg_color);
+
+        let longest = history
+            .iter()
+            .map(|frame| frame.duration)
+            .max()
+            .unwrap_or_default()
+            .max(std::time::Duration::from_micros(1));
+        let bar_width = rect.width() / history.len() as f32;
+        for (index, frame) in history.iter().enumerate() {
+            let height = rect.height() * (ms(frame.duration) / ms(longest));
+            let x = rect.left() + index as f32 * bar_width;
+            let color = if Some(frame.number) == self.selected_frame {
+                ui.visuals().selection.bg_fill
+            } else if frame.measure_calls > MEASURE_CALL_WARNING {
+                ui.visuals().warn_fg_color
+            } else {
+                ui.visuals().widgets.inactive.fg_stroke.color
+            };
+            painter.rect_filled(
+                egui::Rect::from_min_max(
+                    egui::pos2(x, rect.bottom() - height),
+                    egui::pos2(x + bar_width.max(1.0), rect.bottom()),
+                ),
+                0,
+                color,
+            );
+        }
+
+        response
+            .interact_pointer_pos()
+            .filter(|_| response.clicked())
+            .map(|pos| ((pos.x - rect.left()) / bar_width) as usize)
+            .and_then(|index| history.get(index))
+            .map(|frame| frame.number)
+    }
+}
+
+/// Draw every span in the frame as a bar, positioned by time and stacked by
+/// depth.
+fn flame_ui(ui: &mut egui::Ui, frame: &FrameProfile) {
+    const ROW_HEIGHT: f32 = 18.0;
+
+    let depth = frame.spans.iter().map(|span| span.depth).max().unwrap_or(0) + 1;
+    egui::ScrollArea::vertical()
+        .id_salt("profiler_flame")
+        .auto_shrink([false, true])
+        .show(ui, |ui| {
+            let (rect, response) = ui.allocate_exact_size(
+                egui::vec2(ui.available_width(), depth as f32 * ROW_HEIGHT),
+                egui::Sense::hover(),
+            );
+            let painter = ui.painter_at(rect);
+            let total = ms(frame.duration).max(f32::EPSILON);
+            let scale = rect.width() / total;
+
+            for span in &frame.spans {
+                let span_rect = egui::Rect::from_min_size(
+                    egui::pos2(
+                        rect.left() + ms(span.start) * scale,
+                        rect.top() + span.depth as f32 * ROW_HEIGHT,
+                    ),
+                    egui::vec2((ms(span.duration) * scale).max(1.0), ROW_HEIGHT - 1.0),
+                );
+                painter.rect_filled(span_rect, 1, span_color(span.kind));
+
+                let label = match span.object_id {
+                    Some(id) => format!("{} #{id}", span.kind.name()),
+                    None => span.kind.name().to_string(),
+                };
+                if span_rect.width() > 40.0 {
+                    painter.text(
+                        span_rect.left_center() + egui::vec2(2.0, 0.0),
+                        egui::Align2::LEFT_CENTER,
+                        &label,
+                        egui::FontId::proportional(11.0),
+                        egui::Color32::BLACK,
+                    );
+                }
+                if response
+                    .hover_pos()
+                    .is_some_and(|pos| span_rect.contains(pos))
+                {
+                    response
+                        .clone()
+                        .on_hover_text_at_pointer(format!("{label}\n{:.3} ms", ms(span.duration)));
+                }
+            }
+        });
+}
+
+fn span_color(kind: SpanKind) -> egui::Color32 {
+    match kind {
+        SpanKind::UpdatePass => egui::Color32::from_rgb(0x9a, 0xd0, 0x8c),
+        SpanKind::EventPass => egui::Color32::from_rgb(0xe0, 0xc0, 0x70),
+        SpanKind::HitTest => egui::Color32::from_rgb(0xe0, 0x90, 0x70),
+        SpanKind::LayoutPass | SpanKind::Layout => egui::Color32::from_rgb(0x70, 0xb0, 0xe0),
+        SpanKind::ComposePass => egui::Color32::from_rgb(0xb0, 0x90, 0xe0),
+        SpanKind::RenderPass | SpanKind::Render => egui::Color32::from_rgb(0xe0, 0x80, 0xb0),
+        SpanKind::Measure => egui::Color32::from_rgb(0x80, 0xd0, 0xd0),
+    }
+}
+
+#[inline]
+fn ms(duration: std::time::Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
+}
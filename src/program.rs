@@ -0,0 +1,1276 @@
+//! # Programs
+
+use {
+    crate::{
+        assets::{AssetManager, AssetState},
+        compile_queue::{CompileJob, CompileQueue},
+        compiler::{CompileInput, CompileOptions},
+        inspector::Inspector,
+        overlay::LayoutOverlayPainter,
+        program_config::{PROGRAM_CONFIG_FILE, ProgramConfig},
+        source_file::SourceFile,
+    },
+    anyhow::{Result, anyhow, bail},
+    base::*,
+    eframe::egui,
+    std::{
+        mem::ManuallyDrop,
+        path::{Path, PathBuf},
+        time::{Duration, Instant},
+    },
+};
+
+
+
+
+/// How a [`Program`]'s source is turned into an [`ObjectTree`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ProgramKind {
+    /// Rust source, compiled into a shared object and loaded with `dlopen`.
+    Compiled,
+    /// A declarative view, built directly with a [`ViewRegistry`] without
+    /// recompiling anything.
+    Declarative,
+}
+
+impl ProgramKind {
+    pub fn from_path(path: impl AsRef<std::path::Path>) -> Self {
+        if path.as_ref().extension().is_some_and(|ext| ext == "view") {
+            Self::Declarative
+        } else {
+            Self::Compiled
+        }
+    }
+}
+
+/// A single program open in the host, with its own [`ObjectTree`].
+pub struct Program {
+    pub name: String,
+    kind: ProgramKind,
+    handle: Option<ProgramHandle>,
+    editing: bool,
+    /// Compiles the program into a shared object, which is distinct for every
+    /// open program.
+    compile_queue: CompileQueue,
+    pub compile_options: CompileOptions,
+    /// The latest revision of the source that was submitted to be built.
+    /// Every build request is a new revision.
+    revision: u64,
+    /// The revision of the source the running program was built from.
+    loaded_revision: Option<u64>,
+    /// How long the latest successful compilation took, and how it was
+    /// built.
+    last_build: Option<(Duration, CompileOptions)>,
+    /// A revision which was built successfully, but not loaded yet.
+    pending_reload: Option<u64>,
+    /// The source text the revision in `pending_reload` was built from, if
+    /// it was just built rather than restarted.
+    pending_source: Option<String>,
+    /// Why the latest compilation failed, if it did.
+    compile_error: Option<String>,
+    view_error: Option<String>,
+    /// Why the latest build of the program couldn't be loaded, if it
+    /// couldn't.
+    load_error: Option<String>,
+    /// A [stable key](ObjectBuilder::with_key) which was reused by an object
+    /// instantiated while the program ran. The object is still shown, just
+    /// without its key, until the program is reloaded.
+    duplicate_key: Option<DuplicateKeyError>,
+    /// The blob the state of a previous build handed over, which is kept
+    /// until a build takes it over, even if some builds fail to load in the
+    /// meantime.
+    pending_blob: Option<Vec<u8>>,
+    /// The panic which poisoned the program's tree, if it panicked. The tree
+    /// isn't called into again until the program is reloaded.
+    panic: Option<ProgramPanic>,
+    /// The assets loaded by any build of the program, which are kept across
+    /// reloads.
+    assets: AssetManager,
+    /// The entry file of the program, which is the only one that can be
+    /// edited in the host.
+    source: SourceFile,
+    config: ProgramConfig,
+    /// The last error encountered while saving or reverting the source.
+    file_error: Option<String>,
+    /// Whether the file was modified externally, or the program's entry was
+    /// moved, while there were unsaved edits.
+    external_change: bool,
+    /// Whether a view file the program read was modified, so its view must be
+    /// built again.
+    view_changed: bool,
+    egui_context: egui::Context,
+    known_size: Size,
+    known_position: Point,
+    known_pointer_position: Option<Point>,
+}
+
+impl Program {
+    pub fn load(
+        name: String,
+        output_name: String,
+        kind: ProgramKind,
+        source: SourceFile,
+        config: ProgramConfig,
+        compile_options: CompileOptions,
+        egui_context: egui::Context,
+    ) -> Self {
+        let mut this = Self {
+            name,
+            kind,
+            handle: None,
+            editing: false,
+            compile_queue: CompileQueue::new(&output_name, egui_context.clone()),
+            compile_options,
+            revision: 0,
+            loaded_revision: None,
+            last_build: None,
+            pending_reload: None,
+            pending_source: None,
+            compile_error: None,
+            view_error: None,
+            load_error: None,
+            duplicate_key: None,
+            pending_blob: None,
+            panic: None,
+            assets: AssetManager::new(egui_context.clone(), config.assets.clone()),
+            source,
+            config,
+            file_error: None,
+            external_change: false,
+            view_changed: false,
+            egui_context,
+            known_size: Size::ZERO,
+            known_position: Point::ZERO,
+            known_pointer_position: None,
+        };
+
+        this.start_compiling();
+
+        this
+    }
+
+    /// Build a new revision of the program from its current source,
+    /// superseding any build in progress.
+    pub fn start_compiling(&mut self) {
+        self.revision += 1;
+
+        // Declarative views are rebuilt on the next reload, there's nothing to compile.
+        if self.kind == ProgramKind::Declarative {
+            self.pending_reload = Some(self.revision);
+            self.pending_source = Some(self.source.text.clone());
+            return;
+        }
+
+        self.compile_queue.push(CompileJob {
+            revision: self.revision,
+            input: CompileInput {
+                name: self.name.clone(),
+                entry: self.source.path().to_path_buf(),
+                content: self.source.text.clone(),
+                dependencies: self.config.dependencies.clone(),
+            },
+            options: self.compile_options,
+        });
+    }
+
+    /// Pick up the result of the latest compilation, if it finished.
+    fn poll_compile_queue(&mut self) {
+        let Some(output) = self.compile_queue.poll() else {
+            return;
+        };
+
+        match output.result {
+            Ok(()) => {
+                self.compile_error = None;
+                self.pending_reload = Some(output.revision);
+                self.pending_source = Some(output.content);
+                self.last_build = Some((output.duration, output.options));
+            }
+            Err(error) => {
+                println!("ERROR: {error}");
+                self.compile_error = Some(error);
+            }
+        }
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        if self.kind == ProgramKind::Declarative {
+            self.panic = None;
+            self.rebuild_view();
+            return Ok(());
+        }
+
+        // A program which panicked starts afresh, as its state can't be trusted.
+        if self.panic.is_some() {
+            self.pending_blob = None;
+        } else if let Some(blob) = self.unload_state() {
+            self.pending_blob = Some(blob);
+        }
+        self.panic = None;
+
+        // We need to drop the previous shared object before reloading because `dlopen`
+        // won't load the new version if there are existing references to the old one.
+        drop(self.handle.take());
+
+        let handle = unsafe { libloading::Library::new(self.compile_queue.output_path())? };
+
+        // Nothing else in the program can be trusted until its manifest has been
+        // checked.
+        let manifest = unsafe { handle.get::<*const ProgramManifest>(PROGRAM_MANIFEST_SYMBOL) }
+            .map_err(|_| {
+                anyhow!(
+                    "the program doesn't export a manifest, export it with `base::export_program!`"
+                )
+            })?;
+        let manifest_version = unsafe { manifest.cast::<u32>().read() };
+        if manifest_version != MANIFEST_VERSION {
+            bail!(ManifestMismatch {
+                field: "manifest version",
+                found: manifest_version.to_string(),
+                expected: MANIFEST_VERSION.to_string(),
+            });
+        }
+        let manifest = unsafe { &**manifest };
+        manifest.check(&PROGRAM_MANIFEST)?;
+
+        // Programs export either a state, or only a view.
+        let new_state: Box<dyn Fn() -> Box<dyn ProgramState>> =
+            if let Ok(new_state) = unsafe { handle.get::<NewStateFn>(PROGRAM_STATE_SYMBOL) } {
+                let new_state = *new_state;
+                Box::new(move || new_state())
+            } else if let Ok(view_fn) = unsafe { handle.get::<ViewFn>(VIEW_SYMBOL) } {
+                let view_fn = *view_fn;
+                Box::new(move || Box::new(ViewFnState(view_fn)))
+            } else {
+                bail!(
+                    "the program exports neither a state nor a view, export one with \
+                    `base::export_program!`"
+                );
+            };
+
+        self.assets.start_view_build();
+        let assets = &mut self.assets;
+        let pending_blob = &self.pending_blob;
+        let mut loaded = None;
+        let result = (manifest.catch_panic)(&mut || {
+            let mut state = new_state();
+            match pending_blob {
+                Some(blob) => state.on_reload(blob.clone()),
+                None => state.init(),
+            }
+            let root_object = state.view(&mut ViewContextImpl { assets });
+            // The state is dropped here if the tree can't be built, while
+            // panics are still caught.
+            loaded = Some(ObjectTree::new(root_object).map(|tree| (state, tree)));
+        });
+        if let Err(panic) = result {
+            self.panic = Some(panic);
+            return Ok(());
+        }
+        let Some(loaded) = loaded else {
+            bail!("the program's view wasn't built");
+        };
+        let (state, tree) = loaded?;
+
+        self.pending_blob = None;
+        self.duplicate_key = None;
+        self.handle = Some(ProgramHandle {
+            tree: ManuallyDrop::new(tree),
+            state: ManuallyDrop::new(Some(state)),
+            last_update: None,
+            catch_panic: manifest.catch_panic,
+            display_list: DisplayList::default(),
+            shapes: Vec::new(),
+            shapes_origin: None,
+            shapes_pixels_per_point: 0.0,
+            _handle: Some(handle),
+        });
+
+        Ok(())
+    }
+
+    fn rebuild_view(&mut self) {
+        self.assets.start_view_build();
+        let result = ViewRegistry::with_builtins().build_source(
+            &self.source.text,
+            &mut ViewContextImpl {
+                assets: &mut self.assets,
+            },
+        );
+
+        match result.and_then(|root_object| {
+            ObjectTree::new(root_object).map_err(|error| ViewError {
+                message: error.to_string(),
+                path: None,
+                line: 0,
+                column: 0,
+            })
+        }) {
+            Ok(tree) => {
+                self.view_error = None;
+                self.duplicate_key = None;
+                self.handle = Some(ProgramHandle {
+                    tree: ManuallyDrop::new(tree),
+                    state: ManuallyDrop::new(None),
+                    last_update: None,
+                    // Declarative views are made of the host's own objects.
+                    catch_panic,
+                    display_list: DisplayList::default(),
+                    shapes: Vec::new(),
+                    shapes_origin: None,
+                    shapes_pixels_per_point: 0.0,
+                    _handle: None,
+                });
+            }
+            Err(error) => {
+                self.view_error = Some(error.to_string());
+            }
+        }
+    }
+
+    #[inline]
+    pub fn is_dirty(&self) -> bool {
+        self.source.is_dirty()
+    }
+
+    /// The last error encountered while saving or reverting the source.
+    #[inline]
+    pub fn file_error(&self) -> Option<&str> {
+        self.file_error.as_deref()
+    }
+
+    pub fn save(&mut self) {
+        self.file_error = self
+            .source
+            .save()
+            .err()
+            .map(|error| format!("Failed to save {}: {error}", self.source.path().display()));
+        // The saved edits are safe, but the new entry still has to be opened.
+        self.external_change = self.entry_moved();
+    }
+
+    /// Discard any unsaved edits and rebuild the program from the file on
+    /// disk, which is the program's new entry if it moved.
+    pub fn revert(&mut self) {
+        let result = if self.entry_moved() {
+            SourceFile::open(&self.config.entry).map(|source| self.source = source)
+        } else {
+            self.source.revert()
+        };
+        self.file_error = result
+            .err()
+            .map(|error| format!("Failed to read {}: {error}", self.config.entry.display()));
+        self.external_change = false;
+        self.start_compiling();
+    }
+
+    /// Whether the program's config names another entry than the one being
+    /// edited.
+    fn entry_moved(&self) -> bool {
+        self.config.entry != self.source.path()
+    }
+
+    #[inline]
+    pub fn kind(&self) -> ProgramKind {
+        self.kind
+    }
+
+    /// Pick up an external modification to one of the program's files,
+    /// reloading it straight away unless that would lose unsaved edits. The
+    /// provided path is expected to be canonical.
+    pub fn on_file_changed(&mut self, path: &Path) {
+        if self.assets.on_file_changed(path) {
+            self.view_changed = true;
+            self.egui_context.request_repaint();
+        }
+
+        if !self
+            .source
+            .path()
+            .canonicalize()
+            .is_ok_and(|entry| entry == path)
+        {
+            let Some(dir) = self.config.dir.clone() else {
+                return;
+            };
+            if !dir.canonicalize().is_ok_and(|dir| path.starts_with(dir)) {
+                return;
+            }
+
+            if path
+                .file_name()
+                .is_some_and(|name| name == PROGRAM_CONFIG_FILE)
+            {
+                match ProgramConfig::load(&dir) {
+                    Ok(config) => {
+                        self.file_error = None;
+                        self.assets.set_root(config.assets.clone());
+                        self.config = config;
+                    }
+                    Err(error) => {
+                        self.file_error = Some(error.to_string());
+                        return;
+                    }
+                }
+                // The entry may have moved, which is only picked up straight
+                // away if that doesn't lose unsaved edits.
+                if !self.entry_moved() {
+                    self.start_compiling();
+                } else if self.source.is_dirty() {
+                    self.external_change = true;
+                } else {
+                    self.revert();
+                }
+            } else if path.extension().is_some_and(|ext| ext == "rs") {
+                // Other modules can't be edited in the host, the entry's unsaved
+                // edits are built along with them.
+                self.start_compiling();
+            }
+            return;
+        }
+
+        self.check_disk();
+    }
+
+    /// Pick up an external modification to the program's entry by comparing
+    /// its modification time, reloading it straight away unless that would
+    /// lose unsaved edits.
+    fn check_disk(&mut self) {
+        if !self.external_change && self.source.changed_on_disk() {
+            if self.source.is_dirty() {
+                self.external_change = true;
+            } else {
+                self.revert();
+            }
+        }
+    }
+
+    /// Get the files of the assets loaded since the last call, which should be
+    /// watched for changes.
+    pub fn take_unwatched_assets(&mut self) -> Vec<PathBuf> {
+        self.assets.take_unwatched()
+    }
+
+    /// Show the state of the program's file, along with buttons to save or
+    /// revert it. Expects a right-to-left layout.
+    fn file_ui(&mut self, ui: &mut egui::Ui) {
+        if ui
+            .add_enabled(
+                self.source.is_dirty() && self.source.exists_on_disk(),
+                egui::Button::new("Revert"),
+            )
+            .on_hover_text("Discard unsaved edits")
+            .clicked()
+        {
+            self.revert();
+        }
+        if ui
+            .add_enabled(self.source.is_dirty(), egui::Button::new("Save"))
+            .on_hover_text("Ctrl+S")
+            .clicked()
+        {
+            self.save();
+        }
+
+        if self.external_change {
+            if ui.button("Keep mine").clicked() {
+                self.source.ignore_disk_changes();
+                self.external_change = false;
+            }
+            if ui.button("Reload").clicked() {
+                self.revert();
+            }
+            let message = if self.entry_moved() {
+                format!("The entry was moved to {}.", self.config.entry.display())
+            } else {
+                "The file was changed on disk.".to_string()
+            };
+            ui.colored_label(ui.visuals().warn_fg_color, message);
+        }
+        if let Some(error) = &self.file_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+        if let Some(error) = &self.duplicate_key {
+            ui.colored_label(ui.visuals().warn_fg_color, error.to_string())
+                .on_hover_text("The object was added without its key");
+        }
+        for (path, error) in self.assets.errors() {
+            ui.colored_label(
+                ui.visuals().error_fg_color,
+                format!("Failed to load {path}: {error}"),
+            );
+        }
+        let mut unused = self.assets.unused().collect::<Vec<_>>();
+        if !unused.is_empty() {
+            unused.sort_unstable();
+            ui.weak(format!("{} unused assets", unused.len()))
+                .on_hover_text(unused.join("\n"));
+        }
+
+        let mut path = self.source.path().display().to_string();
+        if self.source.is_dirty() {
+            path.push_str(" (modified)");
+        }
+        ui.weak(path);
+        if let Some(revision) = self.loaded_revision {
+            ui.weak(format!("Revision {revision}"))
+                .on_hover_text("The revision of the source the running program was built from");
+        }
+        if let Some((duration, options)) = self.last_build {
+            let debuginfo = if options.debuginfo {
+                ", debug info"
+            } else {
+                ""
+            };
+            ui.weak(format!(
+                "Built in {:.2} s ({}{debuginfo})",
+                duration.as_secs_f32(),
+                options.profile,
+            ));
+        }
+    }
+
+    /// The program's tree, unless it isn't loaded or has panicked. The tree
+    /// mustn't be used to call into the program's objects, see
+    /// [`Program::with_tree`].
+    pub fn tree_mut(&mut self) -> Option<&mut ObjectTree> {
+        if self.panic.is_some() {
+            return None;
+        }
+        self.handle.as_mut().map(|handle| &mut *handle.tree)
+    }
+
+    /// Run `f` with the program's tree, unless it isn't loaded or has
+    /// panicked. The tree's objects must be called through the provided
+    /// [guard](ProgramGuard).
+    pub fn with_tree<R>(
+        &mut self,
+        f: impl FnOnce(&mut ObjectTree, &mut ProgramGuard<'_>) -> R,
+    ) -> Option<R> {
+        if self.panic.is_some() {
+            return None;
+        }
+        let handle = self.handle.as_mut()?;
+        let mut guard = ProgramGuard {
+            catch_panic: handle.catch_panic,
+            panic: &mut self.panic,
+        };
+
+        Some(f(&mut handle.tree, &mut guard))
+    }
+
+    /// Ask the program's state for a blob to hand over to its next build.
+    fn unload_state(&mut self) -> Option<Vec<u8>> {
+        let handle = self.handle.as_mut()?;
+        let state = handle.state.as_mut()?;
+        let mut guard = ProgramGuard {
+            catch_panic: handle.catch_panic,
+            panic: &mut self.panic,
+        };
+
+        guard.call(|| state.on_unload()).flatten()
+    }
+
+    /// Show the program. Only the focused program is given the inspector.
+    pub fn update(&mut self, ui: &mut egui::Ui, inspector: Option<&mut Inspector>) -> Result<()> {
+        self.poll_compile_queue();
+        if self.compile_queue.is_compiling() {
+            ui.vertical_centered(|ui| {
+                ui.add_space(ui.available_height() / 2.0 - ui.spacing().interact_size.y);
+                ui.spinner();
+                if let Some(revision) = self.compile_queue.pending_revision() {
+                    ui.weak(format!("Compiling revision {revision}"));
+                }
+                if self.compile_options.out_of_process && ui.button("Cancel").clicked() {
+                    self.compile_queue.cancel();
+                }
+            });
+            return Ok(());
+        }
+
+        let recompiled = if let Some(revision) = self.pending_reload.take() {
+            let built_source = self.pending_source.take();
+            self.load_error = self.reload().err().map(|error| error.to_string());
+            if self.load_error.is_none() && self.view_error.is_none() {
+                self.loaded_revision = Some(revision);
+                // Only keep edits that built successfully, and not any made
+                // while they were being built.
+                if self.source.is_dirty() && built_source.as_ref() == Some(&self.source.text) {
+                    self.save();
+                }
+            }
+            true
+        } else {
+            false
+        };
+
+        ui.set_width(ui.available_width());
+        ui.set_height(ui.available_height());
+
+        if self.editing {
+            ui.allocate_ui_with_layout(
+                egui::vec2(
+                    ui.available_size_before_wrap().x,
+                    ui.spacing().interact_size.y,
+                ),
+                egui::Layout::right_to_left(egui::Align::Center).with_main_wrap(true),
+                |ui| {
+                    if ui.button("Confirm").clicked() {
+                        self.editing = false;
+                        self.start_compiling();
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.editing = false;
+                    }
+                    self.file_ui(ui);
+                },
+            );
+            ui.separator();
+
+            // There's no syntax for declarative views, which are better shown plain
+            // than as Rust.
+            let language = match self.kind {
+                ProgramKind::Compiled => "rs",
+                ProgramKind::Declarative => "txt",
+            };
+            let mut layouter = |ui: &egui::Ui, text: &dyn egui::TextBuffer, wrap_width: f32| {
+                let mut layout_job: egui::text::LayoutJob =
+                    egui_extras::syntax_highlighting::highlight(
+                        ui.ctx(),
+                        ui.style(),
+                        &egui_extras::syntax_highlighting::CodeTheme::dark(16.0),
+                        text.as_str(),
+                        language,
+                    );
+                layout_job.wrap.max_width = wrap_width;
+
+                ui.fonts_mut(|f| f.layout_job(layout_job))
+            };
+            ui.add(
+                egui::TextEdit::multiline(&mut self.source.text)
+                    .layouter(&mut layouter)
+                    .desired_width(ui.available_width()),
+            );
+
+            return Ok(());
+        }
+
+        ui.allocate_ui_with_layout(
+            egui::vec2(
+                ui.available_size_before_wrap().x,
+                ui.spacing().interact_size.y,
+            ),
+            egui::Layout::right_to_left(egui::Align::Center).with_main_wrap(true),
+            |ui| {
+                if ui.button("Edit").clicked() {
+                    self.editing = true;
+                }
+                self.file_ui(ui);
+            },
+        );
+        ui.separator();
+        if let Some(error) = &self.load_error {
+            ui.centered_and_justified(|ui| {
+                ui.heading(format!("Failed to load program: {error}"));
+            });
+            return Ok(());
+        }
+        // Reloading a declarative view can fail without compiling anything.
+        if let Some(error) = &self.view_error {
+            ui.centered_and_justified(|ui| {
+                ui.heading(format!("Invalid view: {error}"));
+            });
+            return Ok(());
+        }
+        if let Some(error) = &self.compile_error {
+            ui.heading("Compilation failed");
+            // Diagnostics from the compiler are laid out for a monospace font.
+            ui.label(egui::RichText::new(error).monospace());
+            return Ok(());
+        }
+        if let Some(panic) = &self.panic {
+            ui.heading("The program panicked");
+            ui.label(egui::RichText::new(panic.to_string()).monospace());
+            if ui
+                .button("Restart")
+                .on_hover_text("Reload the program from its latest build")
+                .clicked()
+            {
+                self.pending_reload = self.loaded_revision;
+            }
+            return Ok(());
+        }
+        if self.handle.is_none() {
+            // The compilation of the first revision was cancelled.
+            ui.centered_and_justified(|ui| {
+                ui.heading("Not compiled yet");
+            });
+            return Ok(());
+        }
+
+        // Any panic is shown from the next frame on.
+        self.update_tree(ui, inspector, recompiled)
+    }
+
+    /// Pass input to the program's tree and paint it, along with the
+    /// inspector's overlays. Only the calls into the program are guarded, see
+    /// [`ProgramGuard`].
+    fn update_tree(
+        &mut self,
+        ui: &mut egui::Ui,
+        mut inspector: Option<&mut Inspector>,
+        recompiled: bool,
+    ) -> Result<()> {
+        let Some(handle) = self.handle.as_mut() else {
+            return Ok(());
+        };
+        let mut guard = ProgramGuard {
+            catch_panic: handle.catch_panic,
+            panic: &mut self.panic,
+        };
+
+        let mut rebuilt = false;
+        if let Some(state) = handle.state.as_mut() {
+            let now = Instant::now();
+            let dt = handle
+                .last_update
+                .map_or(Duration::ZERO, |last_update| now - last_update);
+            handle.last_update = Some(now);
+
+            let mut context = UpdateContextImpl {
+                egui_context: &self.egui_context,
+                rebuild_view: false,
+            };
+            let Some(()) = guard.call(|| state.update(&mut context, dt)) else {
+                return Ok(());
+            };
+            if context.rebuild_view || std::mem::take(&mut self.view_changed) {
+                self.assets.start_view_build();
+                let assets = &mut self.assets;
+                let Some(result) = guard.call(|| {
+                    let root_object = state.view(&mut ViewContextImpl { assets });
+                    ObjectTree::new(root_object)
+                }) else {
+                    return Ok(());
+                };
+                match result {
+                    Ok(new_tree) => {
+                        // The previous tree's objects are dropped by the program.
+                        let tree = &mut *handle.tree;
+                        let Some(()) = guard.call(|| *tree = new_tree) else {
+                            return Ok(());
+                        };
+                        handle.display_list = DisplayList::default();
+                        rebuilt = true;
+                    }
+                    // The previous tree is kept, but the error is shown
+                    // instead until the program is reloaded.
+                    Err(error) => self.load_error = Some(error.to_string()),
+                }
+            }
+        }
+        let tree = &mut *handle.tree;
+
+        tree.profiler_mut().begin_frame();
+
+        let window_rect = ui.available_rect_before_wrap();
+        let window_size = convert_vec2_to_size(window_rect.size());
+        self.known_position = convert_pos2_to_point(window_rect.min);
+        let mut measure_context = MeasureContextImpl {
+            egui_context: ui.ctx(),
+            assets: &self.assets,
+        };
+        if self.known_size != window_size || recompiled || rebuilt {
+            self.known_size = window_size;
+            let Some(()) = guard.call(|| tree.resize(window_size, &mut measure_context)) else {
+                return Ok(());
+            };
+        }
+        // Objects may be measured from the sizes of the textures which just
+        // finished loading.
+        let assets_loaded = self.assets.poll();
+        let mut measure_context = MeasureContextImpl {
+            egui_context: ui.ctx(),
+            assets: &self.assets,
+        };
+        if !assets_loaded.is_empty()
+            && guard
+                .call(|| tree.invalidate_textures(&assets_loaded, &mut measure_context))
+                .is_none()
+        {
+            return Ok(());
+        }
+
+        for event in ui.input(|i| {
+            i.filtered_events(&egui::EventFilter {
+                tab: true,
+                horizontal_arrows: true,
+                vertical_arrows: true,
+                escape: true,
+            })
+        }) {
+            match event {
+                egui::Event::PointerMoved(pos) => {
+                    let position = if window_rect.contains(pos) {
+                        Some(convert_pos2_to_point(pos) - self.known_position)
+                    } else {
+                        None
+                    };
+                    if self.known_pointer_position == position {
+                        continue;
+                    }
+                    self.known_pointer_position = position;
+                    if let Some(inspector) = inspector.as_deref_mut()
+                        && inspector.picking
+                    {
+                        let Some(highlighted) =
+                            guard.call(|| position.and_then(|pos| tree.find_at(pos)))
+                        else {
+                            return Ok(());
+                        };
+                        inspector.highlighted = highlighted;
+                    }
+                    let event = PointerEvent::Move { position };
+                    let Some(()) =
+                        guard.call(|| tree.handle_pointer_event(event, &mut measure_context))
+                    else {
+                        return Ok(());
+                    };
+                }
+                egui::Event::PointerButton {
+                    pos,
+                    button,
+                    pressed,
+                    ..
+                } => {
+                    if !window_rect.contains(pos) {
+                        continue;
+                    }
+                    // Clicks made while picking select an object instead of reaching the
+                    // program.
+                    if let Some(inspector) = inspector.as_deref_mut()
+                        && inspector.picking
+                        && pressed
+                    {
+                        let position = convert_pos2_to_point(pos) - self.known_position;
+                        let Some(picked) = guard.call(|| tree.find_at(position)) else {
+                            return Ok(());
+                        };
+                        inspector.pick(picked);
+                        continue;
+                    }
+                    if !pressed
+                        && inspector
+                            .as_deref_mut()
+                            .is_some_and(Inspector::take_pending_release)
+                    {
+                        continue;
+                    }
+                    let button = match button {
+                        egui::PointerButton::Primary => PointerButton::Primary,
+                        egui::PointerButton::Secondary => PointerButton::Secondary,
+                        egui::PointerButton::Middle => PointerButton::Auxiliary,
+                        egui::PointerButton::Extra1 => PointerButton::Back,
+                        egui::PointerButton::Extra2 => PointerButton::Forward,
+                    };
+                    let event = if pressed {
+                        PointerEvent::Down { button }
+                    } else {
+                        PointerEvent::Up { button }
+                    };
+                    let Some(()) =
+                        guard.call(|| tree.handle_pointer_event(event, &mut measure_context))
+                    else {
+                        return Ok(());
+                    };
+                }
+                _ => {}
+            }
+        }
+
+        if ui.ui_contains_pointer() {
+            ui.ctx()
+                .set_cursor_icon(convert_cursor_icon(tree.cursor_icon()));
+        }
+
+        // Objects instantiated by the passes above may reuse a key, which
+        // leaves the tree usable.
+        if let Some(error) = tree.take_duplicate_key() {
+            self.duplicate_key = Some(error);
+        }
+
+        // Only re-record the shapes of damaged objects, and only rebuild the
+        // cached shapes if anything was re-recorded, a texture finished
+        // loading, or the program moved or was scaled.
+        let display_list = &mut handle.display_list;
+        let Some(repainted) = guard.call(|| render_pass_damaged(tree, display_list)) else {
+            return Ok(());
+        };
+        tree.profiler_mut().end_frame();
+        let pixels_per_point = ui.ctx().pixels_per_point();
+        if repainted
+            || !assets_loaded.is_empty()
+            || handle.shapes_origin != Some(self.known_position)
+            || handle.shapes_pixels_per_point != pixels_per_point
+        {
+            handle.shapes.clear();
+            let mut renderer = RendererImpl {
+                position: self.known_position,
+                egui_context: ui.ctx(),
+                shapes: &mut handle.shapes,
+                mesh: egui::Mesh::default(),
+                assets: &mut self.assets,
+            };
+            handle.display_list.replay(&mut renderer);
+            renderer.finish();
+            // Only the rasters of the new shapes are still needed.
+            self.assets.evict_rasters();
+            handle.shapes_origin = Some(self.known_position);
+            handle.shapes_pixels_per_point = pixels_per_point;
+        }
+        ui.painter().extend(handle.shapes.iter().cloned());
+
+        let Some(inspector) = inspector else {
+            return Ok(());
+        };
+
+        if inspector.show_layout {
+            let mut overlay = LayoutOverlayPainter {
+                painter: ui.painter(),
+                origin: self.known_position,
+            };
+            // Objects report their own layout regions.
+            guard.call(|| layout_overlay_pass(tree, &mut overlay));
+        }
+
+        if inspector.open {
+            inspector.paint_overlay(ui.painter(), self.known_position, tree);
+        }
+
+        Ok(())
+    }
+}
+
+/// Calls into a program under the program's own [`catch_panic`], since the
+/// host can't catch the program's panics itself. Host code shouldn't run
+/// under it, as the program can't catch the host's panics either.
+pub struct ProgramGuard<'program> {
+    catch_panic: fn(&mut dyn FnMut()) -> Result<(), ProgramPanic>,
+    /// Where a panic is recorded, which poisons the program's tree until
+    /// it's reloaded.
+    panic: &'program mut Option<ProgramPanic>,
+}
+
+impl ProgramGuard<'_> {
+    /// Run `f`, which calls into the program, unless the program has already
+    /// panicked. Returns `None` if it panics.
+    pub fn call<R>(&mut self, f: impl FnOnce() -> R) -> Option<R> {
+        if self.panic.is_some() {
+            return None;
+        }
+        let mut f = Some(f);
+        let mut output = None;
+        let result = (self.catch_panic)(&mut || {
+            if let Some(f) = f.take() {
+                output = Some(f());
+            }
+        });
+        if let Err(panic) = result {
+            *self.panic = Some(panic);
+        }
+
+        output
+    }
+}
+
+struct ProgramHandle {
+    /// Dropped under `catch_panic`, before the shared object.
+    tree: ManuallyDrop<ObjectTree>,
+    /// The state of the program, unless it's a declarative view. Dropped like
+    /// the tree.
+    state: ManuallyDrop<Option<Box<dyn ProgramState>>>,
+    /// When the state was last updated.
+    last_update: Option<Instant>,
+    /// The build of [`catch_panic`] the tree's objects must be called
+    /// through.
+    catch_panic: fn(&mut dyn FnMut()) -> Result<(), ProgramPanic>,
+    display_list: DisplayList,
+    /// The display list converted into shapes, drawn every frame. Quads and
+    /// images are batched into shared meshes and text shares its galleys, so
+    /// copying these into the painter is cheap.
+    shapes: Vec<egui::Shape>,
+    /// The position the shapes were converted at.
+    shapes_origin: Option<Point>,
+    /// The scale the shapes were converted at, which vector images were
+    /// rasterized for.
+    shapes_pixels_per_point: f32,
+    /// The shared object the tree's objects were loaded from, if any. This must
+    /// be dropped after the tree.
+    _handle: Option<libloading::Library>,
+}
+
+impl Drop for ProgramHandle {
+    fn drop(&mut self) {
+        // The objects' destructors are part of the program, and may panic too,
+        // which the panic hook already reports.
+        let tree = &mut self.tree;
+        let state = &mut self.state;
+        let _ = (self.catch_panic)(&mut || unsafe {
+            ManuallyDrop::drop(tree);
+            ManuallyDrop::drop(state);
+        });
+    }
+}
+
+/// The state of programs which only export a `view` function.
+struct ViewFnState(ViewFn);
+
+impl ProgramState for ViewFnState {
+    fn view(&mut self, context: &mut dyn ViewContext) -> Box<dyn Object> {
+        (self.0)(context)
+    }
+}
+
+
+
+struct ViewContextImpl<'pass> {
+    assets: &'pass mut AssetManager,
+}
+
+impl ViewContext for ViewContextImpl<'_> {
+    fn load_texture(&mut self, path: &str) -> u64 {
+        self.assets.load(path)
+    }
+
+    fn embed_asset(&mut self, path: &str, content: &[u8]) {
+        self.assets.embed(path, content);
+    }
+
+    fn read_view(&mut self, path: &str) -> Result<String, String> {
+        self.assets.read_view(path)
+    }
+}
+
+struct UpdateContextImpl<'pass> {
+    egui_context: &'pass egui::Context,
+    rebuild_view: bool,
+}
+
+impl UpdateContext for UpdateContextImpl<'_> {
+    fn rebuild_view(&mut self) {
+        self.rebuild_view = true;
+    }
+
+    fn request_update_after(&mut self, delay: Duration) {
+        self.egui_context.request_repaint_after(delay);
+    }
+}
+
+struct RendererImpl<'pass> {
+    position: Point,
+    egui_context: &'pass egui::Context,
+    shapes: &'pass mut Vec<egui::Shape>,
+    /// The quads and images drawn since the last text or change of texture.
+    mesh: egui::Mesh,
+    assets: &'pass mut AssetManager,
+}
+
+impl RendererImpl<'_> {
+    fn rect(
+        &mut self,
+        texture: egui::TextureId,
+        rect: egui::Rect,
+        uv: egui::Rect,
+        color: egui::Color32,
+    ) {
+        if self.mesh.texture_id != texture {
+            self.flush_mesh();
+            self.mesh.texture_id = texture;
+        }
+        self.mesh.add_rect_with_uv(rect, uv, color);
+    }
+
+    fn colored_rect(&mut self, rect: egui::Rect, color: egui::Color32) {
+        let uv = egui::Rect::from_min_max(egui::epaint::WHITE_UV, egui::epaint::WHITE_UV);
+        self.rect(egui::TextureId::default(), rect, uv, color);
+    }
+
+    fn flush_mesh(&mut self) {
+        if !self.mesh.is_empty() {
+            self.shapes
+                .push(egui::Shape::mesh(std::mem::take(&mut self.mesh)));
+        }
+    }
+
+    fn finish(mut self) {
+        self.flush_mesh();
+    }
+
+    /// Draw the texture with the provided ID, rasterizing vector images so
+    /// that the part of them covered by `uv` is drawn at `size` in pixels.
+    fn texture(
+        &mut self,
+        texture_id: u64,
+        position: Point,
+        size: Size,
+        uv: Area,
+        color: Rgba,
+        mask: bool,
+    ) {
+        let rect =
+            egui::Rect::from_min_size(convert_point(self.position + position), convert_size(size));
+        let pixels_per_point = self.egui_context.pixels_per_point();
+        let raster_size = [
+            (size.width / uv.size.width * pixels_per_point).round() as u32,
+            (size.height / uv.size.height * pixels_per_point).round() as u32,
+        ];
+        if let Some(texture) = self.assets.texture(texture_id, raster_size, mask) {
+            self.rect(
+                texture,
+                rect,
+                egui::Rect::from_min_size(convert_point(uv.position), convert_size(uv.size)),
+                convert_color(color),
+            );
+            return;
+        }
+
+        let placeholder_color = match self.assets.get(texture_id).map(|asset| &asset.state) {
+            Some(AssetState::Loading) => egui::Color32::from_gray(0x30),
+            // Errors are reported above the program.
+            _ => egui::Color32::from_rgb(0x60, 0x20, 0x20),
+        };
+        self.colored_rect(rect, placeholder_color);
+    }
+}
+
+impl Renderer for RendererImpl<'_> {
+    fn text(&mut self, content: &str, position: Point, font_size: f32, color: Rgba) {
+        let shape = self.egui_context.fonts_mut(|fonts| {
+            egui::Shape::text(
+                fonts,
+                convert_point(self.position + position),
+                egui::Align2::LEFT_TOP,
+                content,
+                egui::FontId::proportional(font_size),
+                convert_color(color),
+            )
+        });
+        self.flush_mesh();
+        self.shapes.push(shape);
+    }
+
+    fn quad(&mut self, position: Point, size: Size, color: Rgba) {
+        self.colored_rect(
+            egui::Rect::from_min_size(convert_point(self.position + position), convert_size(size)),
+            convert_color(color),
+        );
+    }
+
+    fn image(&mut self, texture_id: u64, position: Point, size: Size) {
+        self.image_region(
+            texture_id,
+            position,
+            size,
+            Area::new(Point::ZERO, Size::new(1.0, 1.0)),
+            Rgba::WHITE,
+        );
+    }
+
+    fn image_region(&mut self, texture_id: u64, position: Point, size: Size, uv: Area, tint: Rgba) {
+        self.texture(texture_id, position, size, uv, tint, false);
+    }
+
+    fn icon(&mut self, texture_id: u64, position: Point, size: Size, color: Rgba) {
+        let uv = Area::new(Point::ZERO, Size::new(1.0, 1.0));
+        self.texture(texture_id, position, size, uv, color, true);
+    }
+}
+
+struct MeasureContextImpl<'pass> {
+    egui_context: &'pass egui::Context,
+    assets: &'pass AssetManager,
+}
+
+impl MeasureContext for MeasureContextImpl<'_> {
+    fn text_size(&mut self, content: &str, font_size: f32) -> Size {
+        convert_vec2_to_size(
+            self.egui_context
+                .fonts_mut(|f| {
+                    f.layout(
+                        content.to_string(),
+                        egui::FontId::proportional(font_size),
+                        egui::Color32::WHITE,
+                        f32::INFINITY,
+                    )
+                })
+                .rect
+                .size(),
+        )
+    }
+
+    fn text_baseline_offset(&mut self, content: &str, font_size: f32) -> f32 {
+        let galley = self.egui_context.fonts_mut(|f| {
+            f.layout(
+                content.to_string(),
+                egui::FontId::proportional(font_size),
+                egui::Color32::WHITE,
+                f32::INFINITY,
+            )
+        });
+        let Some(row) = galley.rows.first() else {
+            return 0.0;
+        };
+        let ascent = row
+            .glyphs
+            .first()
+            .map_or(row.height() * 0.8, |glyph| glyph.pos.y);
+
+        galley.rect.height() - (row.pos.y + ascent)
+    }
+
+    fn texture_size(&mut self, texture_id: u64) -> Option<Size> {
+        let [width, height] = self.assets.size(texture_id)?;
+        Some(Size::new(width, height))
+    }
+}
+
+
+
+#[inline(always)]
+const fn convert_point(point: Point) -> egui::Pos2 {
+    egui::Pos2 {
+        x: point.x,
+        y: point.y,
+    }
+}
+
+#[inline(always)]
+const fn convert_size(size: Size) -> egui::Vec2 {
+    egui::Vec2 {
+        x: size.width,
+        y: size.height,
+    }
+}
+
+#[inline(always)]
+const fn convert_color(rgba: Rgba) -> egui::Color32 {
+    egui::Color32::from_rgba_premultiplied(rgba.r, rgba.g, rgba.b, rgba.a)
+}
+
+const fn convert_cursor_icon(icon: CursorIcon) -> egui::CursorIcon {
+    match icon {
+        CursorIcon::PointingHand => egui::CursorIcon::PointingHand,
+        CursorIcon::IBeam => egui::CursorIcon::Text,
+        _ => egui::CursorIcon::Default,
+    }
+}
+
+#[inline(always)]
+const fn convert_vec2_to_size(vec2: egui::Vec2) -> Size {
+    Size::new(vec2.x, vec2.y)
+}
+
+#[inline(always)]
+const fn convert_pos2_to_point(pos2: egui::Pos2) -> Point {
+    Point::new(pos2.x, pos2.y)
+}
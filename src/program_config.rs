@@ -0,0 +1,257 @@
+//! # Program Configuration
+//!
+//! A program can be a directory rather than a single file, in which case it
+//! is described by a `program.toml`:
+//!
+//! ```toml
+//! # The root module of the program, relative to the directory. Defaults to
+//! # `main.rs`.
+//! entry = "main.rs"
+//! # The directory asset paths are relative to, relative to the directory.
+//! # Defaults to the directory itself.
+//! assets = "res"
+//!
+//! [dependencies]
+//! # A prebuilt library, relative to the directory.
+//! utils = "lib/libutils.rlib"
+//! # A crate from the host's workspace, which is built along with `base`.
+//! shapes = { workspace = true }
+//! ```
+//!
+//! Other modules of the program are read from the directory as usual, and
+//! every dependency is available to the program like `base`, through
+//! `extern crate`.
+
+use {
+    anyhow::{Result, anyhow, bail},
+    std::path::{Path, PathBuf},
+};
+
+use crate::WORKSPACE_DIR;
+
+
+
+/// The name of the file describing a directory program.
+pub const PROGRAM_CONFIG_FILE: &str = "program.toml";
+
+/// How a program is laid out on disk, and what it depends on.
+#[derive(Clone, Debug, Default)]
+pub struct ProgramConfig {
+    /// The directory of the program, if it isn't a single file.
+    pub dir: Option<PathBuf>,
+    /// The root module of the program.
+    pub entry: PathBuf,
+    /// The directory the program's asset paths are relative to.
+    pub assets: PathBuf,
+    pub dependencies: Vec<Dependency>,
+}
+
+/// A library a program is linked against, in addition to `base`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Dependency {
+    /// The name the program refers to the crate by.
+    pub name: String,
+    /// The path of the crate's rlib.
+    pub path: PathBuf,
+    /// Whether the crate is part of the host's workspace, and should be built
+    /// before the program.
+    pub workspace: bool,
+}
+
+impl ProgramConfig {
+    /// The configuration of a program made of a single file.
+    pub fn single_file(path: impl Into<PathBuf>) -> Self {
+        let entry = path.into();
+        Self {
+            dir: None,
+            assets: entry.parent().unwrap_or(Path::new("")).to_path_buf(),
+            entry,
+            dependencies: Vec::new(),
+        }
+    }
+
+    /// Whether the provided directory holds a program.
+    pub fn is_program_dir(dir: &Path) -> bool {
+        dir.join(PROGRAM_CONFIG_FILE).is_file()
+    }
+
+    /// Read the `program.toml` of the program in the provided directory.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let config_path = dir.join(PROGRAM_CONFIG_FILE);
+        let text = std::fs::read_to_string(&config_path)
+            .map_err(|error| anyhow!("failed to read {}: {error}", config_path.display()))?;
+
+        Self::parse(dir, &text).map_err(|error| anyhow!("{}: {error}", config_path.display()))
+    }
+
+    fn parse(dir: &Path, text: &str) -> Result<Self> {
+        let document = toml_edit::Document::parse(text)?;
+        let table = document.as_table();
+        if let Some((key, _)) = table
+            .iter()
+            .find(|(key, _)| !matches!(*key, "entry" | "assets" | "dependencies"))
+        {
+            bail!("unknown key `{key}`, expected `entry`, `assets` or `dependencies`");
+        }
+
+        let entry = match table.get("entry") {
+            Some(entry) => entry
+                .as_str()
+                .ok_or_else(|| anyhow!("`entry` must be a string"))?,
+            None => "main.rs",
+        };
+        let assets = match table.get("assets") {
+            Some(assets) => assets
+                .as_str()
+                .ok_or_else(|| anyhow!("`assets` must be a string"))?,
+            None => "",
+        };
+
+        let mut dependencies = Vec::new();
+        if let Some(item) = table.get("dependencies") {
+            let deps = item
+                .as_table_like()
+                .ok_or_else(|| anyhow!("`dependencies` must be a table"))?;
+            for (name, value) in deps.iter() {
+                if let Some(path) = value.as_str() {
+                    dependencies.push(Dependency {
+                        name: name.to_string(),
+                        path: dir.join(path),
+                        workspace: false,
+                    });
+                } else if let Some(value) = value.as_table_like()
+                    && value
+                        .get("workspace")
+                        .and_then(|workspace| workspace.as_bool())
+                        == Some(true)
+                {
+                    if let Some((key, _)) = value.iter().find(|(key, _)| *key != "workspace") {
+                        bail!("unknown key `{key}` in dependency `{name}`");
+                    }
+                    dependencies.push(Dependency {
+                        name: name.to_string(),
+                        path: PathBuf::from(format!(
+                            "{WORKSPACE_DIR}/target/debug/lib{}.rlib",
+                            name.replace('-', "_"),
+                        )),
+                        workspace: true,
+                    });
+                } else {
+                    bail!(
+                        "dependency `{name}` must be a path to an rlib, or `{{ workspace = true }}`"
+                    );
+                }
+            }
+        }
+
+        Ok(Self {
+            dir: Some(dir.to_path_buf()),
+            entry: dir.join(entry),
+            assets: dir.join(assets),
+            dependencies,
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<ProgramConfig> {
+        ProgramConfig::parse(Path::new("/programs/demo"), text)
+    }
+
+    fn error(text: &str) -> String {
+        parse(text).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parse_defaults() {
+        let config = parse("").unwrap();
+        assert_eq!(config.dir.as_deref(), Some(Path::new("/programs/demo")));
+        assert_eq!(config.entry, Path::new("/programs/demo/main.rs"));
+        assert_eq!(config.assets, Path::new("/programs/demo/"));
+        assert!(config.dependencies.is_empty());
+    }
+
+    #[test]
+    fn parse_entry_and_assets() {
+        let config = parse(
+            r#"
+            entry = "src/lib.rs"
+            assets = "res"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.entry, Path::new("/programs/demo/src/lib.rs"));
+        assert_eq!(config.assets, Path::new("/programs/demo/res"));
+    }
+
+    #[test]
+    fn parse_dependencies() {
+        let config = parse(
+            r#"
+            [dependencies]
+            utils = "lib/libutils.rlib"
+            fancy-shapes = { workspace = true }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.dependencies,
+            [
+                Dependency {
+                    name: "utils".to_string(),
+                    path: PathBuf::from("/programs/demo/lib/libutils.rlib"),
+                    workspace: false,
+                },
+                Dependency {
+                    name: "fancy-shapes".to_string(),
+                    path: PathBuf::from(format!(
+                        "{WORKSPACE_DIR}/target/debug/libfancy_shapes.rlib"
+                    )),
+                    workspace: true,
+                },
+            ],
+        );
+
+        // Inline tables work the same.
+        let config = parse(r#"dependencies = { utils = "libutils.rlib" }"#).unwrap();
+        assert_eq!(config.dependencies[0].name, "utils");
+    }
+
+    #[test]
+    fn report_invalid_keys() {
+        assert_eq!(error("entry = 1"), "`entry` must be a string",);
+        assert_eq!(error("assets = [\"res\"]"), "`assets` must be a string",);
+        assert_eq!(
+            error("entri = \"main.rs\""),
+            "unknown key `entri`, expected `entry`, `assets` or `dependencies`",
+        );
+        assert_eq!(
+            error("dependencies = \"utils\""),
+            "`dependencies` must be a table",
+        );
+        let invalid = "dependency `utils` must be a path to an rlib, or `{ workspace = true }`";
+        assert_eq!(error("[dependencies]\nutils = 1"), invalid);
+        assert_eq!(
+            error("[dependencies]\nutils = { workspace = false }"),
+            invalid
+        );
+        assert_eq!(
+            error("[dependencies]\nutils = { path = \"libutils.rlib\" }"),
+            invalid
+        );
+        assert_eq!(
+            error("[dependencies]\nutils = { workspace = true, version = \"1\" }"),
+            "unknown key `version` in dependency `utils`",
+        );
+    }
+
+    #[test]
+    fn report_invalid_toml() {
+        assert!(error("entry = ").contains("TOML parse error"));
+    }
+}
@@ -0,0 +1,96 @@
+//! # Source Files
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+
+
+/// The source of a program, backed by a file on disk.
+pub struct SourceFile {
+    path: PathBuf,
+    /// The text being edited, which may differ from what's on disk.
+    pub text: String,
+    /// The text as it was last read from or written to disk.
+    saved: Option<String>,
+    /// The file's modification time when it was last read or written.
+    modified: Option<SystemTime>,
+}
+
+impl SourceFile {
+    /// Read the file at the provided path.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let text = std::fs::read_to_string(&path)?;
+
+        Ok(Self {
+            modified: modified_time(&path),
+            saved: Some(text.clone()),
+            text,
+            path,
+        })
+    }
+
+    /// Create a source file which won't exist on disk until it is first
+    /// [saved](Self::save).
+    pub fn unsaved(path: impl Into<PathBuf>, text: String) -> Self {
+        Self {
+            path: path.into(),
+            text,
+            saved: None,
+            modified: None,
+        }
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the text has been edited since it was last saved.
+    pub fn is_dirty(&self) -> bool {
+        self.saved.as_ref() != Some(&self.text)
+    }
+
+    /// Whether the file has been read from or written to disk, so that it can
+    /// be [reverted](Self::revert).
+    #[inline]
+    pub fn exists_on_disk(&self) -> bool {
+        self.saved.is_some()
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        std::fs::write(&self.path, &self.text)?;
+        self.saved = Some(self.text.clone());
+        self.modified = modified_time(&self.path);
+
+        Ok(())
+    }
+
+    /// Discard any edits and read the file from disk again.
+    pub fn revert(&mut self) -> io::Result<()> {
+        *self = Self::open(self.path.clone())?;
+
+        Ok(())
+    }
+
+    /// Whether the file was modified by something else since it was last read
+    /// or written.
+    pub fn changed_on_disk(&self) -> bool {
+        self.saved.is_some() && modified_time(&self.path) != self.modified
+    }
+
+    /// Stop reporting the current external modification, keeping the edited
+    /// text as it is.
+    pub fn ignore_disk_changes(&mut self) {
+        self.modified = modified_time(&self.path);
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
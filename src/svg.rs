@@ -0,0 +1,1090 @@
+//! # SVG
+//!
+//! See [`Svg`] for details.
+
+use {
+    eframe::egui,
+    quick_xml::{
+        Reader,
+        events::{BytesStart, Event},
+    },
+    std::f32::consts::{FRAC_PI_2, TAU},
+    tiny_skia::{
+        FillRule, LineCap, LineJoin, Paint, Path, PathBuilder, Pixmap, Rect, Stroke, Transform,
+    },
+};
+
+
+
+/// Elements whose content isn't drawn directly.
+const SKIPPED_ELEMENTS: &[&[u8]] = &[
+    b"defs",
+    b"clipPath",
+    b"mask",
+    b"symbol",
+    b"marker",
+    b"pattern",
+    b"linearGradient",
+    b"radialGradient",
+    b"style",
+    b"title",
+    b"desc",
+    b"metadata",
+];
+
+/// A parsed SVG document, which can be rasterized at any size.
+///
+/// Only the subset of SVG that icons are usually made of is supported: paths
+/// and basic shapes, optionally grouped and transformed, filled and stroked
+/// with solid colors. Everything else, like gradients, text or stylesheets, is
+/// ignored.
+pub struct Svg {
+    /// The intrinsic width of the document, in points.
+    pub width: f32,
+    /// The intrinsic height of the document, in points.
+    pub height: f32,
+    shapes: Vec<Shape>,
+}
+
+struct Shape {
+    path: Path,
+    /// Maps the shape's path to the document's intrinsic size.
+    transform: Transform,
+    fill: Option<([u8; 4], FillRule)>,
+    stroke: Option<([u8; 4], Stroke)>,
+}
+
+/// The presentation attributes inherited by an element from its parent.
+#[derive(Clone)]
+struct Style {
+    fill: Option<[u8; 4]>,
+    fill_rule: FillRule,
+    fill_opacity: f32,
+    stroke: Option<[u8; 4]>,
+    stroke_width: f32,
+    stroke_opacity: f32,
+    line_cap: LineCap,
+    line_join: LineJoin,
+    opacity: f32,
+    /// The value of `currentColor`.
+    color: [u8; 4],
+    transform: Transform,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            fill: Some([0, 0, 0, 255]),
+            fill_rule: FillRule::Winding,
+            fill_opacity: 1.0,
+            stroke: None,
+            stroke_width: 1.0,
+            stroke_opacity: 1.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            opacity: 1.0,
+            color: [0, 0, 0, 255],
+            transform: Transform::identity(),
+        }
+    }
+}
+
+impl Svg {
+    pub fn parse(content: &[u8]) -> Result<Self, String> {
+        let text = std::str::from_utf8(content).map_err(|_| "the SVG isn't valid UTF-8")?;
+        let mut reader = Reader::from_str(text);
+
+        let mut svg = None;
+        // The style of every open element, or `None` if it isn't drawn.
+        let mut stack = Vec::<Option<Style>>::new();
+        loop {
+            let event = reader.read_event().map_err(|error| error.to_string())?;
+            let (element, empty) = match &event {
+                Event::Start(element) => (element, false),
+                Event::Empty(element) => (element, true),
+                Event::End(_) => {
+                    stack.pop();
+                    continue;
+                }
+                Event::Eof => break,
+                _ => continue,
+            };
+
+            let parent = match stack.last() {
+                Some(Some(parent)) => Some(parent),
+                Some(None) => {
+                    if !empty {
+                        stack.push(None);
+                    }
+                    continue;
+                }
+                None => None,
+            };
+            let style = enter_element(element, parent, &mut svg)?;
+            if !empty {
+                stack.push(style);
+            }
+        }
+
+        svg.ok_or_else(|| "the document has no `svg` element".to_string())
+    }
+
+    /// Rasterize the document at the provided size in pixels. Masks are
+    /// drawn in white, only keeping the coverage of the shapes, so that they
+    /// can be recolored.
+    pub fn rasterize(&self, width: u32, height: u32, mask: bool) -> egui::ColorImage {
+        let width = width.max(1);
+        let height = height.max(1);
+        let mut pixmap = Pixmap::new(width, height).expect("the size is valid");
+
+        let scale = Transform::from_scale(width as f32 / self.width, height as f32 / self.height);
+        let paint = |color: [u8; 4]| {
+            let [r, g, b, a] = if mask {
+                [255, 255, 255, color[3]]
+            } else {
+                color
+            };
+            let mut paint = Paint::default();
+            paint.set_color_rgba8(r, g, b, a);
+            paint
+        };
+        for shape in &self.shapes {
+            let transform = scale.pre_concat(shape.transform);
+            if let Some((color, fill_rule)) = shape.fill {
+                pixmap.fill_path(&shape.path, &paint(color), fill_rule, transform, None);
+            }
+            if let Some((color, stroke)) = &shape.stroke {
+                pixmap.stroke_path(&shape.path, &paint(*color), stroke, transform, None);
+            }
+        }
+
+        egui::ColorImage::from_rgba_premultiplied([width as usize, height as usize], pixmap.data())
+    }
+}
+
+/// Read an element, adding its shape to the document if it has one, and get
+/// the style its children inherit, unless they aren't drawn.
+fn enter_element(
+    element: &BytesStart<'_>,
+    parent: Option<&Style>,
+    svg: &mut Option<Svg>,
+) -> Result<Option<Style>, String> {
+    let name = element.local_name();
+    let name = name.as_ref();
+    if SKIPPED_ELEMENTS.contains(&name) {
+        return Ok(None);
+    }
+
+    let mut attributes = Vec::new();
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(|error| error.to_string())?;
+        let key = String::from_utf8_lossy(attribute.key.local_name().as_ref()).into_owned();
+        let value = attribute
+            .unescape_value()
+            .map_err(|error| error.to_string())?
+            .into_owned();
+        attributes.push((key, value));
+    }
+    // Inline styles take precedence over attributes.
+    if let Some((_, style)) = attributes.iter().find(|(key, _)| key == "style") {
+        let declarations = style
+            .split(';')
+            .filter_map(|declaration| declaration.split_once(':'))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .collect::<Vec<_>>();
+        attributes.extend(declarations);
+    }
+    let attribute = |key: &str| {
+        attributes
+            .iter()
+            .rev()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    };
+    let number = |key: &str| attribute(key).and_then(parse_length).unwrap_or(0.0);
+
+    let mut style = match parent {
+        Some(parent) => parent.clone(),
+        // Only the outermost `svg` element establishes the document.
+        None if name == b"svg" => {
+            let view_box = attribute("viewBox").and_then(|view_box| {
+                let mut lexer = Lexer::new(view_box);
+                let view_box = [
+                    lexer.number()?,
+                    lexer.number()?,
+                    lexer.number()?,
+                    lexer.number()?,
+                ];
+                (view_box[2] > 0.0 && view_box[3] > 0.0).then_some(view_box)
+            });
+            let length = |key: &str, fallback: f32| {
+                attribute(key)
+                    .filter(|value| !value.ends_with('%'))
+                    .and_then(parse_length)
+                    .filter(|length| *length > 0.0)
+                    .unwrap_or(fallback)
+            };
+            let width = length("width", view_box.map_or(100.0, |view_box| view_box[2]));
+            let height = length("height", view_box.map_or(100.0, |view_box| view_box[3]));
+
+            let mut style = Style::default();
+            if let Some(view_box) = view_box {
+                style.transform = view_box_transform(
+                    view_box,
+                    width,
+                    height,
+                    attribute("preserveAspectRatio").unwrap_or(""),
+                );
+            }
+            *svg = Some(Svg {
+                width,
+                height,
+                shapes: Vec::new(),
+            });
+            style
+        }
+        None => return Err("the root element isn't `svg`".to_string()),
+    };
+
+    if let Some(color) = attribute("color").and_then(|value| parse_color(value, &style)) {
+        style.color = color.unwrap_or([0, 0, 0, 0]);
+    }
+    if let Some(fill) = attribute("fill").and_then(|value| parse_color(value, &style)) {
+        style.fill = fill;
+    }
+    if let Some(stroke) = attribute("stroke").and_then(|value| parse_color(value, &style)) {
+        style.stroke = stroke;
+    }
+    match attribute("fill-rule") {
+        Some("evenodd") => style.fill_rule = FillRule::EvenOdd,
+        Some("nonzero") => style.fill_rule = FillRule::Winding,
+        _ => {}
+    }
+    match attribute("stroke-linecap") {
+        Some("butt") => style.line_cap = LineCap::Butt,
+        Some("round") => style.line_cap = LineCap::Round,
+        Some("square") => style.line_cap = LineCap::Square,
+        _ => {}
+    }
+    match attribute("stroke-linejoin") {
+        Some("miter") => style.line_join = LineJoin::Miter,
+        Some("round") => style.line_join = LineJoin::Round,
+        Some("bevel") => style.line_join = LineJoin::Bevel,
+        _ => {}
+    }
+    if let Some(width) = attribute("stroke-width").and_then(parse_length) {
+        style.stroke_width = width;
+    }
+    if let Some(opacity) = attribute("fill-opacity").and_then(parse_length) {
+        style.fill_opacity = opacity.clamp(0.0, 1.0);
+    }
+    if let Some(opacity) = attribute("stroke-opacity").and_then(parse_length) {
+        style.stroke_opacity = opacity.clamp(0.0, 1.0);
+    }
+    // Group opacity is approximated by applying it to every shape separately.
+    if let Some(opacity) = attribute("opacity").and_then(parse_length) {
+        style.opacity *= opacity.clamp(0.0, 1.0);
+    }
+    if let Some(transform) = attribute("transform") {
+        style.transform = style.transform.pre_concat(parse_transform(transform));
+    }
+
+    let path = match name {
+        b"path" => attribute("d").and_then(parse_path_data),
+        b"rect" => {
+            let rx = attribute("rx").and_then(parse_length);
+            let ry = attribute("ry").and_then(parse_length);
+            rect_path(
+                number("x"),
+                number("y"),
+                number("width"),
+                number("height"),
+                rx.or(ry).unwrap_or(0.0),
+                ry.or(rx).unwrap_or(0.0),
+            )
+        }
+        b"circle" => {
+            let r = number("r");
+            Rect::from_xywh(number("cx") - r, number("cy") - r, r * 2.0, r * 2.0)
+                .and_then(PathBuilder::from_oval)
+        }
+        b"ellipse" => {
+            let (rx, ry) = (number("rx"), number("ry"));
+            Rect::from_xywh(number("cx") - rx, number("cy") - ry, rx * 2.0, ry * 2.0)
+                .and_then(PathBuilder::from_oval)
+        }
+        b"line" => {
+            let mut builder = PathBuilder::new();
+            builder.move_to(number("x1"), number("y1"));
+            builder.line_to(number("x2"), number("y2"));
+            builder.finish()
+        }
+        b"polyline" | b"polygon" => attribute("points").and_then(|points| {
+            let mut lexer = Lexer::new(points);
+            let mut builder = PathBuilder::new();
+            while let (Some(x), Some(y)) = (lexer.number(), lexer.number()) {
+                if builder.is_empty() {
+                    builder.move_to(x, y);
+                } else {
+                    builder.line_to(x, y);
+                }
+            }
+            if name == b"polygon" {
+                builder.close();
+            }
+            builder.finish()
+        }),
+        _ => None,
+    };
+
+    if let (Some(path), Some(svg)) = (path, svg.as_mut()) {
+        let with_opacity = |[r, g, b, a]: [u8; 4], opacity: f32| {
+            [r, g, b, (a as f32 * opacity * style.opacity).round() as u8]
+        };
+        let mut stroke = Stroke {
+            width: style.stroke_width,
+            line_cap: style.line_cap,
+            line_join: style.line_join,
+            ..Stroke::default()
+        };
+        stroke.width = stroke.width.max(0.0);
+        svg.shapes.push(Shape {
+            path,
+            transform: style.transform,
+            fill: style
+                .fill
+                .map(|fill| (with_opacity(fill, style.fill_opacity), style.fill_rule)),
+            stroke: style
+                .stroke
+                .filter(|_| style.stroke_width > 0.0)
+                .map(|color| (with_opacity(color, style.stroke_opacity), stroke)),
+        });
+    }
+
+    Ok(Some(style))
+}
+
+/// Get the transform fitting a `viewBox` into a viewport of the provided
+/// size, as described by the `preserveAspectRatio` attribute. By default, the
+/// view box is scaled uniformly to fit in the viewport, and centered in it.
+fn view_box_transform(
+    [x, y, view_width, view_height]: [f32; 4],
+    width: f32,
+    height: f32,
+    preserve_aspect_ratio: &str,
+) -> Transform {
+    let mut words = preserve_aspect_ratio.split_ascii_whitespace().peekable();
+    // Only meaningful for images referencing other documents.
+    words.next_if_eq(&"defer");
+    let align = words.next().unwrap_or("xMidYMid");
+    let (scale_x, scale_y) = (width / view_width, height / view_height);
+    if align == "none" {
+        return Transform::from_scale(scale_x, scale_y).pre_translate(-x, -y);
+    }
+
+    let scale = match words.next() {
+        Some("slice") => scale_x.max(scale_y),
+        _ => scale_x.min(scale_y),
+    };
+    let offset = |align: Option<&str>, space: f32| match align {
+        Some("Min") => 0.0,
+        Some("Max") => space,
+        _ => space / 2.0,
+    };
+    Transform::from_translate(
+        offset(align.get(1..4), width - view_width * scale),
+        offset(align.get(5..8), height - view_height * scale),
+    )
+    .pre_scale(scale, scale)
+    .pre_translate(-x, -y)
+}
+
+/// Parse a length, ignoring its unit.
+fn parse_length(value: &str) -> Option<f32> {
+    Lexer::new(value).number()
+}
+
+/// Parse a paint, which is `None` if it's `none`. Unsupported paints, like
+/// gradients, are drawn with their fallback color, if any, or in black.
+fn parse_color(value: &str, style: &Style) -> Option<Option<[u8; 4]>> {
+    let value = value.trim();
+    if let Some(reference) = value.strip_prefix("url(") {
+        let fallback = reference
+            .split_once(')')
+            .map_or("", |(_, rest)| rest.trim());
+        return match fallback {
+            "" => Some(Some([0, 0, 0, 255])),
+            fallback => parse_color(fallback, style),
+        };
+    }
+
+    let color = match value {
+        "none" | "transparent" => return Some(None),
+        "currentColor" => style.color,
+        "black" => [0, 0, 0, 255],
+        "white" => [255, 255, 255, 255],
+        "gray" | "grey" => [128, 128, 128, 255],
+        "red" => [255, 0, 0, 255],
+        "green" => [0, 128, 0, 255],
+        "blue" => [0, 0, 255, 255],
+        "yellow" => [255, 255, 0, 255],
+        "orange" => [255, 165, 0, 255],
+        "purple" => [128, 0, 128, 255],
+        _ => {
+            if let Some(hex) = value.strip_prefix('#') {
+                let digit = |index: usize| {
+                    u8::from_str_radix(hex.get(index..index + 1)?, 16)
+                        .ok()
+                        .map(|digit| digit * 17)
+                };
+                let byte = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+                match hex.len() {
+                    3 => [digit(0)?, digit(1)?, digit(2)?, 255],
+                    6 => [byte(0)?, byte(2)?, byte(4)?, 255],
+                    8 => [byte(0)?, byte(2)?, byte(4)?, byte(6)?],
+                    _ => return None,
+                }
+            } else if let Some(channels) = value
+                .strip_prefix("rgb(")
+                .and_then(|channels| channels.strip_suffix(')'))
+            {
+                let mut lexer = Lexer::new(channels);
+                let mut channel = || Some(lexer.number()?.clamp(0.0, 255.0) as u8);
+                [channel()?, channel()?, channel()?, 255]
+            } else {
+                return None;
+            }
+        }
+    };
+
+    Some(Some(color))
+}
+
+fn parse_transform(value: &str) -> Transform {
+    let mut transform = Transform::identity();
+    for function in value.split(')') {
+        let Some((name, arguments)) = function.split_once('(') else {
+            continue;
+        };
+        let mut lexer = Lexer::new(arguments);
+        let mut arguments = Vec::new();
+        while let Some(argument) = lexer.number() {
+            arguments.push(argument);
+        }
+
+        let next = match (name.trim_matches([' ', ',']), arguments.as_slice()) {
+            ("matrix", &[a, b, c, d, e, f]) => Transform::from_row(a, b, c, d, e, f),
+            ("translate", &[x]) => Transform::from_translate(x, 0.0),
+            ("translate", &[x, y]) => Transform::from_translate(x, y),
+            ("scale", &[scale]) => Transform::from_scale(scale, scale),
+            ("scale", &[x, y]) => Transform::from_scale(x, y),
+            ("rotate", &[angle]) => Transform::from_rotate(angle),
+            ("rotate", &[angle, x, y]) => Transform::from_translate(x, y)
+                .pre_concat(Transform::from_rotate(angle))
+                .pre_translate(-x, -y),
+            ("skewX", &[angle]) => {
+                Transform::from_row(1.0, 0.0, angle.to_radians().tan(), 1.0, 0.0, 0.0)
+            }
+            ("skewY", &[angle]) => {
+                Transform::from_row(1.0, angle.to_radians().tan(), 0.0, 1.0, 0.0, 0.0)
+            }
+            _ => continue,
+        };
+        transform = transform.pre_concat(next);
+    }
+
+    transform
+}
+
+fn rect_path(x: f32, y: f32, width: f32, height: f32, rx: f32, ry: f32) -> Option<Path> {
+    let rect = Rect::from_xywh(x, y, width, height)?;
+    let rx = rx.clamp(0.0, width / 2.0);
+    let ry = ry.clamp(0.0, height / 2.0);
+    if rx == 0.0 || ry == 0.0 {
+        return Some(PathBuilder::from_rect(rect));
+    }
+
+    let mut builder = PathBuilder::new();
+    let (right, bottom) = (x + width, y + height);
+    builder.move_to(x + rx, y);
+    builder.line_to(right - rx, y);
+    arc_to(
+        &mut builder,
+        (right - rx, y),
+        (rx, ry),
+        0.0,
+        false,
+        true,
+        (right, y + ry),
+    );
+    builder.line_to(right, bottom - ry);
+    arc_to(
+        &mut builder,
+        (right, bottom - ry),
+        (rx, ry),
+        0.0,
+        false,
+        true,
+        (right - rx, bottom),
+    );
+    builder.line_to(x + rx, bottom);
+    arc_to(
+        &mut builder,
+        (x + rx, bottom),
+        (rx, ry),
+        0.0,
+        false,
+        true,
+        (x, bottom - ry),
+    );
+    builder.line_to(x, y + ry);
+    arc_to(
+        &mut builder,
+        (x, y + ry),
+        (rx, ry),
+        0.0,
+        false,
+        true,
+        (x + rx, y),
+    );
+    builder.close();
+    builder.finish()
+}
+
+/// Parse the `d` attribute of a `path`. Parsing stops at the first error,
+/// keeping what was parsed up to it, like browsers do.
+fn parse_path_data(data: &str) -> Option<Path> {
+    let mut lexer = Lexer::new(data);
+    let mut builder = PathBuilder::new();
+    let mut command = None;
+    let mut current = (0.0, 0.0);
+    let mut subpath_start = (0.0, 0.0);
+    // The control point of the previous curve, for the smooth variants, which
+    // reflect it.
+    let mut last_cubic_control = None;
+    let mut last_quad_control = None;
+
+    while let Some(next) = lexer.command() {
+        let command = match next {
+            Some(next) => {
+                command = Some(next);
+                next
+            }
+            None => match command {
+                // Coordinates after a move are implicit lines.
+                Some(b'M') => b'L',
+                Some(b'm') => b'l',
+                // Only another command can follow a close, so anything else
+                // is an error.
+                Some(b'Z' | b'z') | None => break,
+                Some(command) => command,
+            },
+        };
+        let relative = command.is_ascii_lowercase();
+        let offset = |(x, y): (f32, f32)| {
+            if relative {
+                (current.0 + x, current.1 + y)
+            } else {
+                (x, y)
+            }
+        };
+        let reflect = |control: Option<(f32, f32)>| {
+            control.map_or(current, |(x, y): (f32, f32)| {
+                (2.0 * current.0 - x, 2.0 * current.1 - y)
+            })
+        };
+
+        let mut cubic_control = None;
+        let mut quad_control = None;
+        match command.to_ascii_uppercase() {
+            b'M' => {
+                let Some(point) = lexer.point() else { break };
+                current = offset(point);
+                subpath_start = current;
+                builder.move_to(current.0, current.1);
+            }
+            b'L' => {
+                let Some(point) = lexer.point() else { break };
+                current = offset(point);
+                builder.line_to(current.0, current.1);
+            }
+            b'H' => {
+                let Some(x) = lexer.number() else { break };
+                current.0 = if relative { current.0 + x } else { x };
+                builder.line_to(current.0, current.1);
+            }
+            b'V' => {
+                let Some(y) = lexer.number() else { break };
+                current.1 = if relative { current.1 + y } else { y };
+                builder.line_to(current.0, current.1);
+            }
+            b'C' => {
+                let (Some(first), Some(second), Some(end)) =
+                    (lexer.point(), lexer.point(), lexer.point())
+                else {
+                    break;
+                };
+                let (first, second, end) = (offset(first), offset(second), offset(end));
+                builder.cubic_to(first.0, first.1, second.0, second.1, end.0, end.1);
+                cubic_control = Some(second);
+                current = end;
+            }
+            b'S' => {
+                let (Some(second), Some(end)) = (lexer.point(), lexer.point()) else {
+                    break;
+                };
+                let first = reflect(last_cubic_control);
+                let (second, end) = (offset(second), offset(end));
+                builder.cubic_to(first.0, first.1, second.0, second.1, end.0, end.1);
+                cubic_control = Some(second);
+                current = end;
+            }
+            b'Q' => {
+                let (Some(control), Some(end)) = (lexer.point(), lexer.point()) else {
+                    break;
+                };
+                let (control, end) = (offset(control), offset(end));
+                builder.quad_to(control.0, control.1, end.0, end.1);
+                quad_control = Some(control);
+                current = end;
+            }
+            b'T' => {
+                let Some(end) = lexer.point() else { break };
+                let control = reflect(last_quad_control);
+                let end = offset(end);
+                builder.quad_to(control.0, control.1, end.0, end.1);
+                quad_control = Some(control);
+                current = end;
+            }
+            b'A' => {
+                let (Some(rx), Some(ry), Some(rotation), Some(large_arc), Some(sweep), Some(end)) = (
+                    lexer.number(),
+                    lexer.number(),
+                    lexer.number(),
+                    lexer.flag(),
+                    lexer.flag(),
+                    lexer.point(),
+                ) else {
+                    break;
+                };
+                let end = offset(end);
+                arc_to(
+                    &mut builder,
+                    current,
+                    (rx, ry),
+                    rotation,
+                    large_arc,
+                    sweep,
+                    end,
+                );
+                current = end;
+            }
+            b'Z' => {
+                builder.close();
+                current = subpath_start;
+            }
+            _ => break,
+        }
+        last_cubic_control = cubic_control;
+        last_quad_control = quad_control;
+    }
+
+    builder.finish()
+}
+
+/// Add an elliptical arc to the provided path, approximated with cubic
+/// curves. See the [SVG implementation notes].
+///
+/// [SVG implementation notes]: https://www.w3.org/TR/SVG11/implnote.html#ArcImplementationNotes
+fn arc_to(
+    builder: &mut PathBuilder,
+    from: (f32, f32),
+    (rx, ry): (f32, f32),
+    rotation: f32,
+    large_arc: bool,
+    sweep: bool,
+    to: (f32, f32),
+) {
+    if from == to {
+        return;
+    }
+    let (mut rx, mut ry) = (rx.abs(), ry.abs());
+    if rx == 0.0 || ry == 0.0 {
+        builder.line_to(to.0, to.1);
+        return;
+    }
+
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let half_dx = (from.0 - to.0) / 2.0;
+    let half_dy = (from.1 - to.1) / 2.0;
+    let x1 = cos * half_dx + sin * half_dy;
+    let y1 = -sin * half_dx + cos * half_dy;
+
+    // Radii too small to reach the end point are scaled up.
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut coefficient = (numerator / denominator).max(0.0).sqrt();
+    if large_arc == sweep {
+        coefficient = -coefficient;
+    }
+    let center_x1 = coefficient * rx * y1 / ry;
+    let center_y1 = -coefficient * ry * x1 / rx;
+    let center_x = cos * center_x1 - sin * center_y1 + (from.0 + to.0) / 2.0;
+    let center_y = sin * center_x1 + cos * center_y1 + (from.1 + to.1) / 2.0;
+
+    let angle =
+        |(ux, uy): (f32, f32), (vx, vy): (f32, f32)| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+    let start = ((x1 - center_x1) / rx, (y1 - center_y1) / ry);
+    let end = ((-x1 - center_x1) / rx, (-y1 - center_y1) / ry);
+    let start_angle = angle((1.0, 0.0), start);
+    let mut sweep_angle = angle(start, end);
+    if !sweep && sweep_angle > 0.0 {
+        sweep_angle -= TAU;
+    } else if sweep && sweep_angle < 0.0 {
+        sweep_angle += TAU;
+    }
+
+    // Every segment spans at most a quarter turn, to keep the approximation
+    // close.
+    let segments = (sweep_angle.abs() / FRAC_PI_2).ceil().max(1.0);
+    let step = sweep_angle / segments;
+    let handle = 4.0 / 3.0 * (step / 4.0).tan();
+    let map = |x: f32, y: f32| {
+        (
+            center_x + cos * rx * x - sin * ry * y,
+            center_y + sin * rx * x + cos * ry * y,
+        )
+    };
+    for segment in 0..segments as usize {
+        let (sin1, cos1) = (start_angle + step * segment as f32).sin_cos();
+        let (sin2, cos2) = (start_angle + step * (segment + 1) as f32).sin_cos();
+        let first = map(cos1 - handle * sin1, sin1 + handle * cos1);
+        let second = map(cos2 + handle * sin2, sin2 - handle * cos2);
+        let end = map(cos2, sin2);
+        builder.cubic_to(first.0, first.1, second.0, second.1, end.0, end.1);
+    }
+}
+
+/// Reads the numbers, flags and commands of SVG attributes, skipping the
+/// whitespace and commas between them.
+struct Lexer<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            bytes: text.as_bytes(),
+            position: 0,
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while self
+            .bytes
+            .get(self.position)
+            .is_some_and(|byte| byte.is_ascii_whitespace() || *byte == b',')
+        {
+            self.position += 1;
+        }
+    }
+
+    /// Read the next path command, if the next token is one, or `Some(None)`
+    /// if it's a number. Returns `None` at the end.
+    fn command(&mut self) -> Option<Option<u8>> {
+        self.skip_separators();
+        let byte = *self.bytes.get(self.position)?;
+        if byte.is_ascii_alphabetic() && !matches!(byte, b'e' | b'E') {
+            self.position += 1;
+            Some(Some(byte))
+        } else {
+            Some(None)
+        }
+    }
+
+    fn number(&mut self) -> Option<f32> {
+        self.skip_separators();
+        let start = self.position;
+        let mut end = start;
+        let at = |index: usize| self.bytes.get(index).copied();
+        if matches!(at(end), Some(b'+' | b'-')) {
+            end += 1;
+        }
+        while at(end).is_some_and(|byte| byte.is_ascii_digit()) {
+            end += 1;
+        }
+        // A second dot starts the next number, as in `0.5.5`.
+        if at(end) == Some(b'.') {
+            end += 1;
+            while at(end).is_some_and(|byte| byte.is_ascii_digit()) {
+                end += 1;
+            }
+        }
+        if matches!(at(end), Some(b'e' | b'E'))
+            && at(end + 1).is_some_and(|byte| byte.is_ascii_digit() || matches!(byte, b'+' | b'-'))
+        {
+            end += 2;
+            while at(end).is_some_and(|byte| byte.is_ascii_digit()) {
+                end += 1;
+            }
+        }
+
+        let number = std::str::from_utf8(&self.bytes[start..end])
+            .ok()?
+            .parse()
+            .ok()?;
+        self.position = end;
+        Some(number)
+    }
+
+    /// Read an arc flag, which doesn't need to be separated from what
+    /// follows it.
+    fn flag(&mut self) -> Option<bool> {
+        self.skip_separators();
+        let flag = match self.bytes.get(self.position)? {
+            b'0' => false,
+            b'1' => true,
+            _ => return None,
+        };
+        self.position += 1;
+        Some(flag)
+    }
+
+    fn point(&mut self) -> Option<(f32, f32)> {
+        Some((self.number()?, self.number()?))
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        tiny_skia::{PathSegment, Point},
+    };
+
+    fn segments(data: &str) -> Vec<PathSegment> {
+        parse_path_data(data)
+            .map(|path| path.segments().collect())
+            .unwrap_or_default()
+    }
+
+    fn move_to(x: f32, y: f32) -> PathSegment {
+        PathSegment::MoveTo(Point::from_xy(x, y))
+    }
+
+    fn line_to(x: f32, y: f32) -> PathSegment {
+        PathSegment::LineTo(Point::from_xy(x, y))
+    }
+
+    fn quad_to(x1: f32, y1: f32, x: f32, y: f32) -> PathSegment {
+        PathSegment::QuadTo(Point::from_xy(x1, y1), Point::from_xy(x, y))
+    }
+
+    fn cubic_to(x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) -> PathSegment {
+        PathSegment::CubicTo(
+            Point::from_xy(x1, y1),
+            Point::from_xy(x2, y2),
+            Point::from_xy(x, y),
+        )
+    }
+
+    /// Get whether the pixel at the provided position is drawn.
+    fn is_drawn(image: &egui::ColorImage, x: usize, y: usize) -> bool {
+        image.pixels[y * image.size[0] + x].a() > 0
+    }
+
+    #[test]
+    fn parse_lines() {
+        assert_eq!(
+            segments("M1 2 L3 4 H5 V6 Z"),
+            [
+                move_to(1.0, 2.0),
+                line_to(3.0, 4.0),
+                line_to(5.0, 4.0),
+                line_to(5.0, 6.0),
+                PathSegment::Close,
+            ],
+        );
+        assert_eq!(
+            segments("m1 2 l3 4 h5 v6 z m1 1 l1 0"),
+            [
+                move_to(1.0, 2.0),
+                line_to(4.0, 6.0),
+                line_to(9.0, 6.0),
+                line_to(9.0, 12.0),
+                PathSegment::Close,
+                // Relative to the start of the closed subpath.
+                move_to(2.0, 3.0),
+                line_to(3.0, 3.0),
+            ],
+        );
+    }
+
+    #[test]
+    fn parse_implicit_commands() {
+        // Coordinates after a move are lines, and other commands repeat.
+        assert_eq!(
+            segments("M0 0 1 1 2 2 H3 4 v1,1"),
+            [
+                move_to(0.0, 0.0),
+                line_to(1.0, 1.0),
+                line_to(2.0, 2.0),
+                line_to(3.0, 2.0),
+                line_to(4.0, 2.0),
+                line_to(4.0, 3.0),
+                line_to(4.0, 4.0),
+            ],
+        );
+        assert_eq!(segments("m1 1 1 1"), [move_to(1.0, 1.0), line_to(2.0, 2.0)],);
+        // Numbers don't need separators when they can't be confused.
+        assert_eq!(
+            segments("M.5.5L-1-1"),
+            [move_to(0.5, 0.5), line_to(-1.0, -1.0)],
+        );
+    }
+
+    #[test]
+    fn parse_curves() {
+        assert_eq!(
+            segments("M0 0 C1 0 2 1 2 2 S3 4 4 4"),
+            [
+                move_to(0.0, 0.0),
+                cubic_to(1.0, 0.0, 2.0, 1.0, 2.0, 2.0),
+                // The first control point reflects the previous second one.
+                cubic_to(2.0, 3.0, 3.0, 4.0, 4.0, 4.0),
+            ],
+        );
+        assert_eq!(
+            segments("M1 1 c1 0 2 1 2 2 s1 2 2 2"),
+            [
+                move_to(1.0, 1.0),
+                cubic_to(2.0, 1.0, 3.0, 2.0, 3.0, 3.0),
+                cubic_to(3.0, 4.0, 4.0, 5.0, 5.0, 5.0),
+            ],
+        );
+        assert_eq!(
+            segments("M0 0 Q1 0 2 2 T4 4 q1 0 1 1 t1 1"),
+            [
+                move_to(0.0, 0.0),
+                quad_to(1.0, 0.0, 2.0, 2.0),
+                quad_to(3.0, 4.0, 4.0, 4.0),
+                quad_to(5.0, 4.0, 5.0, 5.0),
+                quad_to(5.0, 6.0, 6.0, 6.0),
+            ],
+        );
+        // Without a previous curve of the same kind, the control point is
+        // the current point.
+        assert_eq!(
+            segments("M0 0 L1 1 S2 2 3 3 T4 4"),
+            [
+                move_to(0.0, 0.0),
+                line_to(1.0, 1.0),
+                cubic_to(1.0, 1.0, 2.0, 2.0, 3.0, 3.0),
+                quad_to(3.0, 3.0, 4.0, 4.0),
+            ],
+        );
+    }
+
+    #[test]
+    fn parse_arcs() {
+        let is_near =
+            |point: Point, x: f32, y: f32| (point.x - x).abs() < 1e-3 && (point.y - y).abs() < 1e-3;
+        let path = parse_path_data("M0 10 A10 10 0 0 1 10 0 a10 10 0 1,1 10 10").unwrap();
+        let arcs = path.segments().collect::<Vec<_>>();
+        assert_eq!(arcs[0], move_to(0.0, 10.0));
+        // The quarter turn is a single curve, and the rest of the circle is
+        // split into quarter turns.
+        assert_eq!(arcs.len(), 5);
+        let PathSegment::CubicTo(_, _, end) = arcs[1] else {
+            panic!("arcs should be drawn with cubic curves");
+        };
+        assert!(is_near(end, 10.0, 0.0));
+        let PathSegment::CubicTo(_, _, end) = arcs[4] else {
+            panic!("arcs should be drawn with cubic curves");
+        };
+        assert!(is_near(end, 20.0, 10.0));
+        // The large arc goes around the far side of its circle.
+        let bounds = path.bounds();
+        assert!((bounds.right() - 30.0).abs() < 0.1);
+        assert!((bounds.top() + 10.0).abs() < 0.1);
+
+        // Flags don't need separators, and arcs without a radius are lines.
+        assert_eq!(
+            segments("M0 0 A0 5 0 1110 10"),
+            [move_to(0.0, 0.0), line_to(10.0, 10.0)],
+        );
+    }
+
+    #[test]
+    fn stop_at_malformed_data() {
+        // What was parsed before the error is kept.
+        assert_eq!(
+            segments("M0 0 L1 1 Z 5"),
+            [move_to(0.0, 0.0), line_to(1.0, 1.0), PathSegment::Close],
+        );
+        assert_eq!(
+            segments("M0 0 L1 1 X 2 2"),
+            [move_to(0.0, 0.0), line_to(1.0, 1.0)],
+        );
+        assert_eq!(
+            segments("M0 0 L1 1 L2"),
+            [move_to(0.0, 0.0), line_to(1.0, 1.0)],
+        );
+        // Arc flags can only be `0` or `1`.
+        assert_eq!(
+            segments("M0 0 L1 1 A1 1 0 2 0 2 2"),
+            [move_to(0.0, 0.0), line_to(1.0, 1.0)],
+        );
+        assert!(segments("5 5 L1 1").is_empty());
+        assert!(segments("").is_empty());
+    }
+
+    #[test]
+    fn fit_view_box() {
+        let rasterize = |preserve_aspect_ratio: &str| {
+            let document = format!(
+                r#"<svg width="20" height="10" viewBox="0 0 10 10" preserveAspectRatio="{preserve_aspect_ratio}">
+                    <rect width="10" height="3" fill="red"/>
+                </svg>"#,
+            );
+            Svg::parse(document.as_bytes())
+                .unwrap()
+                .rasterize(20, 10, false)
+        };
+
+        // The view box is scaled uniformly, and centered.
+        let image = rasterize("");
+        assert!(!is_drawn(&image, 2, 2));
+        assert!(is_drawn(&image, 10, 2));
+        assert!(!is_drawn(&image, 10, 7));
+        assert!(!is_drawn(&image, 17, 2));
+
+        let image = rasterize("xMinYMin meet");
+        assert!(is_drawn(&image, 2, 2));
+        assert!(!is_drawn(&image, 17, 2));
+
+        let image = rasterize("xMaxYMax");
+        assert!(!is_drawn(&image, 2, 2));
+        assert!(is_drawn(&image, 17, 2));
+
+        // Slicing fills the viewport, cutting off the top and bottom.
+        let image = rasterize("xMidYMid slice");
+        assert!(is_drawn(&image, 2, 0));
+        assert!(is_drawn(&image, 17, 0));
+        assert!(!is_drawn(&image, 10, 2));
+
+        let image = rasterize("none");
+        assert!(is_drawn(&image, 2, 2));
+        assert!(is_drawn(&image, 17, 2));
+        assert!(!is_drawn(&image, 10, 4));
+    }
+}
@@ -0,0 +1,157 @@
+//! # Virtual Filesystem
+//!
+//! See [`Vfs`] for details.
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+
+
+/// The files a program's assets are read from.
+///
+/// Paths are relative to the program's asset directory, see
+/// [`ProgramConfig::assets`](crate::program_config::ProgramConfig::assets).
+/// Files the program embedded with
+/// [`ViewContext::embed_asset`](base::ViewContext::embed_asset) take
+/// precedence over the ones on disk.
+pub struct Vfs {
+    root: PathBuf,
+    /// The content of the embedded files, by path. The content is copied out
+    /// of the program, as it goes away when the program is reloaded.
+    embedded: HashMap<String, Arc<[u8]>>,
+}
+
+/// A file of a [`Vfs`], which may not exist.
+#[derive(Clone, Debug)]
+pub enum VfsFile {
+    Embedded(Arc<[u8]>),
+    Disk(PathBuf),
+}
+
+impl Vfs {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            embedded: HashMap::new(),
+        }
+    }
+
+    /// Change the directory paths are relative to, returning whether it
+    /// changed.
+    pub fn set_root(&mut self, root: PathBuf) -> bool {
+        if self.root == root {
+            return false;
+        }
+        self.root = root;
+        true
+    }
+
+    /// Embed a file with the provided content, returning whether its content
+    /// changed.
+    pub fn embed(&mut self, path: &str, content: &[u8]) -> bool {
+        let path = normalize(path);
+        if self
+            .embedded
+            .get(&path)
+            .is_some_and(|embedded| **embedded == *content)
+        {
+            return false;
+        }
+        self.embedded.insert(path, content.into());
+        true
+    }
+
+    pub fn open(&self, path: &str) -> VfsFile {
+        let path = normalize(path);
+        match self.embedded.get(&path) {
+            Some(content) => VfsFile::Embedded(content.clone()),
+            None => VfsFile::Disk(self.root.join(path)),
+        }
+    }
+}
+
+impl VfsFile {
+    /// The path of the file on disk, unless it's embedded.
+    pub fn disk_path(&self) -> Option<&Path> {
+        match self {
+            Self::Embedded(_) => None,
+            Self::Disk(path) => Some(path),
+        }
+    }
+
+    pub fn read(&self) -> io::Result<Cow<'_, [u8]>> {
+        match self {
+            Self::Embedded(content) => Ok(Cow::Borrowed(content)),
+            Self::Disk(path) => std::fs::read(path).map(Cow::Owned),
+        }
+    }
+}
+
+/// Make equivalent relative paths the same, so that `./icons/x.svg`,
+/// `icons//x.svg` and `icons/../icons/x.svg` all become `icons/x.svg`.
+///
+/// Leading `..` components, which leave the asset directory, are kept, as
+/// they can't be resolved without the file system.
+pub fn normalize(path: &str) -> String {
+    let mut components = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." if components.last().is_some_and(|last| *last != "..") => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+
+    let normalized = components.join("/");
+    if path.starts_with('/') {
+        format!("/{normalized}")
+    } else {
+        normalized
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_paths() {
+        for (path, normalized) in [
+            ("icons/x.svg", "icons/x.svg"),
+            ("./icons/x.svg", "icons/x.svg"),
+            ("icons//x.svg", "icons/x.svg"),
+            ("icons/./x.svg", "icons/x.svg"),
+            ("icons/../icons/x.svg", "icons/x.svg"),
+            ("icons/sub/../../x.svg", "x.svg"),
+            ("../shared/x.svg", "../shared/x.svg"),
+            ("icons/../../x.svg", "../x.svg"),
+            ("/usr/share/x.svg", "/usr/share/x.svg"),
+        ] {
+            assert_eq!(normalize(path), normalized, "normalizing {path:?}");
+        }
+    }
+
+    #[test]
+    fn embedded_files_match_equivalent_paths() {
+        let mut vfs = Vfs::new(PathBuf::from("assets"));
+        assert!(vfs.embed("./icons/x.svg", b"<svg/>"));
+        assert!(!vfs.embed("icons//x.svg", b"<svg/>"));
+        assert!(matches!(
+            vfs.open("icons/../icons/x.svg"),
+            VfsFile::Embedded(_)
+        ));
+        assert_eq!(
+            vfs.open("./icons/y.svg").disk_path(),
+            Some(Path::new("assets/icons/y.svg")),
+        );
+    }
+}
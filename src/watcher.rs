@@ -0,0 +1,410 @@
+//! # File Watcher
+
+use std::{
+    collections::HashMap,
+    ffi::{CString, OsStr},
+    io,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::ffi::OsStrExt,
+    },
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver, Sender},
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use eframe::egui;
+
+
+
+/// How long a file must go without being modified before it's reported, so
+/// that editors writing a file in several steps only trigger one rebuild.
+const DEBOUNCE: Duration = Duration::from_millis(150);
+/// How often the watched directories are scanned for modifications when
+/// inotify isn't available.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Watches directories for modified files using inotify, or by periodically
+/// comparing modification times if inotify couldn't be started.
+///
+/// Directories are watched instead of files, because many editors save by
+/// writing a new file and renaming it over the old one, which would silently
+/// end a watch on the old file.
+pub struct FileWatcher {
+    backend: Backend,
+    /// Modified files which haven't been reported yet, along with when they
+    /// were last modified.
+    pending: HashMap<PathBuf, Instant>,
+    egui_context: egui::Context,
+}
+
+enum Backend {
+    Inotify {
+        fd: Arc<OwnedFd>,
+        directories: Arc<Mutex<WatchedDirectories>>,
+        events: Receiver<PathBuf>,
+    },
+    Polling(Poller),
+}
+
+impl FileWatcher {
+    pub fn new(egui_context: egui::Context) -> Self {
+        let backend = match Backend::inotify(egui_context.clone()) {
+            Ok(backend) => backend,
+            Err(error) => {
+                println!("ERROR: failed to start inotify, polling files instead: {error}");
+                Backend::Polling(Poller::default())
+            }
+        };
+
+        Self {
+            backend,
+            pending: HashMap::new(),
+            egui_context,
+        }
+    }
+
+    /// Watch the directory containing the provided file, if it isn't already
+    /// watched.
+    pub fn watch_file(&mut self, path: &Path) {
+        if let Some(directory) = path.parent() {
+            self.watch_directory(directory);
+        }
+    }
+
+    pub fn watch_directory(&mut self, directory: &Path) {
+        self.backend.watch(directory, false);
+    }
+
+    /// Watch the provided directory along with all of its subdirectories,
+    /// including those created later on.
+    pub fn watch_directory_recursive(&mut self, directory: &Path) {
+        self.backend.watch(directory, true);
+    }
+
+    /// Get the files which were modified and have since settled.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        match &mut self.backend {
+            Backend::Inotify { events, .. } => {
+                for path in events.try_iter() {
+                    self.pending.insert(path, now);
+                }
+            }
+            Backend::Polling(poller) => {
+                self.egui_context.request_repaint_after(POLL_INTERVAL);
+                if now.duration_since(poller.last_scan) >= POLL_INTERVAL {
+                    poller.last_scan = now;
+                    for path in poller.scan() {
+                        self.pending.insert(path, now);
+                    }
+                }
+            }
+        }
+
+        let mut settled = Vec::new();
+        self.pending.retain(|path, modified| {
+            if now.duration_since(*modified) >= DEBOUNCE {
+                settled.push(path.clone());
+                false
+            } else {
+                true
+            }
+        });
+        if !self.pending.is_empty() {
+            self.egui_context.request_repaint_after(DEBOUNCE);
+        }
+
+        settled
+    }
+}
+
+impl Backend {
+    fn inotify(egui_context: egui::Context) -> io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = Arc::new(unsafe { OwnedFd::from_raw_fd(fd) });
+        let directories = Arc::new(Mutex::new(WatchedDirectories::default()));
+        let (sender, events) = mpsc::channel();
+
+        // The thread lives as long as the process, blocked reading events.
+        std::thread::spawn({
+            let fd = fd.clone();
+            let directories = directories.clone();
+            move || {
+                let mut buffer = vec![0_u8; 4096];
+                loop {
+                    let read = unsafe {
+                        libc::read(fd.as_raw_fd(), buffer.as_mut_ptr().cast(), buffer.len())
+                    };
+                    if read <= 0 {
+                        let error = io::Error::last_os_error();
+                        // Reads interrupted by a signal can be retried.
+                        if read < 0 && error.kind() == io::ErrorKind::Interrupted {
+                            continue;
+                        }
+                        println!("ERROR: file watcher stopped: {error}");
+                        return;
+                    }
+
+                    let mut directories = directories.lock().unwrap();
+                    for (wd, mask, name) in parse_events(&buffer[..read as usize]) {
+                        let Some(watched) = directories.by_wd.get(&wd) else {
+                            continue;
+                        };
+                        let path = watched.path.join(name);
+
+                        // New subdirectories of recursively watched directories are
+                        // watched too. Files may have been written to them before the
+                        // watch was added, so those are reported straight away.
+                        if mask & libc::IN_ISDIR != 0 {
+                            if watched.recursive {
+                                directories.add_inotify(&fd, &path, true, Some(&sender));
+                            }
+                            continue;
+                        }
+
+                        if sender.send(path).is_err() {
+                            return;
+                        }
+                    }
+                    egui_context.request_repaint();
+                }
+            }
+        });
+
+        Ok(Self::Inotify {
+            fd,
+            directories,
+            events,
+        })
+    }
+
+    fn watch(&mut self, directory: &Path, recursive: bool) {
+        match self {
+            Self::Inotify {
+                fd, directories, ..
+            } => {
+                directories
+                    .lock()
+                    .unwrap()
+                    .add_inotify(fd, directory, recursive, None);
+            }
+            Self::Polling(poller) => poller.watch(directory, recursive),
+        }
+    }
+}
+
+/// The directories watched through inotify.
+#[derive(Default)]
+struct WatchedDirectories {
+    by_wd: HashMap<i32, WatchedDirectory>,
+}
+
+struct WatchedDirectory {
+    path: PathBuf,
+    /// Whether new subdirectories of the directory are watched as well.
+    recursive: bool,
+}
+
+impl WatchedDirectories {
+    /// Watch the provided directory, and its subdirectories if `recursive` is
+    /// set. The files already in the directories are sent to `existing`, if
+    /// provided.
+    fn add_inotify(
+        &mut self,
+        fd: &OwnedFd,
+        directory: &Path,
+        recursive: bool,
+        existing: Option<&Sender<PathBuf>>,
+    ) {
+        let Ok(directory) = directory.canonicalize() else {
+            return;
+        };
+        let watched = self
+            .by_wd
+            .values_mut()
+            .find(|watched| watched.path == directory);
+        match watched {
+            // Directories watched recursively before are already covered.
+            Some(watched) if watched.recursive || !recursive => return,
+            Some(watched) => watched.recursive = true,
+            None => {
+                let Ok(path) = CString::new(directory.as_os_str().as_bytes()) else {
+                    return;
+                };
+                // Subdirectories are only created, or moved in, as a whole.
+                let mut mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO;
+                if recursive {
+                    mask |= libc::IN_CREATE;
+                }
+                let wd = unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), mask) };
+                if wd < 0 {
+                    println!(
+                        "ERROR: failed to watch {}: {}",
+                        directory.display(),
+                        io::Error::last_os_error(),
+                    );
+                    return;
+                }
+                self.by_wd.insert(
+                    wd,
+                    WatchedDirectory {
+                        path: directory.clone(),
+                        recursive,
+                    },
+                );
+            }
+        }
+
+        let Ok(entries) = std::fs::read_dir(&directory) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if recursive {
+                    self.add_inotify(fd, &entry.path(), true, existing);
+                }
+            } else if let Some(existing) = existing {
+                let _ = existing.send(entry.path());
+            }
+        }
+    }
+}
+
+/// Finds modified files by comparing their modification times, for when
+/// inotify isn't available.
+struct Poller {
+    /// The watched directories, and whether their new subdirectories are
+    /// watched as well.
+    directories: HashMap<PathBuf, bool>,
+    /// The modification times of the files in the watched directories, as of
+    /// the last scan.
+    modified: HashMap<PathBuf, SystemTime>,
+    last_scan: Instant,
+}
+
+impl Default for Poller {
+    fn default() -> Self {
+        Self {
+            directories: HashMap::new(),
+            modified: HashMap::new(),
+            last_scan: Instant::now(),
+        }
+    }
+}
+
+impl Poller {
+    fn watch(&mut self, directory: &Path, recursive: bool) {
+        let Ok(directory) = directory.canonicalize() else {
+            return;
+        };
+        match self.directories.get_mut(&directory) {
+            Some(watched) if *watched || !recursive => return,
+            Some(watched) => *watched = true,
+            None => {
+                self.directories.insert(directory.clone(), recursive);
+            }
+        }
+
+        // Only files modified from now on are reported.
+        let mut subdirectories = Vec::new();
+        self.scan_directory(&directory, recursive, &mut Vec::new(), &mut subdirectories);
+        for subdirectory in subdirectories {
+            self.watch(&subdirectory, true);
+        }
+    }
+
+    /// Get the files which were created or modified since the last scan.
+    fn scan(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        let mut subdirectories = Vec::new();
+        let directories = self.directories.clone();
+        for (directory, recursive) in directories {
+            self.scan_directory(&directory, recursive, &mut changed, &mut subdirectories);
+        }
+
+        // Files may have been written to new subdirectories before they were
+        // found, so everything in them is reported.
+        while let Some(subdirectory) = subdirectories.pop() {
+            if self.directories.contains_key(&subdirectory) {
+                continue;
+            }
+            self.directories.insert(subdirectory.clone(), true);
+            self.scan_directory(&subdirectory, true, &mut changed, &mut subdirectories);
+        }
+
+        changed
+    }
+
+    /// Record the modification times of the files in the provided directory,
+    /// collecting the files which changed since they were last recorded, and
+    /// the subdirectories if `recursive` is set.
+    fn scan_directory(
+        &mut self,
+        directory: &Path,
+        recursive: bool,
+        changed: &mut Vec<PathBuf>,
+        subdirectories: &mut Vec<PathBuf>,
+    ) {
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let path = entry.path();
+            if metadata.is_dir() {
+                if recursive {
+                    subdirectories.push(path);
+                }
+                continue;
+            }
+
+            let Ok(modified) = metadata.modified() else {
+                continue;
+            };
+            if self.modified.insert(path.clone(), modified) != Some(modified) {
+                changed.push(path);
+            }
+        }
+    }
+}
+
+/// Read the watch descriptors, masks and file names out of a buffer of
+/// `inotify_event`s.
+fn parse_events(mut buffer: &[u8]) -> Vec<(i32, u32, &OsStr)> {
+    const HEADER_SIZE: usize = size_of::<libc::inotify_event>();
+
+    let mut events = Vec::new();
+    while buffer.len() >= HEADER_SIZE {
+        let event = unsafe {
+            buffer
+                .as_ptr()
+                .cast::<libc::inotify_event>()
+                .read_unaligned()
+        };
+        let end = (HEADER_SIZE + event.len as usize).min(buffer.len());
+        // The name is padded with nul bytes.
+        let name = &buffer[HEADER_SIZE..end];
+        let name = &name[..name
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(name.len())];
+        if !name.is_empty() {
+            events.push((event.wd, event.mask, OsStr::from_bytes(name)));
+        }
+        buffer = &buffer[end..];
+    }
+
+    events
+}
@@ -0,0 +1,25 @@
+//! # New Program
+//!
+//! Edit this program and press "Confirm" to recompile it.
+
+extern crate base;
+
+use base::*;
+
+
+base::export_program!(view: view);
+
+fn view(_context: &mut dyn ViewContext) -> Box<dyn Object> {
+    Box::new(
+        Flex::column()
+            .gap(10.0)
+            .main_align(AxisAlignment::Center)
+            .with(Label::new("Hello!").font_size(40.0), 0.0)
+            .with(
+                Label::new("This program was created from a template.")
+                    .font_size(18.0)
+                    .color(Rgba::rgb(0xaa, 0xaa, 0xad)),
+                0.0,
+            ),
+    )
+}